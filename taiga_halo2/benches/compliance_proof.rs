@@ -72,7 +72,7 @@ fn bench_compliance_proof(name: &str, c: &mut Criterion) {
             &mut rng,
         )
     };
-    let (compliance, compliance_circuit) = compliance_info.build();
+    let (compliance, compliance_circuit) = compliance_info.build().unwrap();
     let params = SETUP_PARAMS_MAP
         .get(&COMPLIANCE_CIRCUIT_PARAMS_SIZE)
         .unwrap();
@@ -73,7 +73,7 @@ fn bench_vp_proof(name: &str, c: &mut Criterion) {
     let empty_circuit: TrivialValidityPredicateCircuit = Default::default();
     let vk = keygen_vk(params, &empty_circuit).expect("keygen_vk should not fail");
     let pk = keygen_pk(params, vk, &empty_circuit).expect("keygen_pk should not fail");
-    let public_inputs = vp_circuit.get_public_inputs(&mut rng);
+    let public_inputs = vp_circuit.get_public_inputs(&mut rng).unwrap();
 
     // Prover bench
     let prover_name = name.to_string() + "-prover";
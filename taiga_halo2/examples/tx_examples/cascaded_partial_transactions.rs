@@ -211,14 +211,23 @@ pub fn create_transaction<R: RngCore + CryptoRng>(mut rng: R) -> Transaction {
     // Create the final transaction
     let shielded_tx_bundle = ShieldedPartialTxBundle::new(vec![ptx_1, ptx_2]);
     let transparent_ptx_bundle = TransparentPartialTxBundle::default();
-    Transaction::build(&mut rng, shielded_tx_bundle, transparent_ptx_bundle).unwrap()
+    Transaction::build(
+        &mut rng,
+        shielded_tx_bundle,
+        transparent_ptx_bundle,
+        vec![],
+        0,
+        None,
+    )
+    .unwrap()
 }
 
 #[test]
 fn test_cascade_ptxs() {
     use rand::rngs::OsRng;
+    use taiga_halo2::transaction::ExecutionContext;
 
     let mut rng = OsRng;
     let tx = create_transaction(&mut rng);
-    tx.execute().unwrap();
+    tx.execute(&ExecutionContext::default()).unwrap();
 }
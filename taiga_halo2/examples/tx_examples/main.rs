@@ -5,17 +5,18 @@ mod token_swap_with_intent;
 mod token_swap_without_intent;
 fn main() {
     use rand::rngs::OsRng;
+    use taiga_halo2::transaction::ExecutionContext;
 
     let rng = OsRng;
     let tx = token_swap_without_intent::create_token_swap_transaction(rng);
-    tx.execute().unwrap();
+    tx.execute(&ExecutionContext::default()).unwrap();
 
     let tx = token_swap_with_intent::create_token_swap_intent_transaction(rng);
-    tx.execute().unwrap();
+    tx.execute(&ExecutionContext::default()).unwrap();
 
     let tx = partial_fulfillment_token_swap::create_token_swap_transaction(rng);
-    tx.execute().unwrap();
+    tx.execute(&ExecutionContext::default()).unwrap();
 
     let tx = cascaded_partial_transactions::create_transaction(rng);
-    tx.execute().unwrap();
+    tx.execute(&ExecutionContext::default()).unwrap();
 }
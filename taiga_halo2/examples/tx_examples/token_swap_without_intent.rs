@@ -69,14 +69,23 @@ pub fn create_token_swap_transaction<R: RngCore + CryptoRng>(mut rng: R) -> Tran
     // Solver creates the final transaction
     let shielded_tx_bundle = ShieldedPartialTxBundle::new(vec![alice_ptx, bob_ptx, carol_ptx]);
     let transparent_ptx_bundle = TransparentPartialTxBundle::default();
-    Transaction::build(&mut rng, shielded_tx_bundle, transparent_ptx_bundle).unwrap()
+    Transaction::build(
+        &mut rng,
+        shielded_tx_bundle,
+        transparent_ptx_bundle,
+        vec![],
+        0,
+        None,
+    )
+    .unwrap()
 }
 
 #[test]
 fn test_basic_swap_tx() {
     use rand::rngs::OsRng;
+    use taiga_halo2::transaction::ExecutionContext;
 
     let mut rng = OsRng;
     let tx = create_token_swap_transaction(&mut rng);
-    tx.execute().unwrap();
+    tx.execute(&ExecutionContext::default()).unwrap();
 }
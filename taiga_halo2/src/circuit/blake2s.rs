@@ -1,9 +1,7 @@
 use super::gadgets::assign_free_advice;
 use crate::circuit::gadgets::assign_free_constant;
 use crate::constant::{
-    VP_CIRCUIT_FIRST_DYNAMIC_VP_CM_1, VP_CIRCUIT_FIRST_DYNAMIC_VP_CM_2,
-    VP_CIRCUIT_SECOND_DYNAMIC_VP_CM_1, VP_CIRCUIT_SECOND_DYNAMIC_VP_CM_2,
-    VP_COMMITMENT_PERSONALIZATION,
+    VP_CIRCUIT_DYNAMIC_VP_CM_1, VP_CIRCUIT_DYNAMIC_VP_CM_2, VP_COMMITMENT_PERSONALIZATION,
 };
 use crate::vp_commitment::ValidityPredicateCommitment;
 use byteorder::{ByteOrder, LittleEndian};
@@ -28,7 +26,8 @@ pub fn vp_commitment_gadget<F: PrimeField>(
     blake2s_chip.encode_result(layouter, &hash)
 }
 
-pub fn publicize_default_dynamic_vp_commitments<F: PrimeField>(
+// Publicize the commitment to an empty list of dynamic vps
+pub fn publicize_default_dynamic_vp_commitment<F: PrimeField>(
     layouter: &mut impl Layouter<F>,
     advice: Column<Advice>,
     instances: Column<Instance>,
@@ -45,10 +44,8 @@ pub fn publicize_default_dynamic_vp_commitments<F: PrimeField>(
         Value::known(vp_cm_fields[1]),
     )?;
 
-    layouter.constrain_instance(vp_cm_1.cell(), instances, VP_CIRCUIT_FIRST_DYNAMIC_VP_CM_1)?;
-    layouter.constrain_instance(vp_cm_2.cell(), instances, VP_CIRCUIT_FIRST_DYNAMIC_VP_CM_2)?;
-    layouter.constrain_instance(vp_cm_1.cell(), instances, VP_CIRCUIT_SECOND_DYNAMIC_VP_CM_1)?;
-    layouter.constrain_instance(vp_cm_2.cell(), instances, VP_CIRCUIT_SECOND_DYNAMIC_VP_CM_2)?;
+    layouter.constrain_instance(vp_cm_1.cell(), instances, VP_CIRCUIT_DYNAMIC_VP_CM_1)?;
+    layouter.constrain_instance(vp_cm_2.cell(), instances, VP_CIRCUIT_DYNAMIC_VP_CM_2)?;
 
     Ok(())
 }
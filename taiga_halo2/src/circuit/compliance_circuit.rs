@@ -341,7 +341,7 @@ fn test_halo2_compliance_circuit() {
 
     let mut rng = OsRng;
     let compliance_info = random_compliance_info(&mut rng);
-    let (compliance, compliance_circuit) = compliance_info.build();
+    let (compliance, compliance_circuit) = compliance_info.build().unwrap();
     let instances = vec![compliance.to_instance()];
     let prover = MockProver::<pallas::Base>::run(
         COMPLIANCE_CIRCUIT_PARAMS_SIZE,
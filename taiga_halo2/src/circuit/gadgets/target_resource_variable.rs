@@ -119,53 +119,37 @@ impl GetOwnedResourceVariableConfig {
                 })
                 .collect();
 
+            let is_zero_checks = poly_vec.into_iter().enumerate().map(|(i, poly)| {
+                (
+                    format!("nf_or_cm_minus_owned_resource_id_is_zero check{i}"),
+                    poly,
+                )
+            });
+            let owned_resource_variable_checks = nf_or_cm_minus_owned_resource_id_is_zero_vec
+                .clone()
+                .into_iter()
+                .zip(target_variable_vec)
+                .enumerate()
+                .map(|(i, (is_zero, target_variable))| {
+                    (
+                        format!("owned_resource_variable check{i}"),
+                        is_zero * (owned_resource_variable.clone() - target_variable),
+                    )
+                });
+            let exists_check = nf_or_cm_minus_owned_resource_id_is_zero_vec
+                .into_iter()
+                .reduce(|acc, is_zero| acc * is_zero)
+                .unwrap();
+
             Constraints::with_selector(
                 q_get_owned_resource_variable,
-                [
-                    (
-                        "nf_or_cm_minus_owned_resource_id_is_zero check0",
-                        poly_vec[0].clone(),
-                    ),
-                    (
-                        "nf_or_cm_minus_owned_resource_id_is_zero check1",
-                        poly_vec[1].clone(),
-                    ),
-                    (
-                        "nf_or_cm_minus_owned_resource_id_is_zero check2",
-                        poly_vec[2].clone(),
-                    ),
-                    (
-                        "nf_or_cm_minus_owned_resource_id_is_zero check3",
-                        poly_vec[3].clone(),
-                    ),
-                    (
-                        "owned_resource_variable check0",
-                        nf_or_cm_minus_owned_resource_id_is_zero_vec[0].clone()
-                            * (owned_resource_variable.clone() - target_variable_vec[0].clone()),
-                    ),
-                    (
-                        "owned_resource_variable check1",
-                        nf_or_cm_minus_owned_resource_id_is_zero_vec[1].clone()
-                            * (owned_resource_variable.clone() - target_variable_vec[1].clone()),
-                    ),
-                    (
-                        "owned_resource_variable check2",
-                        nf_or_cm_minus_owned_resource_id_is_zero_vec[2].clone()
-                            * (owned_resource_variable.clone() - target_variable_vec[2].clone()),
-                    ),
-                    (
-                        "owned_resource_variable check3",
-                        nf_or_cm_minus_owned_resource_id_is_zero_vec[3].clone()
-                            * (owned_resource_variable - target_variable_vec[3].clone()),
-                    ),
-                    (
-                        "owned_resource_id exists in the resources",
-                        nf_or_cm_minus_owned_resource_id_is_zero_vec[0].clone()
-                            * nf_or_cm_minus_owned_resource_id_is_zero_vec[1].clone()
-                            * nf_or_cm_minus_owned_resource_id_is_zero_vec[2].clone()
-                            * nf_or_cm_minus_owned_resource_id_is_zero_vec[3].clone(),
-                    ),
-                ],
+                is_zero_checks
+                    .chain(owned_resource_variable_checks)
+                    .chain(std::iter::once((
+                        "owned_resource_id exists in the resources".to_string(),
+                        exists_check,
+                    )))
+                    .collect::<Vec<_>>(),
             )
         });
     }
@@ -256,25 +240,37 @@ impl GetIsInputResourceFlagConfig {
             let q_get_is_input_resource_flag = meta.query_selector(self.q_get_is_input_resource_flag);
             let owned_resource_id = meta.query_advice(self.owned_resource_id, Rotation::cur());
             let is_input_resource_flag = meta.query_advice(self.owned_resource_id, Rotation::next());
-            let input_resource_nf_1 = meta.query_advice(self.input_resource_nf, Rotation::cur());
-            let input_resource_nf_2 = meta.query_advice(self.input_resource_nf, Rotation::next());
-            let output_resource_cm_1 =
-                meta.query_advice(self.output_resource_cm, Rotation::cur());
-            let output_resource_cm_2 =
-                meta.query_advice(self.output_resource_cm, Rotation::next());
-                let one = Expression::Constant(pallas::Base::one());
+            // The input resource nfs and output resource cms are in the NUM_RESOURCE rows from the current one.
+            let input_resource_nfs: Vec<_> = (0..NUM_RESOURCE)
+                .map(|i| meta.query_advice(self.input_resource_nf, Rotation(i as i32)))
+                .collect();
+            let output_resource_cms: Vec<_> = (0..NUM_RESOURCE)
+                .map(|i| meta.query_advice(self.output_resource_cm, Rotation(i as i32)))
+                .collect();
+            let one = Expression::Constant(pallas::Base::one());
+
+            let input_check = input_resource_nfs
+                .into_iter()
+                .fold(is_input_resource_flag.clone(), |acc, nf| {
+                    acc * (owned_resource_id.clone() - nf)
+                });
+            let output_check = output_resource_cms
+                .into_iter()
+                .fold(is_input_resource_flag.clone() - one, |acc, cm| {
+                    acc * (owned_resource_id.clone() - cm)
+                });
 
             Constraints::with_selector(
                 q_get_is_input_resource_flag,
                 [
-                    ("bool_check is_input_resource_flag", bool_check(is_input_resource_flag.clone())),
+                    ("bool_check is_input_resource_flag", bool_check(is_input_resource_flag)),
                     (
-                        "if is_input_resource_flag, then owned_resource_id == input_resource_nf_1 or owned_resource_id == input_resource_nf_2",
-                        is_input_resource_flag.clone() * (owned_resource_id.clone() - input_resource_nf_1) * (owned_resource_id.clone() - input_resource_nf_2),
+                        "if is_input_resource_flag, then owned_resource_id is one of the input_resource_nfs",
+                        input_check,
                     ),
                     (
-                        "if not is_input_resource_flag, then owned_resource_id == output_resource_cm_1 or owned_resource_id == output_resource_cm_2",
-                        (is_input_resource_flag - one) * (owned_resource_id.clone() - output_resource_cm_1) * (owned_resource_id - output_resource_cm_2),
+                        "if not is_input_resource_flag, then owned_resource_id is one of the output_resource_cms",
+                        output_check,
                     ),
                 ],
             )
@@ -299,47 +295,41 @@ impl GetIsInputResourceFlagConfig {
             self.owned_resource_id,
             offset,
         )?;
-        input_resource_nfs[0].copy_advice(
-            || "input_resource_nf 1",
-            region,
-            self.input_resource_nf,
-            offset,
-        )?;
-        input_resource_nfs[1].copy_advice(
-            || "input_resource_nf 2",
-            region,
-            self.input_resource_nf,
-            offset + 1,
-        )?;
-        output_resource_cms[0].copy_advice(
-            || "output_resource_cm 1",
-            region,
-            self.output_resource_cm,
-            offset,
-        )?;
-        output_resource_cms[1].copy_advice(
-            || "output_resource_cm 2",
-            region,
-            self.output_resource_cm,
-            offset + 1,
-        )?;
+        for (i, (nf, cm)) in input_resource_nfs
+            .iter()
+            .zip(output_resource_cms.iter())
+            .enumerate()
+        {
+            nf.copy_advice(
+                || format!("input_resource_nf {}", i + 1),
+                region,
+                self.input_resource_nf,
+                offset + i,
+            )?;
+            cm.copy_advice(
+                || format!("output_resource_cm {}", i + 1),
+                region,
+                self.output_resource_cm,
+                offset + i,
+            )?;
+        }
 
         // compute the is_input_resource_flag
-        let is_input_resource_flag = owned_resource_id
-            .value()
-            .zip(input_resource_nfs[0].value())
-            .zip(input_resource_nfs[1].value())
-            .map(
-                |((&owned_resource_id, &input_resource_nf_1), &input_resource_nf_2)| {
-                    if owned_resource_id == input_resource_nf_1
-                        || owned_resource_id == input_resource_nf_2
-                    {
-                        pallas::Base::one()
-                    } else {
-                        pallas::Base::zero()
-                    }
-                },
-            );
+        let is_input_resource_flag = input_resource_nfs
+            .iter()
+            .fold(Value::known(false), |is_input, nf| {
+                is_input
+                    .zip(owned_resource_id.value())
+                    .zip(nf.value())
+                    .map(|((is_input, owned_resource_id), nf)| is_input || owned_resource_id == nf)
+            })
+            .map(|is_input| {
+                if is_input {
+                    pallas::Base::one()
+                } else {
+                    pallas::Base::zero()
+                }
+            });
         region.assign_advice(
             || "is_input_resource_flag",
             self.owned_resource_id,
@@ -10,6 +10,10 @@ pub mod curve;
 pub mod hash_to_curve;
 pub mod resource_commitment;
 pub mod resource_encryption_circuit;
+pub mod vamp_ir_prelude;
 mod vamp_ir_utils;
 pub mod vp_bytecode;
+pub mod vp_diagnostics;
 pub mod vp_examples;
+pub mod vp_key_cache;
+pub mod vp_registry;
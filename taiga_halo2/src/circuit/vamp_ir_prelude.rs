@@ -0,0 +1,349 @@
+//! The Taiga prelude of vamp-ir VPs.
+//!
+//! The prelude is compiled in front of the source of every vamp-ir VP. It declares the mandatory
+//! public inputs in the `VP_CIRCUIT_*_IDX` layout, and constrains them the way
+//! `ValidityPredicateCircuit::basic_constraints` does for native VPs:
+//! - `input_nf_{i}` is the nullifier of the `i`-th input resource,
+//! - `output_cm_{i}` is the commitment of the `i`-th output resource, whose nonce is `input_nf_{i}`,
+//! - `owned_resource_id` is one of the nullifiers or output commitments,
+//! - `dynamic_vp_cm_1` and `dynamic_vp_cm_2` are the default dynamic vp commitment: vamp-ir VPs
+//!   don't require dynamic vps, since the blake2s vp commitment has no vamp-ir gadget.
+//!
+//! The resources are witnessed through the variables named by `vamp_ir_resource_assignments`.
+//! The quantities `input_{i}_quantity` and `output_{i}_quantity` are range checked to 64 bits and
+//! the `is_ephemeral` flags to booleans, so VP sources can do arithmetic on them.
+//! The prelude also exposes the Poseidon hashes and the resource integrity functions, e.g.
+//! `taiga_resource_commitment` and `taiga_nullifier`, to the VP source.
+
+use crate::{
+    constant::{
+        vp_circuit_nullifier_public_input_idx, vp_circuit_output_cm_public_input_idx, NUM_RESOURCE,
+        PRF_EXPAND_PERSONALIZATION_TO_FIELD, PRF_EXPAND_PSI, PRF_EXPAND_RCM,
+        VP_CIRCUIT_DYNAMIC_VP_CM_1, VP_CIRCUIT_DYNAMIC_VP_CM_2,
+        VP_CIRCUIT_MANDATORY_PUBLIC_INPUT_NUM, VP_CIRCUIT_OWNED_RESOURCE_ID_PUBLIC_INPUT_IDX,
+    },
+    error::TransactionError,
+    resource::Resource,
+    vp_commitment::ValidityPredicateCommitment,
+};
+use ff::PrimeField;
+use halo2_gadgets::poseidon::primitives::{P128Pow5T3, Spec};
+use halo2_proofs::arithmetic::Field;
+use lazy_static::lazy_static;
+use num_bigint::BigUint;
+use pasta_curves::pallas;
+use std::collections::HashMap;
+
+lazy_static! {
+    static ref VAMP_IR_PRELUDE: String = generate_prelude();
+}
+
+/// The vamp-ir source compiled in front of every vamp-ir VP.
+pub fn vamp_ir_prelude() -> &'static str {
+    &VAMP_IR_PRELUDE
+}
+
+/// The names of the mandatory public inputs of vamp-ir VPs, in the public input order.
+pub fn vamp_ir_mandatory_public_inputs() -> Vec<String> {
+    let mut names = vec![String::new(); VP_CIRCUIT_MANDATORY_PUBLIC_INPUT_NUM];
+    for i in 0..NUM_RESOURCE {
+        names[vp_circuit_nullifier_public_input_idx(i)] = format!("input_nf_{i}");
+        names[vp_circuit_output_cm_public_input_idx(i)] = format!("output_cm_{i}");
+    }
+    names[VP_CIRCUIT_OWNED_RESOURCE_ID_PUBLIC_INPUT_IDX] = "owned_resource_id".to_string();
+    names[VP_CIRCUIT_DYNAMIC_VP_CM_1] = "dynamic_vp_cm_1".to_string();
+    names[VP_CIRCUIT_DYNAMIC_VP_CM_2] = "dynamic_vp_cm_2".to_string();
+    names
+}
+
+/// The assignments of the resource witnesses and the mandatory public inputs of the prelude.
+/// The input resources must have a nullifier key.
+pub fn vamp_ir_resource_assignments(
+    owned_resource_id: pallas::Base,
+    input_resources: &[Resource; NUM_RESOURCE],
+    output_resources: &[Resource; NUM_RESOURCE],
+) -> Result<HashMap<String, pallas::Base>, TransactionError> {
+    let mut assignments = HashMap::new();
+    for (i, (input_resource, output_resource)) in input_resources
+        .iter()
+        .zip(output_resources.iter())
+        .enumerate()
+    {
+        let nk = input_resource
+            .get_nk()
+            .ok_or(TransactionError::MissingTransparentResourceNullifierKey)?;
+        let nf = input_resource
+            .get_nf()
+            .ok_or(TransactionError::MissingTransparentResourceNullifierKey)?;
+        assignments.extend([
+            (format!("input_{i}_logic"), input_resource.get_logic()),
+            (format!("input_{i}_label"), input_resource.get_label()),
+            (format!("input_{i}_value"), input_resource.value),
+            (format!("input_{i}_nk"), nk),
+            (format!("input_{i}_nonce"), input_resource.nonce.inner()),
+            (format!("input_{i}_psi"), input_resource.get_psi()),
+            (
+                format!("input_{i}_is_ephemeral"),
+                pallas::Base::from(input_resource.is_ephemeral),
+            ),
+            (
+                format!("input_{i}_quantity"),
+                pallas::Base::from(input_resource.quantity),
+            ),
+            (format!("input_{i}_rcm"), input_resource.get_rcm()),
+            (format!("output_{i}_logic"), output_resource.get_logic()),
+            (format!("output_{i}_label"), output_resource.get_label()),
+            (format!("output_{i}_value"), output_resource.value),
+            (format!("output_{i}_npk"), output_resource.get_npk()),
+            (format!("output_{i}_rseed"), output_resource.rseed),
+            (
+                format!("output_{i}_is_ephemeral"),
+                pallas::Base::from(output_resource.is_ephemeral),
+            ),
+            (
+                format!("output_{i}_quantity"),
+                pallas::Base::from(output_resource.quantity),
+            ),
+            (format!("input_nf_{i}"), nf.inner()),
+            (
+                format!("output_cm_{i}"),
+                output_resource.commitment().inner(),
+            ),
+        ]);
+    }
+    assignments.insert("owned_resource_id".to_string(), owned_resource_id);
+    let [dynamic_vp_cm_1, dynamic_vp_cm_2] =
+        ValidityPredicateCommitment::default().to_public_inputs::<pallas::Base>();
+    assignments.insert("dynamic_vp_cm_1".to_string(), dynamic_vp_cm_1);
+    assignments.insert("dynamic_vp_cm_2".to_string(), dynamic_vp_cm_2);
+    Ok(assignments)
+}
+
+fn to_decimal(value: &pallas::Base) -> String {
+    BigUint::from_bytes_le(value.to_repr().as_ref()).to_string()
+}
+
+fn generate_prelude() -> String {
+    let prf_expand_personalization = to_decimal(&PRF_EXPAND_PERSONALIZATION_TO_FIELD);
+    let [default_dynamic_vp_cm_1, default_dynamic_vp_cm_2] = ValidityPredicateCommitment::default()
+        .to_public_inputs::<pallas::Base>()
+        .map(|x| to_decimal(&x));
+    // The is_ephemeral flag is above the 128 bits of the quantity in the resource commitment.
+    let is_ephemeral_shift = to_decimal(&pallas::Base::from_u128(1 << 64).square());
+    let mut prelude = vec![
+        "// Taiga vamp-ir VP prelude".to_string(),
+        String::new(),
+        "// Mandatory public inputs".to_string(),
+    ];
+    prelude.extend(
+        vamp_ir_mandatory_public_inputs()
+            .iter()
+            .map(|name| format!("pub {name};")),
+    );
+    prelude.push(String::new());
+
+    prelude.push("// Poseidon hashes, as `poseidon_hash_n` in Taiga".to_string());
+    prelude.extend([2, 4, 8].map(poseidon_hash_def));
+    prelude.push(String::new());
+
+    prelude.extend([
+        "// Range checks".to_string(),
+        "def taiga_bool x = { x * (x - 1) = 0; x };".to_string(),
+        range_check_def(64),
+        String::new(),
+    ]);
+
+    prelude.extend([
+        "// Resource integrity".to_string(),
+        format!(
+            "def taiga_is_ephemeral_quantity is_ephemeral quantity = (taiga_bool is_ephemeral) * {is_ephemeral_shift} + (taiga_range_64 quantity);"
+        ),
+        "def taiga_npk nk = taiga_poseidon_hash_2 nk 0;".to_string(),
+        format!(
+            "def taiga_psi rseed nonce = taiga_poseidon_hash_4 {prf_expand_personalization} {PRF_EXPAND_PSI} rseed nonce;"
+        ),
+        format!(
+            "def taiga_rcm rseed nonce = taiga_poseidon_hash_4 {prf_expand_personalization} {PRF_EXPAND_RCM} rseed nonce;"
+        ),
+        "def taiga_resource_commitment logic label value npk nonce psi is_ephemeral_quantity rcm = taiga_poseidon_hash_8 logic label value npk nonce psi is_ephemeral_quantity rcm;".to_string(),
+        "def taiga_nullifier nk nonce psi cm = taiga_poseidon_hash_4 nk nonce psi cm;".to_string(),
+        String::new(),
+        "// Resource checks".to_string(),
+    ]);
+    for i in 0..NUM_RESOURCE {
+        prelude.push(format!(
+            "input_nf_{i} = taiga_nullifier input_{i}_nk input_{i}_nonce input_{i}_psi (taiga_resource_commitment input_{i}_logic input_{i}_label input_{i}_value (taiga_npk input_{i}_nk) input_{i}_nonce input_{i}_psi (taiga_is_ephemeral_quantity input_{i}_is_ephemeral input_{i}_quantity) input_{i}_rcm);"
+        ));
+        prelude.push(format!(
+            "output_cm_{i} = taiga_resource_commitment output_{i}_logic output_{i}_label output_{i}_value output_{i}_npk input_nf_{i} (taiga_psi output_{i}_rseed input_nf_{i}) (taiga_is_ephemeral_quantity output_{i}_is_ephemeral output_{i}_quantity) (taiga_rcm output_{i}_rseed input_nf_{i});"
+        ));
+    }
+    let owned_resource_checks: Vec<String> = (0..NUM_RESOURCE)
+        .flat_map(|i| {
+            [
+                format!("(owned_resource_id - input_nf_{i})"),
+                format!("(owned_resource_id - output_cm_{i})"),
+            ]
+        })
+        .collect();
+    prelude.push(format!("{} = 0;", owned_resource_checks.join(" * ")));
+    prelude.push(format!("dynamic_vp_cm_1 = {default_dynamic_vp_cm_1};"));
+    prelude.push(format!("dynamic_vp_cm_2 = {default_dynamic_vp_cm_2};"));
+    prelude.push(String::new());
+
+    prelude.join("\n")
+}
+
+// Decomposes x into `bits` witnessed booleans, which constrains x to be less than 2^bits.
+fn range_check_def(bits: u32) -> String {
+    let body: Vec<String> = (0..bits)
+        .map(|j| format!("def b{j} = taiga_bool (fresh ((x \\ {}) % 2));", 1u128 << j))
+        .collect();
+    let sum: Vec<String> = (0..bits)
+        .map(|j| format!("{} * b{j}", 1u128 << j))
+        .collect();
+    format!(
+        "def taiga_range_{bits} x = {{\n    {}\n    x = {};\n    x\n}};",
+        body.join("\n    "),
+        sum.join(" + ")
+    )
+}
+
+// Unrolls the P128Pow5T3 sponge over a message of `len` field elements.
+fn poseidon_hash_def(len: usize) -> String {
+    let (round_constants, mds, _) = <P128Pow5T3 as Spec<pallas::Base, 3, 2>>::constants();
+    let mds = mds.map(|row| row.map(|v| to_decimal(&v)));
+    let half_full_rounds = <P128Pow5T3 as Spec<pallas::Base, 3, 2>>::full_rounds() / 2;
+    let partial_rounds = <P128Pow5T3 as Spec<pallas::Base, 3, 2>>::partial_rounds();
+
+    let message: Vec<String> = (0..len).map(|i| format!("m{i}")).collect();
+    let mut body = vec![];
+    // The capacity element of ConstantLength<len>
+    let mut state = [
+        "0".to_string(),
+        "0".to_string(),
+        to_decimal(&pallas::Base::from_u128((len as u128) << 64)),
+    ];
+    let mut round = 0;
+    for chunk in message.chunks(2) {
+        // Absorb the chunk, the message is padded with zeros.
+        for (word, m) in state.iter_mut().zip(chunk.iter()) {
+            *word = format!("({word} + {m})");
+        }
+        for (r, rc) in round_constants.iter().enumerate() {
+            let is_full_round = r < half_full_rounds || r >= half_full_rounds + partial_rounds;
+            let sboxed: Vec<String> = (0..3)
+                .map(|i| {
+                    let added = format!("({} + {})", state[i], to_decimal(&rc[i]));
+                    if i == 0 || is_full_round {
+                        let name = format!("t{round}_{i}");
+                        body.push(format!("def {name} = {added}^5;"));
+                        name
+                    } else {
+                        added
+                    }
+                })
+                .collect();
+            state = [0, 1, 2].map(|i| {
+                let name = format!("s{round}_{i}");
+                let terms: Vec<String> = (0..3)
+                    .map(|j| format!("{} * {}", mds[i][j], sboxed[j]))
+                    .collect();
+                body.push(format!("def {name} = {};", terms.join(" + ")));
+                name
+            });
+            round += 1;
+        }
+    }
+
+    format!(
+        "def taiga_poseidon_hash_{len} {} = {{\n    {}\n    {}\n}};",
+        message.join(" "),
+        body.join("\n    "),
+        state[0]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::vp_circuit::{
+        ValidityPredicateVerifyingInfo, VampIRValidityPredicateCircuit,
+    };
+    use crate::nullifier::NullifierKeyContainer;
+    use crate::resource::tests::random_resource;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn test_vamp_ir_prelude_layout() {
+        let names = vamp_ir_mandatory_public_inputs();
+        assert!(names.iter().all(|name| !name.is_empty()));
+        let prelude = vamp_ir_prelude();
+        let pub_names: Vec<&str> = prelude
+            .lines()
+            .filter_map(|line| line.strip_prefix("pub "))
+            .map(|line| line.trim_end_matches(';'))
+            .collect();
+        assert_eq!(pub_names, names);
+    }
+
+    #[test]
+    fn test_vamp_ir_prelude_resource_checks() {
+        let mut rng = OsRng;
+        let input_resources = [(); NUM_RESOURCE].map(|_| random_resource(&mut rng));
+        let output_resources = input_resources.map(|input_resource| {
+            let mut output_resource = random_resource(&mut rng);
+            output_resource.set_nonce(&input_resource);
+            output_resource
+        });
+        let owned_resource_id = input_resources[0].get_nf().unwrap().inner();
+        let assignments =
+            vamp_ir_resource_assignments(owned_resource_id, &input_resources, &output_resources)
+                .unwrap();
+
+        let vp_circuit =
+            VampIRValidityPredicateCircuit::from_vamp_ir_vp_source("", assignments.clone())
+                .unwrap();
+        let public_inputs = vp_circuit.verify_transparently().unwrap();
+        assert_eq!(
+            public_inputs.get_from_index(VP_CIRCUIT_OWNED_RESOURCE_ID_PUBLIC_INPUT_IDX),
+            owned_resource_id
+        );
+        for i in 0..NUM_RESOURCE {
+            assert_eq!(
+                public_inputs.get_from_index(vp_circuit_output_cm_public_input_idx(i)),
+                output_resources[i].commitment().inner()
+            );
+        }
+        // vamp-ir VPs don't require dynamic vps.
+        assert!(public_inputs.check_dynamic_vps(&[]).is_ok());
+
+        // A dynamic vp commitment other than the default one is rejected.
+        let mut dynamic_vp_assignments = assignments.clone();
+        dynamic_vp_assignments.insert("dynamic_vp_cm_1".to_string(), pallas::Base::one());
+        assert!(
+            VampIRValidityPredicateCircuit::from_vamp_ir_vp_source("", dynamic_vp_assignments)
+                .unwrap()
+                .verify_transparently()
+                .is_err()
+        );
+
+        // A quantity that doesn't fit in 64 bits is rejected, even if the commitment opens to it.
+        let mut quantity_assignments = assignments;
+        quantity_assignments.insert("input_0_quantity".to_string(), -pallas::Base::one());
+        assert!(
+            VampIRValidityPredicateCircuit::from_vamp_ir_vp_source("", quantity_assignments)
+                .unwrap()
+                .verify_transparently()
+                .is_err()
+        );
+
+        // The input resources need a nullifier key.
+        let mut input_resources = input_resources;
+        input_resources[1].nk_container = NullifierKeyContainer::PublicKey(pallas::Base::one());
+        assert!(matches!(
+            vamp_ir_resource_assignments(owned_resource_id, &input_resources, &output_resources),
+            Err(TransactionError::MissingTransparentResourceNullifierKey)
+        ));
+    }
+}
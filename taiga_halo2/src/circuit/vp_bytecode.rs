@@ -1,68 +1,58 @@
-#[cfg(feature = "borsh")]
-use crate::circuit::vp_examples::TrivialValidityPredicateCircuit;
-#[cfg(feature = "examples")]
-use crate::circuit::vp_examples::{
-    cascade_intent::CascadeIntentValidityPredicateCircuit,
-    or_relation_intent::OrRelationIntentValidityPredicateCircuit,
-    partial_fulfillment_intent::PartialFulfillmentIntentValidityPredicateCircuit,
-    receiver_vp::ReceiverValidityPredicateCircuit,
-    signature_verification::SignatureVerificationValidityPredicateCircuit,
-    token::TokenValidityPredicateCircuit,
-};
 use crate::error::TransactionError;
 use crate::shielded_ptx::ResourceVPVerifyingInfoSet;
 use crate::{
-    circuit::vp_circuit::{
-        VPVerifyingInfo, ValidityPredicateVerifyingInfo, VampIRValidityPredicateCircuit,
+    circuit::{
+        vp_circuit::{
+            VPVerifyingInfo, ValidityPredicatePublicInputs, ValidityPredicateVerifyingInfo,
+            VampIRValidityPredicateCircuit,
+        },
+        vp_registry::get_vp_circuit,
     },
     constant::{
-        VP_CIRCUIT_NULLIFIER_ONE_PUBLIC_INPUT_IDX, VP_CIRCUIT_NULLIFIER_TWO_PUBLIC_INPUT_IDX,
-        VP_CIRCUIT_OUTPUT_CM_ONE_PUBLIC_INPUT_IDX, VP_CIRCUIT_OUTPUT_CM_TWO_PUBLIC_INPUT_IDX,
+        vp_circuit_nullifier_public_input_idx, vp_circuit_output_cm_public_input_idx, NUM_RESOURCE,
         VP_CIRCUIT_OWNED_RESOURCE_ID_PUBLIC_INPUT_IDX,
     },
     nullifier::Nullifier,
     resource::ResourceCommitment,
+    utils::is_permutation,
 };
 
 #[cfg(feature = "borsh")]
 use borsh::{BorshDeserialize, BorshSerialize};
 use pasta_curves::pallas;
+use rand::{rngs::OsRng, RngCore};
 #[cfg(feature = "serde")]
 use serde;
-use std::path::PathBuf;
+use std::collections::HashMap;
 
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ValidityPredicateRepresentation {
-    // vampir has a unified circuit representation.
-    VampIR(Vec<u8>),
-    // Native halo2 circuits don't have a unified representatioin, enumerate the vp circuit examples for the moment.
+    // vampir has a unified circuit representation, the vamp-ir source of the VP, compiled after the
+    // Taiga prelude. The inputs are the assignments encoded by
+    // `VampIRValidityPredicateCircuit::encode_assignments`.
+    VampIR(String),
+    // Native halo2 circuits don't have a unified representation, they are referred to by the
+    // identifier they are registered under in `vp_registry`.
     // TODO: figure out if we can have a unified circuit presentation. In theory, it's possible to separate the circuit system and proving system.
-    Trivial,
-    Token,
-    SignatureVerification,
-    Receiver,
-    PartialFulfillmentIntent,
-    OrRelationIntent,
-    CascadeIntent,
-    // Add other native vp types here if needed
+    Native(String),
 }
 
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ValidityPredicateByteCode {
-    circuit: ValidityPredicateRepresentation,
-    inputs: Vec<u8>,
+    pub(crate) circuit: ValidityPredicateRepresentation,
+    pub(crate) inputs: Vec<u8>,
 }
 
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ApplicationByteCode {
-    app_vp_bytecode: ValidityPredicateByteCode,
-    dynamic_vp_bytecode: Vec<ValidityPredicateByteCode>,
+    pub(crate) app_vp_bytecode: ValidityPredicateByteCode,
+    pub(crate) dynamic_vp_bytecode: Vec<ValidityPredicateByteCode>,
 }
 
 impl ValidityPredicateByteCode {
@@ -70,145 +60,114 @@ impl ValidityPredicateByteCode {
         Self { circuit, inputs }
     }
 
+    /// The encoding of the bytecode: the representation tag, then the length-prefixed circuit
+    /// and inputs.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let (tag, circuit) = match &self.circuit {
+            ValidityPredicateRepresentation::VampIR(source) => (0, source),
+            ValidityPredicateRepresentation::Native(id) => (1, id),
+        };
+        let mut bytes = vec![tag];
+        bytes.extend_from_slice(&(circuit.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(circuit.as_bytes());
+        bytes.extend_from_slice(&(self.inputs.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.inputs);
+        bytes
+    }
+
+    pub fn from_vamp_ir_source(
+        vamp_ir_source: impl Into<String>,
+        named_field_assignments: &HashMap<String, pallas::Base>,
+    ) -> Self {
+        Self {
+            circuit: ValidityPredicateRepresentation::VampIR(vamp_ir_source.into()),
+            inputs: VampIRValidityPredicateCircuit::encode_assignments(named_field_assignments),
+        }
+    }
+
     pub fn generate_proof(self) -> Result<VPVerifyingInfo, TransactionError> {
+        self.generate_proof_with_rng(&mut OsRng)
+    }
+
+    /// Creates the vp proof, taking all the randomness from `rng`.
+    pub fn generate_proof_with_rng(
+        self,
+        rng: &mut dyn RngCore,
+    ) -> Result<VPVerifyingInfo, TransactionError> {
         match self.circuit {
-            ValidityPredicateRepresentation::VampIR(circuit) => {
-                // TDDO: use the file_name api atm,
-                // request vamp_ir to provide a api to generate circuit from bytes.
-                let vamp_ir_circuit_file =
-                    PathBuf::from(String::from_utf8_lossy(&circuit).to_string());
-                let inputs_file = PathBuf::from(String::from_utf8_lossy(&self.inputs).to_string());
-                let vp_circuit = VampIRValidityPredicateCircuit::from_vamp_ir_file(
-                    &vamp_ir_circuit_file,
-                    &inputs_file,
-                );
-                Ok(vp_circuit.get_verifying_info())
+            ValidityPredicateRepresentation::VampIR(source) => {
+                let vp_circuit =
+                    VampIRValidityPredicateCircuit::from_vamp_ir_bytecode(&source, &self.inputs)?;
+                vp_circuit.get_verifying_info_with_rng(rng)
             }
-            #[cfg(feature = "borsh")]
-            ValidityPredicateRepresentation::Trivial => {
-                let vp = TrivialValidityPredicateCircuit::from_bytes(&self.inputs);
-                Ok(vp.get_verifying_info())
+            ValidityPredicateRepresentation::Native(id) => {
+                get_vp_circuit(&id)?.generate_proof_with_rng(&self.inputs, rng)
             }
-            #[cfg(feature = "examples")]
-            ValidityPredicateRepresentation::Token => {
-                let vp = TokenValidityPredicateCircuit::from_bytes(&self.inputs);
-                Ok(vp.get_verifying_info())
-            }
-            #[cfg(feature = "examples")]
-            ValidityPredicateRepresentation::SignatureVerification => {
-                let vp = SignatureVerificationValidityPredicateCircuit::from_bytes(&self.inputs);
-                Ok(vp.get_verifying_info())
-            }
-            #[cfg(feature = "examples")]
-            ValidityPredicateRepresentation::Receiver => {
-                let vp = ReceiverValidityPredicateCircuit::from_bytes(&self.inputs);
-                Ok(vp.get_verifying_info())
-            }
-            #[cfg(feature = "examples")]
-            ValidityPredicateRepresentation::PartialFulfillmentIntent => {
-                let vp = PartialFulfillmentIntentValidityPredicateCircuit::from_bytes(&self.inputs);
-                Ok(vp.get_verifying_info())
-            }
-            #[cfg(feature = "examples")]
-            ValidityPredicateRepresentation::OrRelationIntent => {
-                let vp = OrRelationIntentValidityPredicateCircuit::from_bytes(&self.inputs);
-                Ok(vp.get_verifying_info())
+        }
+    }
+
+    /// Returns the compressed vk of the vp, which the dynamic vp commitment of an app vp is over.
+    pub fn get_compressed_vk(&self) -> Result<pallas::Base, TransactionError> {
+        let vk = match &self.circuit {
+            ValidityPredicateRepresentation::VampIR(source) => {
+                VampIRValidityPredicateCircuit::from_vamp_ir_bytecode(source, &self.inputs)?
+                    .get_vp_vk()?
             }
-            #[cfg(feature = "examples")]
-            ValidityPredicateRepresentation::CascadeIntent => {
-                let vp = CascadeIntentValidityPredicateCircuit::from_bytes(&self.inputs);
-                Ok(vp.get_verifying_info())
+            ValidityPredicateRepresentation::Native(id) => {
+                (get_vp_circuit(id)?.decode)(&self.inputs)?.get_vp_vk()?
             }
-            #[allow(unreachable_patterns)]
-            _ => Err(TransactionError::InvalidValidityPredicateRepresentation),
-        }
+        };
+        Ok(vk.get_compressed())
     }
 
-    // Verify vp circuit transparently and return owned resource PubID for further checking
     pub fn verify_transparently(
         &self,
         compliance_nfs: &[Nullifier],
         compliance_cms: &[ResourceCommitment],
     ) -> Result<pallas::Base, TransactionError> {
+        let public_inputs = self.check_transparently(compliance_nfs, compliance_cms)?;
+        Ok(public_inputs.get_from_index(VP_CIRCUIT_OWNED_RESOURCE_ID_PUBLIC_INPUT_IDX))
+    }
+
+    // Checks the vp transparently against the compliance nfs and cms, and returns its public inputs.
+    fn check_transparently(
+        &self,
+        compliance_nfs: &[Nullifier],
+        compliance_cms: &[ResourceCommitment],
+    ) -> Result<ValidityPredicatePublicInputs, TransactionError> {
         // check VP transparently
         let public_inputs = match &self.circuit {
-            ValidityPredicateRepresentation::VampIR(circuit) => {
-                // TDDO: use the file_name api atm,
-                // request vamp_ir to provide a api to generate circuit from bytes.
-                let vamp_ir_circuit_file =
-                    PathBuf::from(String::from_utf8_lossy(circuit).to_string());
-                let inputs_file = PathBuf::from(String::from_utf8_lossy(&self.inputs).to_string());
-                let vp_circuit = VampIRValidityPredicateCircuit::from_vamp_ir_file(
-                    &vamp_ir_circuit_file,
-                    &inputs_file,
-                );
+            ValidityPredicateRepresentation::VampIR(source) => {
+                let vp_circuit =
+                    VampIRValidityPredicateCircuit::from_vamp_ir_bytecode(source, &self.inputs)?;
                 vp_circuit.verify_transparently()?
             }
-            #[cfg(feature = "borsh")]
-            ValidityPredicateRepresentation::Trivial => {
-                let vp = TrivialValidityPredicateCircuit::from_bytes(&self.inputs);
-                vp.verify_transparently()?
-            }
-            #[cfg(feature = "examples")]
-            ValidityPredicateRepresentation::Token => {
-                let vp = TokenValidityPredicateCircuit::from_bytes(&self.inputs);
-                vp.verify_transparently()?
-            }
-            #[cfg(feature = "examples")]
-            ValidityPredicateRepresentation::SignatureVerification => {
-                let vp = SignatureVerificationValidityPredicateCircuit::from_bytes(&self.inputs);
-                vp.verify_transparently()?
-            }
-            #[cfg(feature = "examples")]
-            ValidityPredicateRepresentation::Receiver => {
-                let vp = ReceiverValidityPredicateCircuit::from_bytes(&self.inputs);
-                vp.verify_transparently()?
+            ValidityPredicateRepresentation::Native(id) => {
+                get_vp_circuit(id)?.verify_transparently(&self.inputs)?
             }
-            #[cfg(feature = "examples")]
-            ValidityPredicateRepresentation::PartialFulfillmentIntent => {
-                let vp = PartialFulfillmentIntentValidityPredicateCircuit::from_bytes(&self.inputs);
-                vp.verify_transparently()?
-            }
-            #[cfg(feature = "examples")]
-            ValidityPredicateRepresentation::OrRelationIntent => {
-                let vp = OrRelationIntentValidityPredicateCircuit::from_bytes(&self.inputs);
-                vp.verify_transparently()?
-            }
-            #[cfg(feature = "examples")]
-            ValidityPredicateRepresentation::CascadeIntent => {
-                let vp = CascadeIntentValidityPredicateCircuit::from_bytes(&self.inputs);
-                vp.verify_transparently()?
-            }
-            #[allow(unreachable_patterns)]
-            _ => return Err(TransactionError::InvalidValidityPredicateRepresentation),
         };
 
         // check nullifiers
         // Check the vp actually uses the input resources from compliance circuits.
-        let vp_nfs = [
-            public_inputs.get_from_index(VP_CIRCUIT_NULLIFIER_ONE_PUBLIC_INPUT_IDX),
-            public_inputs.get_from_index(VP_CIRCUIT_NULLIFIER_TWO_PUBLIC_INPUT_IDX),
-        ];
-
-        if !((compliance_nfs[0].inner() == vp_nfs[0] && compliance_nfs[1].inner() == vp_nfs[1])
-            || (compliance_nfs[0].inner() == vp_nfs[1] && compliance_nfs[1].inner() == vp_nfs[0]))
-        {
+        let compliance_nfs: Vec<_> = compliance_nfs.iter().map(|nf| nf.inner()).collect();
+        let vp_nfs: Vec<_> = (0..NUM_RESOURCE)
+            .map(|i| public_inputs.get_from_index(vp_circuit_nullifier_public_input_idx(i)))
+            .collect();
+        if !is_permutation(&compliance_nfs, &vp_nfs) {
             return Err(TransactionError::InconsistentNullifier);
         }
 
         // check resource_commitments
         // Check the vp actually uses the output resources from compliance circuits.
-        let vp_cms = [
-            public_inputs.get_from_index(VP_CIRCUIT_OUTPUT_CM_ONE_PUBLIC_INPUT_IDX),
-            public_inputs.get_from_index(VP_CIRCUIT_OUTPUT_CM_TWO_PUBLIC_INPUT_IDX),
-        ];
-        if !((compliance_cms[0].inner() == vp_cms[0] && compliance_cms[1].inner() == vp_cms[1])
-            || (compliance_cms[0].inner() == vp_cms[1] && compliance_cms[1].inner() == vp_cms[0]))
-        {
+        let compliance_cms: Vec<_> = compliance_cms.iter().map(|cm| cm.inner()).collect();
+        let vp_cms: Vec<_> = (0..NUM_RESOURCE)
+            .map(|i| public_inputs.get_from_index(vp_circuit_output_cm_public_input_idx(i)))
+            .collect();
+        if !is_permutation(&compliance_cms, &vp_cms) {
             return Err(TransactionError::InconsistentOutputResourceCommitment);
         }
 
-        Ok(public_inputs.get_from_index(VP_CIRCUIT_OWNED_RESOURCE_ID_PUBLIC_INPUT_IDX))
+        Ok(public_inputs)
     }
 }
 
@@ -223,13 +182,39 @@ impl ApplicationByteCode {
         }
     }
 
+    /// The encoding of the application vp bytecode followed by the number of dynamic vps and
+    /// their bytecodes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.app_vp_bytecode.to_bytes();
+        bytes.extend_from_slice(&(self.dynamic_vp_bytecode.len() as u32).to_le_bytes());
+        for bytecode in self.dynamic_vp_bytecode.iter() {
+            bytes.extend_from_slice(&bytecode.to_bytes());
+        }
+        bytes
+    }
+
+    // The application vp bytecode followed by the dynamic vp bytecodes
+    pub fn into_vp_bytecodes(self) -> Vec<ValidityPredicateByteCode> {
+        std::iter::once(self.app_vp_bytecode)
+            .chain(self.dynamic_vp_bytecode)
+            .collect()
+    }
+
     pub fn generate_proofs(self) -> Result<ResourceVPVerifyingInfoSet, TransactionError> {
-        let app_vp_verifying_info = self.app_vp_bytecode.generate_proof()?;
+        self.generate_proofs_with_rng(&mut OsRng)
+    }
+
+    /// Creates the app vp and dynamic vp proofs, taking all the randomness from `rng`.
+    pub fn generate_proofs_with_rng(
+        self,
+        rng: &mut dyn RngCore,
+    ) -> Result<ResourceVPVerifyingInfoSet, TransactionError> {
+        let app_vp_verifying_info = self.app_vp_bytecode.generate_proof_with_rng(rng)?;
 
         let app_dynamic_vp_verifying_info: Result<Vec<_>, _> = self
             .dynamic_vp_bytecode
             .into_iter()
-            .map(|bytecode| bytecode.generate_proof())
+            .map(|bytecode| bytecode.generate_proof_with_rng(rng))
             .collect();
         Ok(ResourceVPVerifyingInfoSet::new(
             app_vp_verifying_info,
@@ -243,9 +228,20 @@ impl ApplicationByteCode {
         compliance_nfs: &[Nullifier],
         compliance_cms: &[ResourceCommitment],
     ) -> Result<pallas::Base, TransactionError> {
-        let owned_resource_id = self
+        let app_vp_public_inputs = self
             .app_vp_bytecode
-            .verify_transparently(compliance_nfs, compliance_cms)?;
+            .check_transparently(compliance_nfs, compliance_cms)?;
+        let owned_resource_id =
+            app_vp_public_inputs.get_from_index(VP_CIRCUIT_OWNED_RESOURCE_ID_PUBLIC_INPUT_IDX);
+
+        // check: the dynamic vps are the ones the app vp commits to
+        let dynamic_vps: Vec<_> = self
+            .dynamic_vp_bytecode
+            .iter()
+            .map(|dynamic_vp| dynamic_vp.get_compressed_vk())
+            .collect::<Result<_, _>>()?;
+        app_vp_public_inputs.check_dynamic_vps(&dynamic_vps)?;
+
         for dynamic_vp in self.dynamic_vp_bytecode.iter() {
             let id = dynamic_vp.verify_transparently(compliance_nfs, compliance_cms)?;
             // check: the app_vp and dynamic_vps belong to the resource
@@ -1,6 +1,6 @@
 use crate::{
     circuit::{
-        blake2s::publicize_default_dynamic_vp_commitments,
+        blake2s::publicize_default_dynamic_vp_commitment,
         blake2s::Blake2sConfig,
         gadgets::{
             add::{AddChip, AddConfig},
@@ -16,24 +16,29 @@ use crate::{
         },
         integrity::{check_input_resource, check_output_resource},
         resource_commitment::{ResourceCommitChip, ResourceCommitConfig},
+        vamp_ir_prelude::{vamp_ir_mandatory_public_inputs, vamp_ir_prelude},
         vamp_ir_utils::{get_circuit_assignments, parse, VariableAssignmentError},
+        vp_diagnostics::VPConstraintFailureReport,
+        vp_key_cache::{get_vp_circuit_pk, get_vp_circuit_vk_with_id},
     },
     constant::{
+        vp_circuit_nullifier_public_input_idx, vp_circuit_output_cm_public_input_idx,
         TaigaFixedBases, NUM_RESOURCE, RESOURCE_ENCRYPTION_CIPHERTEXT_NUM, SETUP_PARAMS_MAP,
-        VP_CIRCUIT_NULLIFIER_ONE_PUBLIC_INPUT_IDX, VP_CIRCUIT_NULLIFIER_TWO_PUBLIC_INPUT_IDX,
-        VP_CIRCUIT_OUTPUT_CM_ONE_PUBLIC_INPUT_IDX, VP_CIRCUIT_OUTPUT_CM_TWO_PUBLIC_INPUT_IDX,
-        VP_CIRCUIT_OWNED_RESOURCE_ID_PUBLIC_INPUT_IDX, VP_CIRCUIT_PARAMS_SIZE,
-        VP_CIRCUIT_PUBLIC_INPUT_NUM, VP_CIRCUIT_RESOURCE_ENCRYPTION_PK_X_IDX,
-        VP_CIRCUIT_RESOURCE_ENCRYPTION_PK_Y_IDX,
+        VAMP_IR_SOURCE_DIGEST_PERSONALIZATION, VP_CIRCUIT_DYNAMIC_VP_CM_1,
+        VP_CIRCUIT_DYNAMIC_VP_CM_2, VP_CIRCUIT_OWNED_RESOURCE_ID_PUBLIC_INPUT_IDX,
+        VP_CIRCUIT_PARAMS_SIZE, VP_CIRCUIT_PUBLIC_INPUT_NUM,
+        VP_CIRCUIT_RESOURCE_ENCRYPTION_PK_X_IDX, VP_CIRCUIT_RESOURCE_ENCRYPTION_PK_Y_IDX,
         VP_CIRCUIT_RESOURCE_ENCRYPTION_PUBLIC_INPUT_BEGIN_IDX,
     },
     error::TransactionError,
-    proof::Proof,
+    proof::{BatchProofVerifier, Proof},
     resource::{RandomSeed, Resource, ResourceCommitment},
     resource_encryption::{ResourceCiphertext, SecretKey},
     utils::mod_r_p,
-    vp_vk::ValidityPredicateVerifyingKey,
+    vp_commitment::ValidityPredicateCommitment,
+    vp_vk::{VPVerifyingKeyRegistry, ValidityPredicateVerifyingKey},
 };
+use blake2b_simd::Params as Blake2bParams;
 use dyn_clone::{clone_trait_object, DynClone};
 use group::cofactor::CofactorCurveAffine;
 use halo2_gadgets::{
@@ -46,22 +51,20 @@ use halo2_proofs::{
     arithmetic::CurveAffine,
     circuit::{AssignedCell, Layouter, Value},
     plonk::{
-        keygen_pk, keygen_vk, Advice, Circuit, Column, ConstraintSystem, Error, Instance,
-        TableColumn, VerifyingKey,
+        Advice, Circuit, Column, ConstraintSystem, Error, Instance, TableColumn, VerifyingKey,
     },
-    poly::commitment::Params,
 };
-use pasta_curves::{pallas, vesta, EqAffine, Fp};
+use pasta_curves::{pallas, vesta, Fp};
 use rand::{rngs::OsRng, RngCore};
 use std::collections::HashMap;
 use std::fs;
 //use std::io;
 use std::path::PathBuf;
 use std::rc::Rc;
-use vamp_ir::ast::Module;
+use vamp_ir::ast::VariableId;
 use vamp_ir::halo2::synth::{make_constant, Halo2Module, PrimeFieldOps};
 use vamp_ir::transform::compile;
-use vamp_ir::util::{read_inputs_from_file, Config};
+use vamp_ir::util::Config;
 
 #[cfg(feature = "serde")]
 use serde;
@@ -158,24 +161,24 @@ impl VPVerifyingInfo {
             .verify(&self.vk, params, &[self.public_inputs.inner()])
     }
 
+    pub fn add_to_batch(&self, batch: &mut BatchProofVerifier) -> Result<(), Error> {
+        self.proof
+            .add_to_batch(batch, &self.vk, &[self.public_inputs.inner()])
+    }
+
     pub fn get_nullifiers(&self) -> [pallas::Base; NUM_RESOURCE] {
-        [
+        std::array::from_fn(|i| {
             self.public_inputs
-                .get_from_index(VP_CIRCUIT_NULLIFIER_ONE_PUBLIC_INPUT_IDX),
-            self.public_inputs
-                .get_from_index(VP_CIRCUIT_NULLIFIER_TWO_PUBLIC_INPUT_IDX),
-        ]
+                .get_from_index(vp_circuit_nullifier_public_input_idx(i))
+        })
     }
 
     pub fn get_resource_commitments(&self) -> [ResourceCommitment; NUM_RESOURCE] {
-        [
+        std::array::from_fn(|i| {
             self.public_inputs
-                .get_from_index(VP_CIRCUIT_OUTPUT_CM_ONE_PUBLIC_INPUT_IDX)
-                .into(),
-            self.public_inputs
-                .get_from_index(VP_CIRCUIT_OUTPUT_CM_TWO_PUBLIC_INPUT_IDX)
-                .into(),
-        ]
+                .get_from_index(vp_circuit_output_cm_public_input_idx(i))
+                .into()
+        })
     }
 
     pub fn get_owned_resource_id(&self) -> pallas::Base {
@@ -200,6 +203,43 @@ impl BorshSerialize for VPVerifyingInfo {
     }
 }
 
+#[cfg(feature = "borsh")]
+impl VPVerifyingInfo {
+    /// Writes the verifying info like borsh does, but with the compressed vk instead of the vk.
+    pub fn serialize_compact<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        use ff::PrimeField;
+        let vk = ValidityPredicateVerifyingKey::from_vk(self.vk.clone()).get_compressed();
+        writer.write_all(&vk.to_repr())?;
+        self.proof.serialize(writer)?;
+        for ele in self.public_inputs.inner().iter() {
+            writer.write_all(&ele.to_repr())?;
+        }
+        Ok(())
+    }
+
+    /// Reads verifying info written by `serialize_compact`, resolving the vk in `registry`.
+    pub fn deserialize_compact<R: std::io::Read>(
+        reader: &mut R,
+        registry: &VPVerifyingKeyRegistry,
+    ) -> Result<Self, TransactionError> {
+        use crate::utils::read_base_field;
+        let compressed_vk = read_base_field(reader)?;
+        let vk = registry
+            .get(&compressed_vk)
+            .cloned()
+            .ok_or(TransactionError::UnknownVerifyingKey(compressed_vk))?;
+        let proof = Proof::deserialize_reader(reader)?;
+        let public_inputs: Vec<_> = (0..VP_CIRCUIT_PUBLIC_INPUT_NUM)
+            .map(|_| read_base_field(reader))
+            .collect::<Result<_, _>>()?;
+        Ok(VPVerifyingInfo {
+            vk,
+            proof,
+            public_inputs: public_inputs.into(),
+        })
+    }
+}
+
 #[cfg(feature = "borsh")]
 impl BorshDeserialize for VPVerifyingInfo {
     fn deserialize_reader<R: std::io::Read>(reader: &mut R) -> std::io::Result<Self> {
@@ -278,6 +318,20 @@ impl ValidityPredicatePublicInputs {
         self.0.to_vec()
     }
 
+    /// Checks the dynamic vp commitment of an app vp opens to the compressed vks of `dynamic_vps`.
+    pub fn check_dynamic_vps(&self, dynamic_vps: &[pallas::Base]) -> Result<(), TransactionError> {
+        let expected: [pallas::Base; 2] =
+            ValidityPredicateCommitment::commit_dynamic_vps(dynamic_vps).to_public_inputs();
+        let actual = [
+            self.get_from_index(VP_CIRCUIT_DYNAMIC_VP_CM_1),
+            self.get_from_index(VP_CIRCUIT_DYNAMIC_VP_CM_2),
+        ];
+        if expected != actual {
+            return Err(TransactionError::InconsistentDynamicVPCommitment);
+        }
+        Ok(())
+    }
+
     pub fn decrypt(&self, sk: pallas::Base) -> Option<Vec<pallas::Base>> {
         let cipher: ResourceCiphertext = self.0
             [VP_CIRCUIT_RESOURCE_ENCRYPTION_PUBLIC_INPUT_BEGIN_IDX
@@ -377,7 +431,9 @@ impl ValidityPredicateConfig {
         let get_owned_resource_variable_config = GetOwnedResourceVariableConfig::configure(
             meta,
             advices[0],
-            [advices[1], advices[2], advices[3], advices[4]],
+            // The searchable pairs of all the resources take one column each, see
+            // VP_CIRCUIT_MAX_RESOURCE_NUM.
+            std::array::from_fn(|i| advices[i + 1]),
         );
 
         let get_is_input_resource_flag_config =
@@ -421,10 +477,26 @@ impl ValidityPredicateConfig {
     }
 }
 
-pub trait ValidityPredicateVerifyingInfo: DynClone {
-    fn get_verifying_info(&self) -> VPVerifyingInfo;
+// VPs are Send and Sync so that their proofs can be created on other threads.
+// The searchable pairs of the input and output resources take an advice column each, after the
+// first of the 10 advice columns of the VP circuit.
+const VP_CIRCUIT_MAX_RESOURCE_NUM: usize = (10 - 1) / 2;
+const _: () = assert!(
+    NUM_RESOURCE <= VP_CIRCUIT_MAX_RESOURCE_NUM,
+    "the VP circuit has advice columns for at most 4 input and 4 output resources"
+);
+
+pub trait ValidityPredicateVerifyingInfo: DynClone + Send + Sync {
+    fn get_verifying_info(&self) -> Result<VPVerifyingInfo, TransactionError> {
+        self.get_verifying_info_with_rng(&mut OsRng)
+    }
+    /// Creates the vp proof, taking all the randomness from `rng`.
+    fn get_verifying_info_with_rng(
+        &self,
+        rng: &mut dyn RngCore,
+    ) -> Result<VPVerifyingInfo, TransactionError>;
     fn verify_transparently(&self) -> Result<ValidityPredicatePublicInputs, TransactionError>;
-    fn get_vp_vk(&self) -> ValidityPredicateVerifyingKey;
+    fn get_vp_vk(&self) -> Result<ValidityPredicateVerifyingKey, TransactionError>;
 }
 
 clone_trait_object!(ValidityPredicateVerifyingInfo);
@@ -467,7 +539,7 @@ pub trait ValidityPredicateCircuit: Circuit<pallas::Base> + ValidityPredicateVer
                 config.instances,
                 resource_commit_chip.clone(),
                 input_resources[i],
-                i * 2,
+                vp_circuit_nullifier_public_input_idx(i),
             )?);
 
             // The old_nf may not be from above input resource
@@ -483,7 +555,7 @@ pub trait ValidityPredicateCircuit: Circuit<pallas::Base> + ValidityPredicateVer
                 resource_commit_chip.clone(),
                 output_resources[i],
                 old_nf,
-                i * 2 + 1,
+                vp_circuit_output_cm_public_input_idx(i),
             )?);
         }
 
@@ -517,8 +589,8 @@ pub trait ValidityPredicateCircuit: Circuit<pallas::Base> + ValidityPredicateVer
         mut layouter: impl Layouter<pallas::Base>,
         _basic_variables: BasicValidityPredicateVariables,
     ) -> Result<(), Error> {
-        // Publicize the dynamic vp commitments with default value
-        publicize_default_dynamic_vp_commitments(
+        // Publicize the dynamic vp commitment with default value
+        publicize_default_dynamic_vp_commitment(
             &mut layouter,
             config.advices[0],
             config.instances,
@@ -527,23 +599,30 @@ pub trait ValidityPredicateCircuit: Circuit<pallas::Base> + ValidityPredicateVer
         Ok(())
     }
 
-    fn get_mandatory_public_inputs(&self) -> Vec<pallas::Base> {
+    // The input resources must carry their nullifier keys to derive the nullifiers.
+    fn get_mandatory_public_inputs(&self) -> Result<Vec<pallas::Base>, TransactionError> {
         let mut public_inputs = vec![];
-        self.get_input_resources()
+        for (input_resource, output_resource) in self
+            .get_input_resources()
             .iter()
             .zip(self.get_output_resources().iter())
-            .for_each(|(input_resource, output_resource)| {
-                let nf = input_resource.get_nf().unwrap().inner();
-                public_inputs.push(nf);
-                let cm = output_resource.commitment();
-                public_inputs.push(cm.inner());
-            });
+        {
+            let nf = input_resource
+                .get_nf()
+                .ok_or(TransactionError::MissingTransparentResourceNullifierKey)?;
+            public_inputs.push(nf.inner());
+            let cm = output_resource.commitment();
+            public_inputs.push(cm.inner());
+        }
         public_inputs.push(self.get_owned_resource_id());
-        public_inputs
+        Ok(public_inputs)
     }
     fn get_input_resources(&self) -> &[Resource; NUM_RESOURCE];
     fn get_output_resources(&self) -> &[Resource; NUM_RESOURCE];
-    fn get_public_inputs(&self, rng: impl RngCore) -> ValidityPredicatePublicInputs;
+    fn get_public_inputs(
+        &self,
+        rng: impl RngCore,
+    ) -> Result<ValidityPredicatePublicInputs, TransactionError>;
     // The owned_resource_id is the input_resource_nf or the output_resource_cm_x
     // The owned_resource_id is the key to look up the target variables and
     // help determine whether the owned resource is the input resource or not in VP circuit.
@@ -749,62 +828,86 @@ macro_rules! vp_circuit_impl {
 macro_rules! vp_verifying_info_impl {
     ($name:ident) => {
         impl ValidityPredicateVerifyingInfo for $name {
-            fn get_verifying_info(&self) -> VPVerifyingInfo {
-                let mut rng = OsRng;
+            fn get_verifying_info_with_rng(
+                &self,
+                mut rng: &mut dyn rand::RngCore,
+            ) -> Result<VPVerifyingInfo, TransactionError> {
                 let params = SETUP_PARAMS_MAP.get(&15).unwrap();
-                let vk = keygen_vk(params, self).expect("keygen_vk should not fail");
-                let pk = keygen_pk(params, vk.clone(), self).expect("keygen_pk should not fail");
-                let public_inputs = self.get_public_inputs(&mut rng);
+                let vk = $crate::circuit::vp_key_cache::get_vp_circuit_vk(params, self)?;
+                let pk = $crate::circuit::vp_key_cache::get_vp_circuit_pk(params, &vk, self)?;
+                let public_inputs = self.get_public_inputs(&mut rng)?;
                 let proof = Proof::create(
                     &pk,
                     params,
                     self.clone(),
                     &[public_inputs.inner()],
                     &mut rng,
-                )
-                .unwrap();
-                VPVerifyingInfo {
+                )?;
+                Ok(VPVerifyingInfo {
                     vk,
                     proof,
                     public_inputs,
-                }
+                })
             }
 
             fn verify_transparently(
                 &self,
             ) -> Result<ValidityPredicatePublicInputs, TransactionError> {
-                use halo2_proofs::dev::MockProver;
                 let mut rng = OsRng;
-                let public_inputs = self.get_public_inputs(&mut rng);
-                let prover =
-                    MockProver::<pallas::Base>::run(15, self, vec![public_inputs.to_vec()])
-                        .unwrap();
-                prover.verify().unwrap();
+                let public_inputs = self.get_public_inputs(&mut rng)?;
+                $crate::circuit::vp_circuit::verify_vp_circuit_transparently(self, &public_inputs)?;
                 Ok(public_inputs)
             }
 
-            fn get_vp_vk(&self) -> ValidityPredicateVerifyingKey {
+            fn get_vp_vk(&self) -> Result<ValidityPredicateVerifyingKey, TransactionError> {
                 let params = SETUP_PARAMS_MAP.get(&15).unwrap();
-                let vk = keygen_vk(params, self).expect("keygen_vk should not fail");
-                ValidityPredicateVerifyingKey::from_vk(vk)
+                let vk = $crate::circuit::vp_key_cache::get_vp_circuit_vk(params, self)?;
+                Ok(ValidityPredicateVerifyingKey::from_vk(vk))
             }
         }
     };
 }
 
+/// Checks the VP circuit is satisfied by `public_inputs`, without creating a proof.
+pub fn verify_vp_circuit_transparently<C: Circuit<pallas::Base>>(
+    circuit: &C,
+    public_inputs: &ValidityPredicatePublicInputs,
+) -> Result<(), TransactionError> {
+    use halo2_proofs::dev::MockProver;
+    let prover = MockProver::<pallas::Base>::run(
+        VP_CIRCUIT_PARAMS_SIZE,
+        circuit,
+        vec![public_inputs.to_vec()],
+    )?;
+    prover.verify().map_err(|failures| {
+        TransactionError::ConstraintFailure(VPConstraintFailureReport::new(
+            &failures,
+            public_inputs,
+        ))
+    })
+}
+
+// Vamp-IR circuits are proven with the VP params, so they must fit in VP_CIRCUIT_PARAMS_SIZE.
+// The compiled `Halo2Module` is not Send, so the VP keeps the source and the variable assignments,
+// and compiles the circuit again whenever it's needed.
 #[derive(Clone)]
 pub struct VampIRValidityPredicateCircuit {
-    // TODO: vamp_ir doesn't support to set the params size manually, add the params here temporarily.
-    // remove the params once we can set it as VP_CIRCUIT_PARAMS_SIZE in vamp_ir.
-    pub params: Params<vesta::Affine>,
-    pub circuit: Halo2Module<pallas::Base>,
+    source: String,
+    assignments: HashMap<VariableId, Fp>,
+    public_input_names: Vec<String>,
     pub public_inputs: Vec<pallas::Base>,
+    // The digest of the source identifies the constraint system in the key cache.
+    source_digest: [u8; 32],
 }
 
 #[derive(Debug)]
 pub enum VampIRCircuitError {
     MissingAssignment(String),
     SourceParsingError(String),
+    CircuitTooLarge(u32),
+    FileError(String),
+    InvalidAssignments(String),
+    InvalidPublicInputLayout(String),
 }
 
 impl VampIRCircuitError {
@@ -822,80 +925,321 @@ impl VampIRValidityPredicateCircuit {
         vamp_ir_source: &str,
         named_field_assignments: HashMap<String, Fp>,
     ) -> Result<Self, VampIRCircuitError> {
-        let config = Config { quiet: true };
-        let parsed_vamp_ir_module =
-            parse(vamp_ir_source).map_err(VampIRCircuitError::SourceParsingError)?;
-        let vamp_ir_module = compile(
-            parsed_vamp_ir_module,
-            &PrimeFieldOps::<Fp>::default(),
-            &config,
-        );
-        let mut circuit = Halo2Module::<Fp>::new(Rc::new(vamp_ir_module));
-        let params = Params::new(circuit.k);
+        let circuit = Self::compile_circuit(vamp_ir_source)?;
         let field_assignments = get_circuit_assignments(&circuit.module, &named_field_assignments)
             .map_err(VampIRCircuitError::from_variable_assignment_error)?;
+        Self::new(vamp_ir_source, &circuit, field_assignments)
+    }
 
-        // Populate variable definitions
-        circuit.populate_variables(field_assignments.clone());
-
+    fn new(
+        vamp_ir_source: &str,
+        circuit: &Halo2Module<Fp>,
+        assignments: HashMap<VariableId, Fp>,
+    ) -> Result<Self, VampIRCircuitError> {
         // Get public inputs Fp
-        let public_inputs = circuit
+        let public_inputs = Self::get_public_inputs(circuit, &assignments)?;
+        let public_input_names = circuit
             .module
             .pubs
             .iter()
-            .map(|inst| field_assignments[&inst.id])
-            .collect::<Vec<pallas::Base>>();
+            .map(|var| var.name.clone().unwrap_or_default())
+            .collect();
 
         Ok(Self {
-            params,
-            circuit,
+            source: vamp_ir_source.to_string(),
+            assignments,
+            public_input_names,
             public_inputs,
+            source_digest: Self::source_digest(vamp_ir_source),
         })
     }
 
-    pub fn from_vamp_ir_file(vamp_ir_file: &PathBuf, inputs_file: &PathBuf) -> Self {
+    /// Compiles the circuit and populates its variables with the assignments.
+    pub fn circuit(&self) -> Result<Halo2Module<Fp>, VampIRCircuitError> {
+        let mut circuit = Self::compile_circuit(&self.source)?;
+        circuit.populate_variables(self.assignments.clone());
+        Ok(circuit)
+    }
+
+    fn compile_circuit(vamp_ir_source: &str) -> Result<Halo2Module<Fp>, VampIRCircuitError> {
         let config = Config { quiet: true };
-        let vamp_ir_source = fs::read_to_string(vamp_ir_file).expect("cannot read vamp-ir file");
-        let parsed_vamp_ir_module = Module::parse(&vamp_ir_source).unwrap();
+        let parsed_vamp_ir_module =
+            parse(vamp_ir_source).map_err(VampIRCircuitError::SourceParsingError)?;
         let vamp_ir_module = compile(
             parsed_vamp_ir_module,
             &PrimeFieldOps::<Fp>::default(),
             &config,
         );
-        let mut circuit = Halo2Module::<Fp>::new(Rc::new(vamp_ir_module));
-        let params: Params<EqAffine> = Params::new(circuit.k);
+        let circuit = Halo2Module::<Fp>::new(Rc::new(vamp_ir_module));
+        Self::check_circuit_size(&circuit)?;
+        Ok(circuit)
+    }
+
+    /// Builds a VP from its vamp-ir source, compiled after the Taiga prelude(see `vamp_ir_prelude`).
+    /// The assignments include the resource witnesses of the prelude.
+    pub fn from_vamp_ir_vp_source(
+        vamp_ir_vp_source: &str,
+        named_field_assignments: HashMap<String, Fp>,
+    ) -> Result<Self, VampIRCircuitError> {
+        let vamp_ir_source = format!("{}\n{}", vamp_ir_prelude(), vamp_ir_vp_source);
+        let vp_circuit = Self::from_vamp_ir_source(&vamp_ir_source, named_field_assignments)?;
+        vp_circuit.check_public_input_layout()?;
+        Ok(vp_circuit)
+    }
+
+    /// Builds a VP from the representation carried by a `ValidityPredicateByteCode`: the vamp-ir
+    /// source of the VP and the assignments encoded by `encode_assignments`.
+    pub fn from_vamp_ir_bytecode(
+        vamp_ir_vp_source: &str,
+        encoded_assignments: &[u8],
+    ) -> Result<Self, VampIRCircuitError> {
+        let named_field_assignments = Self::decode_assignments(encoded_assignments)?;
+        Self::from_vamp_ir_vp_source(vamp_ir_vp_source, named_field_assignments)
+    }
 
-        let var_assignments_ints = read_inputs_from_file(&circuit.module, inputs_file);
-        let mut var_assignments = HashMap::new();
-        for (k, v) in var_assignments_ints {
-            var_assignments.insert(k, make_constant(v));
+    /// Checks the public inputs start with the mandatory public inputs of the prelude, and leave
+    /// the resource encryption public inputs to the padding.
+    pub fn check_public_input_layout(&self) -> Result<(), VampIRCircuitError> {
+        let pubs: Vec<&str> = self.public_input_names.iter().map(String::as_str).collect();
+        let mandatory_public_inputs = vamp_ir_mandatory_public_inputs();
+        if pubs.len() < mandatory_public_inputs.len()
+            || pubs
+                .iter()
+                .zip(mandatory_public_inputs.iter())
+                .any(|(name, expected)| *name != expected.as_str())
+        {
+            return Err(VampIRCircuitError::InvalidPublicInputLayout(format!(
+                "the public inputs {pubs:?} don't start with {mandatory_public_inputs:?}"
+            )));
+        }
+        if pubs.len() > VP_CIRCUIT_RESOURCE_ENCRYPTION_PUBLIC_INPUT_BEGIN_IDX {
+            return Err(VampIRCircuitError::InvalidPublicInputLayout(format!(
+                "{} public inputs, at most {VP_CIRCUIT_RESOURCE_ENCRYPTION_PUBLIC_INPUT_BEGIN_IDX} are allowed",
+                pubs.len()
+            )));
         }
+        Ok(())
+    }
 
-        // Populate variable definitions
-        circuit.populate_variables(var_assignments.clone());
+    /// Encodes named assignments, sorted by name:
+    /// | Parameters            | type          | size(bytes)   |
+    /// |       -               |       -       |   -           |
+    /// | assignment num        | u32           | 4             |
+    /// | name len              | u32           | 4             |
+    /// | name                  | utf8          | name len      |
+    /// | value                 | pallas::Base  | 32            |
+    /// where the last three rows are repeated for every assignment.
+    pub fn encode_assignments(named_field_assignments: &HashMap<String, Fp>) -> Vec<u8> {
+        use ff::PrimeField;
+        let mut assignments: Vec<_> = named_field_assignments.iter().collect();
+        assignments.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&(assignments.len() as u32).to_le_bytes());
+        for (name, value) in assignments {
+            bytes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(&value.to_repr());
+        }
+        bytes
+    }
 
-        // Get public inputs Fp
-        let public_inputs = circuit
+    pub fn decode_assignments(bytes: &[u8]) -> Result<HashMap<String, Fp>, VampIRCircuitError> {
+        use ff::PrimeField;
+        fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], VampIRCircuitError> {
+            if bytes.len() < len {
+                return Err(VampIRCircuitError::InvalidAssignments(
+                    "unexpected end of the assignments".to_string(),
+                ));
+            }
+            let (head, tail) = bytes.split_at(len);
+            *bytes = tail;
+            Ok(head)
+        }
+        fn take_u32(bytes: &mut &[u8]) -> Result<usize, VampIRCircuitError> {
+            let mut le_bytes = [0u8; 4];
+            le_bytes.copy_from_slice(take(bytes, 4)?);
+            Ok(u32::from_le_bytes(le_bytes) as usize)
+        }
+
+        let mut bytes = bytes;
+        let num = take_u32(&mut bytes)?;
+        let mut named_field_assignments = HashMap::new();
+        for _ in 0..num {
+            let name_len = take_u32(&mut bytes)?;
+            let name = String::from_utf8(take(&mut bytes, name_len)?.to_vec())
+                .map_err(|e| VampIRCircuitError::InvalidAssignments(e.to_string()))?;
+            let mut repr = [0u8; 32];
+            repr.copy_from_slice(take(&mut bytes, 32)?);
+            let value = Option::<Fp>::from(Fp::from_repr(repr)).ok_or_else(|| {
+                VampIRCircuitError::InvalidAssignments(format!("{name} is not a field element"))
+            })?;
+            if named_field_assignments
+                .insert(name.clone(), value)
+                .is_some()
+            {
+                return Err(VampIRCircuitError::InvalidAssignments(format!(
+                    "{name} is assigned twice"
+                )));
+            }
+        }
+        if !bytes.is_empty() {
+            return Err(VampIRCircuitError::InvalidAssignments(
+                "unexpected bytes after the assignments".to_string(),
+            ));
+        }
+        Ok(named_field_assignments)
+    }
+
+    pub fn from_vamp_ir_file(
+        vamp_ir_file: &PathBuf,
+        inputs_file: &PathBuf,
+    ) -> Result<Self, VampIRCircuitError> {
+        let vamp_ir_source = fs::read_to_string(vamp_ir_file)
+            .map_err(|e| VampIRCircuitError::FileError(e.to_string()))?;
+        let circuit = Self::compile_circuit(&vamp_ir_source)?;
+
+        let inputs = fs::read_to_string(inputs_file)
+            .map_err(|e| VampIRCircuitError::FileError(e.to_string()))?;
+        let var_assignments = Self::parse_inputs(&inputs)?;
+        Self::new(&vamp_ir_source, &circuit, var_assignments)
+    }
+
+    // Parses a vamp-ir inputs file: a flat JSON object mapping variable names
+    // to integers, given either as strings or as bare numbers.
+    fn parse_inputs(inputs: &str) -> Result<HashMap<String, Fp>, VampIRCircuitError> {
+        fn invalid(msg: &str) -> VampIRCircuitError {
+            VampIRCircuitError::InvalidAssignments(msg.to_string())
+        }
+        fn skip_ws(chars: &mut std::iter::Peekable<std::str::Chars>) {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        }
+        fn expect(
+            chars: &mut std::iter::Peekable<std::str::Chars>,
+            expected: char,
+        ) -> Result<(), VampIRCircuitError> {
+            skip_ws(chars);
+            match chars.next() {
+                Some(c) if c == expected => Ok(()),
+                _ => Err(VampIRCircuitError::InvalidAssignments(format!(
+                    "expected '{expected}' in the inputs"
+                ))),
+            }
+        }
+        fn string(
+            chars: &mut std::iter::Peekable<std::str::Chars>,
+        ) -> Result<String, VampIRCircuitError> {
+            expect(chars, '"')?;
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => return Ok(s),
+                    Some('\\') | None => return Err(invalid("unsupported string in the inputs")),
+                    Some(c) => s.push(c),
+                }
+            }
+        }
+
+        let mut chars = inputs.chars().peekable();
+        let mut named_field_assignments = HashMap::new();
+        expect(&mut chars, '{')?;
+        skip_ws(&mut chars);
+        if chars.next_if_eq(&'}').is_none() {
+            loop {
+                let name = string(&mut chars)?;
+                expect(&mut chars, ':')?;
+                skip_ws(&mut chars);
+                let value = if chars.peek() == Some(&'"') {
+                    string(&mut chars)?
+                } else {
+                    let mut s = String::new();
+                    while let Some(c) = chars.next_if(|c| *c == '-' || c.is_ascii_digit()) {
+                        s.push(c);
+                    }
+                    s
+                };
+                let value = value.trim().parse::<num_bigint::BigInt>().map_err(|_| {
+                    VampIRCircuitError::InvalidAssignments(format!("{name} is not an integer"))
+                })?;
+                if named_field_assignments
+                    .insert(name.clone(), make_constant(value))
+                    .is_some()
+                {
+                    return Err(VampIRCircuitError::InvalidAssignments(format!(
+                        "{name} is assigned twice"
+                    )));
+                }
+                skip_ws(&mut chars);
+                match chars.next() {
+                    Some(',') => continue,
+                    Some('}') => break,
+                    _ => return Err(invalid("expected ',' or '}' in the inputs")),
+                }
+            }
+        }
+        skip_ws(&mut chars);
+        if chars.next().is_some() {
+            return Err(invalid("unexpected characters after the inputs"));
+        }
+        Ok(named_field_assignments)
+    }
+
+    fn get_public_inputs(
+        circuit: &Halo2Module<Fp>,
+        assignments: &HashMap<VariableId, Fp>,
+    ) -> Result<Vec<pallas::Base>, VampIRCircuitError> {
+        circuit
             .module
             .pubs
             .iter()
-            .map(|inst| var_assignments[&inst.id])
-            .collect::<Vec<pallas::Base>>();
+            .map(|var| {
+                assignments.get(&var.id).copied().ok_or_else(|| {
+                    VampIRCircuitError::MissingAssignment(var.name.clone().unwrap_or_default())
+                })
+            })
+            .collect()
+    }
 
-        Self {
-            params,
-            circuit,
-            public_inputs,
+    fn source_digest(vamp_ir_source: &str) -> [u8; 32] {
+        Blake2bParams::new()
+            .hash_length(32)
+            .personal(VAMP_IR_SOURCE_DIGEST_PERSONALIZATION)
+            .hash(vamp_ir_source.as_bytes())
+            .as_bytes()
+            .try_into()
+            .unwrap()
+    }
+
+    // The id of the constraint system in the key cache.
+    fn key_cache_id(&self) -> String {
+        let digest: String = self
+            .source_digest
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        format!("vamp-ir:{digest}")
+    }
+
+    // The circuit is laid out in the VP params, whose size is VP_CIRCUIT_PARAMS_SIZE.
+    fn check_circuit_size(circuit: &Halo2Module<Fp>) -> Result<(), VampIRCircuitError> {
+        if circuit.k > VP_CIRCUIT_PARAMS_SIZE {
+            return Err(VampIRCircuitError::CircuitTooLarge(circuit.k));
         }
+        Ok(())
     }
 }
 
 impl ValidityPredicateVerifyingInfo for VampIRValidityPredicateCircuit {
-    fn get_verifying_info(&self) -> VPVerifyingInfo {
-        let mut rng = OsRng;
-        let vk = keygen_vk(&self.params, &self.circuit).expect("keygen_vk should not fail");
-        let pk =
-            keygen_pk(&self.params, vk.clone(), &self.circuit).expect("keygen_pk should not fail");
+    fn get_verifying_info_with_rng(
+        &self,
+        mut rng: &mut dyn RngCore,
+    ) -> Result<VPVerifyingInfo, TransactionError> {
+        // The constraint system of a vamp-ir circuit depends on its source, so the vk is cached
+        // by source digest.
+        let circuit = self.circuit()?;
+        let params = SETUP_PARAMS_MAP.get(&VP_CIRCUIT_PARAMS_SIZE).unwrap();
+        let vk = get_vp_circuit_vk_with_id(params, &self.key_cache_id(), &circuit)?;
+        let pk = get_vp_circuit_pk(params, &vk, &circuit)?;
 
         let mut public_inputs = self.public_inputs.clone();
         let rseed = RandomSeed::random(&mut rng);
@@ -904,23 +1248,15 @@ impl ValidityPredicateVerifyingInfo for VampIRValidityPredicateCircuit {
             &rseed,
         ));
 
-        let proof = Proof::create(
-            &pk,
-            &self.params,
-            self.circuit.clone(),
-            &[&public_inputs.to_vec()],
-            &mut rng,
-        )
-        .unwrap();
-        VPVerifyingInfo {
+        let proof = Proof::create(&pk, params, circuit, &[&public_inputs.to_vec()], &mut rng)?;
+        Ok(VPVerifyingInfo {
             vk,
             proof,
             public_inputs: public_inputs.into(),
-        }
+        })
     }
 
     fn verify_transparently(&self) -> Result<ValidityPredicatePublicInputs, TransactionError> {
-        use halo2_proofs::dev::MockProver;
         let mut rng = OsRng;
         let mut public_inputs = self.public_inputs.clone();
         let rseed = RandomSeed::random(&mut rng);
@@ -928,23 +1264,23 @@ impl ValidityPredicateVerifyingInfo for VampIRValidityPredicateCircuit {
             self.public_inputs.len(),
             &rseed,
         ));
-        let prover =
-            MockProver::<pallas::Base>::run(15, &self.circuit, vec![public_inputs.to_vec()])
-                .unwrap();
-        prover.verify().unwrap();
-        Ok(ValidityPredicatePublicInputs::from(public_inputs))
+        let public_inputs = ValidityPredicatePublicInputs::from(public_inputs);
+        verify_vp_circuit_transparently(&self.circuit()?, &public_inputs)?;
+        Ok(public_inputs)
     }
 
-    fn get_vp_vk(&self) -> ValidityPredicateVerifyingKey {
-        let vk = keygen_vk(&self.params, &self.circuit).expect("keygen_vk should not fail");
-        ValidityPredicateVerifyingKey::from_vk(vk)
+    fn get_vp_vk(&self) -> Result<ValidityPredicateVerifyingKey, TransactionError> {
+        let params = SETUP_PARAMS_MAP.get(&VP_CIRCUIT_PARAMS_SIZE).unwrap();
+        let circuit = self.circuit()?;
+        let vk = get_vp_circuit_vk_with_id(params, &self.key_cache_id(), &circuit)?;
+        Ok(ValidityPredicateVerifyingKey::from_vk(vk))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::circuit::vp_circuit::{
-        ValidityPredicateVerifyingInfo, VampIRValidityPredicateCircuit,
+        ValidityPredicateVerifyingInfo, VampIRCircuitError, VampIRValidityPredicateCircuit,
     };
     use num_bigint::BigInt;
     use std::collections::HashMap;
@@ -957,21 +1293,14 @@ mod tests {
         let vamp_ir_circuit_file = PathBuf::from("./src/circuit/vamp_ir_circuits/pyth.pir");
         let inputs_file = PathBuf::from("./src/circuit/vamp_ir_circuits/pyth.inputs");
         let vp_circuit =
-            VampIRValidityPredicateCircuit::from_vamp_ir_file(&vamp_ir_circuit_file, &inputs_file);
+            VampIRValidityPredicateCircuit::from_vamp_ir_file(&vamp_ir_circuit_file, &inputs_file)
+                .unwrap();
 
         // generate proof and instance
-        let vp_info = vp_circuit.get_verifying_info();
+        let vp_info = vp_circuit.get_verifying_info().unwrap();
 
         // verify the proof
-        // TODO: use the vp_info.verify() instead. vp_info.verify() doesn't work now because it uses the fixed VP_CIRCUIT_PARAMS_SIZE params.
-        vp_info
-            .proof
-            .verify(
-                &vp_info.vk,
-                &vp_circuit.params,
-                &[vp_info.public_inputs.inner()],
-            )
-            .unwrap();
+        vp_info.verify().unwrap();
     }
 
     #[test]
@@ -981,6 +1310,39 @@ mod tests {
         assert!(invalid_vamp_ir_source.is_err());
     }
 
+    #[test]
+    fn test_parse_vamp_ir_inputs() {
+        let inputs = std::fs::read_to_string("./src/circuit/vamp_ir_circuits/pyth.inputs").unwrap();
+        let assignments = VampIRValidityPredicateCircuit::parse_inputs(&inputs).unwrap();
+        assert_eq!(
+            assignments,
+            HashMap::from([
+                (String::from("x"), make_constant(BigInt::from(15))),
+                (String::from("y"), make_constant(BigInt::from(20))),
+                (String::from("R"), make_constant(BigInt::from(25))),
+            ])
+        );
+
+        for malformed in [
+            "",
+            "{\"x\": \"1\"",
+            "{\"x\": \"a\"}",
+            "{\"x\": 1, \"x\": 2}",
+        ] {
+            assert!(VampIRValidityPredicateCircuit::parse_inputs(malformed).is_err());
+        }
+    }
+
+    #[test]
+    fn test_create_vp_from_missing_inputs_file() {
+        let vamp_ir_circuit_file = PathBuf::from("./src/circuit/vamp_ir_circuits/pyth.pir");
+        let inputs_file = PathBuf::from("./src/circuit/vamp_ir_circuits/missing.inputs");
+        assert!(matches!(
+            VampIRValidityPredicateCircuit::from_vamp_ir_file(&vamp_ir_circuit_file, &inputs_file),
+            Err(VampIRCircuitError::FileError(_))
+        ));
+    }
+
     #[test]
     fn test_create_vp_with_missing_assignment() {
         let missing_x_assignment =
@@ -1006,16 +1368,9 @@ mod tests {
         assert!(x_assignment_circuit.is_ok());
 
         let vp_circuit = x_assignment_circuit.unwrap();
-        let vp_info = vp_circuit.get_verifying_info();
+        let vp_info = vp_circuit.get_verifying_info().unwrap();
 
-        assert!(vp_info
-            .proof
-            .verify(
-                &vp_info.vk,
-                &vp_circuit.params,
-                &[vp_info.public_inputs.inner()]
-            )
-            .is_ok());
+        assert!(vp_info.verify().is_ok());
     }
 
     #[ignore]
@@ -1029,16 +1384,65 @@ mod tests {
         assert!(x_assignment_circuit.is_ok());
 
         let vp_circuit = x_assignment_circuit.unwrap();
-        let vp_info = vp_circuit.get_verifying_info();
+        let vp_info = vp_circuit.get_verifying_info().unwrap();
+
+        assert!(vp_info.verify().is_err());
+    }
+
+    #[test]
+    fn test_create_vp_from_vamp_ir_bytecode() {
+        let assignments = HashMap::from([
+            (String::from("x"), make_constant(BigInt::from(15))),
+            (String::from("y"), make_constant(BigInt::from(20))),
+            (String::from("R"), make_constant(BigInt::from(25))),
+        ]);
+        let encoded_assignments = VampIRValidityPredicateCircuit::encode_assignments(&assignments);
+        assert_eq!(
+            VampIRValidityPredicateCircuit::decode_assignments(&encoded_assignments).unwrap(),
+            assignments
+        );
+        assert!(VampIRValidityPredicateCircuit::decode_assignments(
+            &encoded_assignments[..encoded_assignments.len() - 1]
+        )
+        .is_err());
+
+        // pyth doesn't have the mandatory public inputs.
+        let vp_circuit = VampIRValidityPredicateCircuit::from_vamp_ir_source(
+            include_str!("vamp_ir_circuits/pyth.pir"),
+            assignments,
+        )
+        .unwrap();
+        assert!(vp_circuit.check_public_input_layout().is_err());
+        assert!(matches!(
+            VampIRValidityPredicateCircuit::from_vamp_ir_bytecode(
+                include_str!("vamp_ir_circuits/pyth.pir"),
+                &encoded_assignments,
+            ),
+            Err(VampIRCircuitError::InvalidPublicInputLayout(_))
+        ));
+    }
+
+    #[test]
+    fn test_vamp_ir_key_cache_id() {
+        let source = include_str!("vamp_ir_circuits/pyth.pir");
+        let assignments = |x: i32| {
+            HashMap::from([
+                (String::from("x"), make_constant(BigInt::from(x))),
+                (String::from("y"), make_constant(BigInt::from(20))),
+                (String::from("R"), make_constant(BigInt::from(25))),
+            ])
+        };
+        let vp_circuit =
+            VampIRValidityPredicateCircuit::from_vamp_ir_source(source, assignments(15)).unwrap();
 
-        assert!(vp_info
-            .proof
-            .verify(
-                &vp_info.vk,
-                &vp_circuit.params,
-                &[vp_info.public_inputs.inner()]
-            )
-            .is_err());
+        // The vk only depends on the source, not on the assignments.
+        let other_assignments =
+            VampIRValidityPredicateCircuit::from_vamp_ir_source(source, assignments(7)).unwrap();
+        assert_eq!(vp_circuit.key_cache_id(), other_assignments.key_cache_id());
+
+        let other_source =
+            VampIRValidityPredicateCircuit::from_vamp_ir_source("0;", HashMap::new()).unwrap();
+        assert_ne!(vp_circuit.key_cache_id(), other_source.key_cache_id());
     }
 
     #[cfg(feature = "serde")]
@@ -1061,7 +1465,9 @@ mod tests {
             vk: VerifyingKey<vesta::Affine>,
         }
 
-        let t = TrivialValidityPredicateCircuit::default().get_vp_vk();
+        let t = TrivialValidityPredicateCircuit::default()
+            .get_vp_vk()
+            .unwrap();
 
         let a = TestStruct {
             vk: t.get_vk().unwrap(),
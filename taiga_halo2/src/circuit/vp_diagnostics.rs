@@ -0,0 +1,352 @@
+//! Diagnostics of the VP circuits failing transparent verification.
+//!
+//! The `VerifyFailure`s of the mock prover are turned into a report naming the failing regions
+//! and gates, and mapping the mismatched public inputs to their meaning in the VP public input
+//! layout and to the resource they belong to.
+
+use crate::{
+    circuit::vp_circuit::ValidityPredicatePublicInputs,
+    constant::{
+        vp_circuit_nullifier_public_input_idx, vp_circuit_output_cm_public_input_idx, NUM_RESOURCE,
+        VP_CIRCUIT_CUSTOM_PUBLIC_INPUT_BEGIN_IDX, VP_CIRCUIT_DYNAMIC_VP_CM_1,
+        VP_CIRCUIT_DYNAMIC_VP_CM_2, VP_CIRCUIT_OWNED_RESOURCE_ID_PUBLIC_INPUT_IDX,
+        VP_CIRCUIT_PUBLIC_INPUT_NUM, VP_CIRCUIT_RESOURCE_ENCRYPTION_PUBLIC_INPUT_BEGIN_IDX,
+    },
+};
+use halo2_proofs::{
+    dev::{metadata, FailureLocation, VerifyFailure},
+    plonk::Any,
+};
+use pasta_curves::pallas;
+use std::fmt;
+
+/// A resource of a partial transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResourceSlot {
+    Input(usize),
+    Output(usize),
+}
+
+/// The meaning of a VP public input in the `VP_CIRCUIT_*_IDX` layout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VPPublicInputKind {
+    /// The nullifier of the `i`-th input resource.
+    InputNullifier(usize),
+    /// The commitment of the `i`-th output resource.
+    OutputCommitment(usize),
+    OwnedResourceId,
+    /// The `i`-th half of the dynamic vp commitment.
+    DynamicVPCommitment(usize),
+    /// The `i`-th custom public input.
+    Custom(usize),
+    /// The `i`-th resource encryption public input.
+    ResourceEncryption(usize),
+}
+
+impl VPPublicInputKind {
+    pub fn from_index(idx: usize) -> Option<Self> {
+        if idx >= VP_CIRCUIT_PUBLIC_INPUT_NUM {
+            return None;
+        }
+        let kind = (0..NUM_RESOURCE)
+            .find_map(|i| {
+                if idx == vp_circuit_nullifier_public_input_idx(i) {
+                    Some(Self::InputNullifier(i))
+                } else if idx == vp_circuit_output_cm_public_input_idx(i) {
+                    Some(Self::OutputCommitment(i))
+                } else {
+                    None
+                }
+            })
+            .unwrap_or(match idx {
+                VP_CIRCUIT_OWNED_RESOURCE_ID_PUBLIC_INPUT_IDX => Self::OwnedResourceId,
+                VP_CIRCUIT_DYNAMIC_VP_CM_1 => Self::DynamicVPCommitment(0),
+                VP_CIRCUIT_DYNAMIC_VP_CM_2 => Self::DynamicVPCommitment(1),
+                idx if idx >= VP_CIRCUIT_RESOURCE_ENCRYPTION_PUBLIC_INPUT_BEGIN_IDX => {
+                    Self::ResourceEncryption(
+                        idx - VP_CIRCUIT_RESOURCE_ENCRYPTION_PUBLIC_INPUT_BEGIN_IDX,
+                    )
+                }
+                idx => Self::Custom(idx - VP_CIRCUIT_CUSTOM_PUBLIC_INPUT_BEGIN_IDX),
+            });
+        Some(kind)
+    }
+
+    /// The resource the public input belongs to, if any.
+    pub fn resource(&self) -> Option<ResourceSlot> {
+        match self {
+            Self::InputNullifier(i) => Some(ResourceSlot::Input(*i)),
+            Self::OutputCommitment(i) => Some(ResourceSlot::Output(*i)),
+            _ => None,
+        }
+    }
+}
+
+/// A constraint of a VP circuit that isn't satisfied.
+#[derive(Clone, Debug)]
+pub enum VPConstraintFailure {
+    /// A gate constraint isn't satisfied, with the values of the cells it queries.
+    Gate {
+        constraint: String,
+        location: String,
+        cell_values: Vec<(String, String)>,
+    },
+    /// A cell queried by a gate isn't assigned.
+    UnassignedCell {
+        region: String,
+        gate: String,
+        column: String,
+        offset: isize,
+    },
+    /// A constraint is evaluated on rows where the circuit is poisoned.
+    PoisonedConstraint { constraint: String },
+    Lookup {
+        lookup_index: usize,
+        location: String,
+    },
+    /// The public input doesn't match the value computed by the circuit.
+    PublicInput {
+        index: usize,
+        kind: VPPublicInputKind,
+        value: pallas::Base,
+    },
+    /// An equality constraint between two cells isn't satisfied.
+    Permutation { column: String, location: String },
+}
+
+impl VPConstraintFailure {
+    pub fn new(failure: &VerifyFailure, public_inputs: &ValidityPredicatePublicInputs) -> Self {
+        match failure {
+            VerifyFailure::CellNotAssigned {
+                gate,
+                region,
+                column,
+                offset,
+                ..
+            } => Self::UnassignedCell {
+                region: region.to_string(),
+                gate: gate.to_string(),
+                column: format!("{column:?}"),
+                offset: *offset,
+            },
+            VerifyFailure::ConstraintNotSatisfied {
+                constraint,
+                location,
+                cell_values,
+            } => Self::Gate {
+                constraint: constraint.to_string(),
+                location: location.to_string(),
+                cell_values: cell_values
+                    .iter()
+                    .map(|(cell, value)| (format!("{cell:?}"), value.clone()))
+                    .collect(),
+            },
+            VerifyFailure::ConstraintPoisoned { constraint } => Self::PoisonedConstraint {
+                constraint: constraint.to_string(),
+            },
+            VerifyFailure::Lookup {
+                lookup_index,
+                location,
+            } => Self::Lookup {
+                lookup_index: *lookup_index,
+                location: location.to_string(),
+            },
+            VerifyFailure::Permutation { column, location } => {
+                // The instance cells are outside of the regions, on the row of their index.
+                if *column == metadata::Column::from((Any::Instance, 0)) {
+                    if let FailureLocation::OutsideRegion { row } = location {
+                        if let Some(kind) = VPPublicInputKind::from_index(*row) {
+                            return Self::PublicInput {
+                                index: *row,
+                                kind,
+                                value: public_inputs.get_from_index(*row),
+                            };
+                        }
+                    }
+                }
+                Self::Permutation {
+                    column: column.to_string(),
+                    location: location.to_string(),
+                }
+            }
+        }
+    }
+}
+
+/// The constraints of a VP circuit that aren't satisfied.
+#[derive(Clone, Debug)]
+pub struct VPConstraintFailureReport {
+    /// The resource whose VP fails, if known.
+    pub owner: Option<ResourceSlot>,
+    pub failures: Vec<VPConstraintFailure>,
+}
+
+impl VPConstraintFailureReport {
+    pub fn new(failures: &[VerifyFailure], public_inputs: &ValidityPredicatePublicInputs) -> Self {
+        Self {
+            owner: None,
+            failures: failures
+                .iter()
+                .map(|failure| VPConstraintFailure::new(failure, public_inputs))
+                .collect(),
+        }
+    }
+
+    pub fn with_owner(self, owner: ResourceSlot) -> Self {
+        Self {
+            owner: Some(owner),
+            ..self
+        }
+    }
+
+    /// The public inputs that don't match the values computed by the circuit.
+    pub fn mismatched_public_inputs(&self) -> Vec<VPPublicInputKind> {
+        self.failures
+            .iter()
+            .filter_map(|failure| match failure {
+                VPConstraintFailure::PublicInput { kind, .. } => Some(*kind),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for ResourceSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Input(i) => f.write_str(&format!("input resource {i}")),
+            Self::Output(i) => f.write_str(&format!("output resource {i}")),
+        }
+    }
+}
+
+impl fmt::Display for VPPublicInputKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InputNullifier(i) => f.write_str(&format!("nullifier of input resource {i}")),
+            Self::OutputCommitment(i) => f.write_str(&format!("commitment of output resource {i}")),
+            Self::OwnedResourceId => f.write_str("owned resource id"),
+            Self::DynamicVPCommitment(i) => f.write_str(&format!("dynamic vp commitment {i}")),
+            Self::Custom(i) => f.write_str(&format!("custom public input {i}")),
+            Self::ResourceEncryption(i) => {
+                f.write_str(&format!("resource encryption public input {i}"))
+            }
+        }
+    }
+}
+
+impl fmt::Display for VPConstraintFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Gate {
+                constraint,
+                location,
+                cell_values,
+            } => {
+                f.write_str(&format!("{constraint} is not satisfied {location}"))?;
+                for (cell, value) in cell_values {
+                    f.write_str(&format!(", {cell} = {value}"))?;
+                }
+                Ok(())
+            }
+            Self::UnassignedCell {
+                region,
+                gate,
+                column,
+                offset,
+            } => f.write_str(&format!(
+                "{region} uses {gate}, which queries the unassigned cell {column} at offset {offset}"
+            )),
+            Self::PoisonedConstraint { constraint } => {
+                f.write_str(&format!("{constraint} is poisoned"))
+            }
+            Self::Lookup {
+                lookup_index,
+                location,
+            } => f.write_str(&format!("Lookup {lookup_index} is not satisfied {location}")),
+            Self::PublicInput { index, kind, value } => f.write_str(&format!(
+                "Public input {index}({kind}) {value:?} doesn't match the circuit"
+            )),
+            Self::Permutation { column, location } => f.write_str(&format!(
+                "Equality constraint on {column} is not satisfied {location}"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for VPConstraintFailureReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.owner {
+            Some(owner) => f.write_str(&format!("The VP of the {owner} is not satisfied:"))?,
+            None => f.write_str("The VP is not satisfied:")?,
+        }
+        for failure in self.failures.iter() {
+            f.write_str(&format!("\n  {failure}"))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::{
+        vp_circuit::{verify_vp_circuit_transparently, ValidityPredicateCircuit},
+        vp_examples::tests::random_trivial_vp_circuit,
+    };
+    use crate::error::TransactionError;
+    use halo2_proofs::arithmetic::Field;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn test_vp_public_input_kind() {
+        for i in 0..NUM_RESOURCE {
+            let nf_kind =
+                VPPublicInputKind::from_index(vp_circuit_nullifier_public_input_idx(i)).unwrap();
+            assert_eq!(nf_kind, VPPublicInputKind::InputNullifier(i));
+            assert_eq!(nf_kind.resource(), Some(ResourceSlot::Input(i)));
+            assert_eq!(
+                VPPublicInputKind::from_index(vp_circuit_output_cm_public_input_idx(i)),
+                Some(VPPublicInputKind::OutputCommitment(i))
+            );
+        }
+        assert_eq!(
+            VPPublicInputKind::from_index(VP_CIRCUIT_OWNED_RESOURCE_ID_PUBLIC_INPUT_IDX),
+            Some(VPPublicInputKind::OwnedResourceId)
+        );
+        assert_eq!(
+            VPPublicInputKind::from_index(VP_CIRCUIT_CUSTOM_PUBLIC_INPUT_BEGIN_IDX),
+            Some(VPPublicInputKind::Custom(0))
+        );
+        assert_eq!(
+            VPPublicInputKind::from_index(VP_CIRCUIT_RESOURCE_ENCRYPTION_PUBLIC_INPUT_BEGIN_IDX),
+            Some(VPPublicInputKind::ResourceEncryption(0))
+        );
+        assert_eq!(
+            VPPublicInputKind::from_index(VP_CIRCUIT_PUBLIC_INPUT_NUM),
+            None
+        );
+    }
+
+    #[test]
+    fn test_public_input_mismatch_report() {
+        let mut rng = OsRng;
+        let vp = random_trivial_vp_circuit(&mut rng);
+        let mut public_inputs = vp.get_public_inputs(&mut rng).unwrap().to_vec();
+        public_inputs[vp_circuit_output_cm_public_input_idx(1)] = pallas::Base::random(&mut rng);
+
+        match verify_vp_circuit_transparently(&vp, &public_inputs.into()) {
+            Err(TransactionError::ConstraintFailure(report)) => {
+                assert_eq!(
+                    report.mismatched_public_inputs(),
+                    vec![VPPublicInputKind::OutputCommitment(1)]
+                );
+                assert_eq!(
+                    report.with_owner(ResourceSlot::Output(1)).owner,
+                    Some(ResourceSlot::Output(1))
+                );
+            }
+            _ => panic!("the public input mismatch is not reported"),
+        }
+    }
+}
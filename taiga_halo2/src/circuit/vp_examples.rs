@@ -1,9 +1,11 @@
 #[cfg(feature = "borsh")]
 use crate::circuit::vp_bytecode::{ValidityPredicateByteCode, ValidityPredicateRepresentation};
+#[cfg(feature = "borsh")]
+use crate::circuit::vp_registry::TRIVIAL_VP_CIRCUIT_ID;
 use crate::{
     circuit::vp_circuit::{
-        VPVerifyingInfo, ValidityPredicateCircuit, ValidityPredicateConfig,
-        ValidityPredicatePublicInputs, ValidityPredicateVerifyingInfo,
+        verify_vp_circuit_transparently, VPVerifyingInfo, ValidityPredicateCircuit,
+        ValidityPredicateConfig, ValidityPredicatePublicInputs, ValidityPredicateVerifyingInfo,
     },
     constant::{NUM_RESOURCE, SETUP_PARAMS_MAP, VP_CIRCUIT_PARAMS_SIZE},
     error::TransactionError,
@@ -90,7 +92,10 @@ impl TrivialValidityPredicateCircuit {
     // Only for test
     #[cfg(feature = "borsh")]
     pub fn to_bytecode(&self) -> ValidityPredicateByteCode {
-        ValidityPredicateByteCode::new(ValidityPredicateRepresentation::Trivial, self.to_bytes())
+        ValidityPredicateByteCode::new(
+            ValidityPredicateRepresentation::Native(TRIVIAL_VP_CIRCUIT_ID.to_string()),
+            self.to_bytes(),
+        )
     }
 
     // Only for test
@@ -101,8 +106,9 @@ impl TrivialValidityPredicateCircuit {
 
     // Only for test
     #[cfg(feature = "borsh")]
-    pub fn from_bytes(bytes: &Vec<u8>) -> Self {
-        BorshDeserialize::deserialize(&mut bytes.as_ref()).unwrap()
+    pub fn from_bytes(bytes: &Vec<u8>) -> Result<Self, TransactionError> {
+        BorshDeserialize::try_from_slice(bytes)
+            .map_err(|e| TransactionError::InvalidValidityPredicateInputs(e.to_string()))
     }
 
     fn to_proxy(&self) -> TrivialValidtyPredicateCircuitProxy {
@@ -184,18 +190,20 @@ impl ValidityPredicateCircuit for TrivialValidityPredicateCircuit {
         &self.output_resources
     }
 
-    fn get_public_inputs(&self, mut rng: impl RngCore) -> ValidityPredicatePublicInputs {
-        let mut public_inputs = self.get_mandatory_public_inputs();
+    fn get_public_inputs(
+        &self,
+        mut rng: impl RngCore,
+    ) -> Result<ValidityPredicatePublicInputs, TransactionError> {
+        let mut public_inputs = self.get_mandatory_public_inputs()?;
         let default_vp_cm: [pallas::Base; 2] =
             ValidityPredicateCommitment::default().to_public_inputs();
         public_inputs.extend(default_vp_cm);
-        public_inputs.extend(default_vp_cm);
         let padding = ValidityPredicatePublicInputs::get_public_input_padding(
             public_inputs.len(),
             &RandomSeed::random(&mut rng),
         );
         public_inputs.extend(padding);
-        public_inputs.into()
+        Ok(public_inputs.into())
     }
 
     fn get_owned_resource_id(&self) -> pallas::Base {
@@ -206,37 +214,35 @@ impl ValidityPredicateCircuit for TrivialValidityPredicateCircuit {
 vp_circuit_impl!(TrivialValidityPredicateCircuit);
 
 impl ValidityPredicateVerifyingInfo for TrivialValidityPredicateCircuit {
-    fn get_verifying_info(&self) -> VPVerifyingInfo {
-        let mut rng = OsRng;
+    fn get_verifying_info_with_rng(
+        &self,
+        mut rng: &mut dyn RngCore,
+    ) -> Result<VPVerifyingInfo, TransactionError> {
         let params = SETUP_PARAMS_MAP.get(&15).unwrap();
-        let public_inputs = self.get_public_inputs(&mut rng);
+        let public_inputs = self.get_public_inputs(&mut rng)?;
         let proof = Proof::create(
             &TRIVIAL_VP_PK,
             params,
             self.clone(),
             &[public_inputs.inner()],
             &mut rng,
-        )
-        .unwrap();
-        VPVerifyingInfo {
+        )?;
+        Ok(VPVerifyingInfo {
             vk: TRIVIAL_VP_PK.get_vk().clone(),
             proof,
             public_inputs,
-        }
+        })
     }
 
     fn verify_transparently(&self) -> Result<ValidityPredicatePublicInputs, TransactionError> {
-        use halo2_proofs::dev::MockProver;
         let mut rng = OsRng;
-        let public_inputs = self.get_public_inputs(&mut rng);
-        let prover =
-            MockProver::<pallas::Base>::run(15, self, vec![public_inputs.to_vec()]).unwrap();
-        prover.verify().unwrap();
+        let public_inputs = self.get_public_inputs(&mut rng)?;
+        verify_vp_circuit_transparently(self, &public_inputs)?;
         Ok(public_inputs)
     }
 
-    fn get_vp_vk(&self) -> ValidityPredicateVerifyingKey {
-        TRIVIAL_VP_VK.clone()
+    fn get_vp_vk(&self) -> Result<ValidityPredicateVerifyingKey, TransactionError> {
+        Ok(TRIVIAL_VP_VK.clone())
     }
 }
 
@@ -263,7 +269,7 @@ pub mod tests {
 
         let mut rng = OsRng;
         let circuit = random_trivial_vp_circuit(&mut rng);
-        let public_inputs = circuit.get_public_inputs(&mut rng);
+        let public_inputs = circuit.get_public_inputs(&mut rng).unwrap();
 
         let prover = MockProver::<pallas::Base>::run(
             VP_CIRCUIT_PARAMS_SIZE,
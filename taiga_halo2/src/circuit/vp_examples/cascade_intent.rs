@@ -6,7 +6,7 @@
 ///
 use crate::{
     circuit::{
-        blake2s::publicize_default_dynamic_vp_commitments,
+        blake2s::publicize_default_dynamic_vp_commitment,
         gadgets::{
             assign_free_advice,
             target_resource_variable::{get_is_input_resource_flag, get_owned_resource_variable},
@@ -16,6 +16,7 @@ use crate::{
             BasicValidityPredicateVariables, VPVerifyingInfo, ValidityPredicateCircuit,
             ValidityPredicateConfig, ValidityPredicatePublicInputs, ValidityPredicateVerifyingInfo,
         },
+        vp_registry::CASCADE_INTENT_VP_CIRCUIT_ID,
     },
     constant::{NUM_RESOURCE, SETUP_PARAMS_MAP},
     error::TransactionError,
@@ -30,7 +31,7 @@ use borsh::{BorshDeserialize, BorshSerialize};
 use halo2_proofs::arithmetic::Field;
 use halo2_proofs::{
     circuit::{floor_planner, Layouter, Value},
-    plonk::{keygen_pk, keygen_vk, Circuit, ConstraintSystem, Error},
+    plonk::{Circuit, ConstraintSystem, Error},
 };
 use lazy_static::lazy_static;
 use pasta_curves::{group::ff::PrimeField, pallas};
@@ -39,7 +40,9 @@ use rand::RngCore;
 
 lazy_static! {
     pub static ref CASCADE_INTENT_VK: ValidityPredicateVerifyingKey =
-        CascadeIntentValidityPredicateCircuit::default().get_vp_vk();
+        CascadeIntentValidityPredicateCircuit::default()
+            .get_vp_vk()
+            .unwrap();
     pub static ref COMPRESSED_CASCADE_INTENT_VK: pallas::Base = CASCADE_INTENT_VK.get_compressed();
 }
 
@@ -61,7 +64,7 @@ impl CascadeIntentValidityPredicateCircuit {
 
     pub fn to_bytecode(&self) -> ValidityPredicateByteCode {
         ValidityPredicateByteCode::new(
-            ValidityPredicateRepresentation::CascadeIntent,
+            ValidityPredicateRepresentation::Native(CASCADE_INTENT_VP_CIRCUIT_ID.to_string()),
             self.to_bytes(),
         )
     }
@@ -70,8 +73,9 @@ impl CascadeIntentValidityPredicateCircuit {
         borsh::to_vec(&self).unwrap()
     }
 
-    pub fn from_bytes(bytes: &Vec<u8>) -> Self {
-        BorshDeserialize::deserialize(&mut bytes.as_ref()).unwrap()
+    pub fn from_bytes(bytes: &Vec<u8>) -> Result<Self, TransactionError> {
+        BorshDeserialize::try_from_slice(bytes)
+            .map_err(|e| TransactionError::InvalidValidityPredicateInputs(e.to_string()))
     }
 }
 
@@ -127,8 +131,8 @@ impl ValidityPredicateCircuit for CascadeIntentValidityPredicateCircuit {
             },
         )?;
 
-        // Publicize the dynamic vp commitments with default value
-        publicize_default_dynamic_vp_commitments(
+        // Publicize the dynamic vp commitment with default value
+        publicize_default_dynamic_vp_commitment(
             &mut layouter,
             config.advices[0],
             config.instances,
@@ -145,18 +149,20 @@ impl ValidityPredicateCircuit for CascadeIntentValidityPredicateCircuit {
         &self.output_resources
     }
 
-    fn get_public_inputs(&self, mut rng: impl RngCore) -> ValidityPredicatePublicInputs {
-        let mut public_inputs = self.get_mandatory_public_inputs();
+    fn get_public_inputs(
+        &self,
+        mut rng: impl RngCore,
+    ) -> Result<ValidityPredicatePublicInputs, TransactionError> {
+        let mut public_inputs = self.get_mandatory_public_inputs()?;
         let default_vp_cm: [pallas::Base; 2] =
             ValidityPredicateCommitment::default().to_public_inputs();
         public_inputs.extend(default_vp_cm);
-        public_inputs.extend(default_vp_cm);
         let padding = ValidityPredicatePublicInputs::get_public_input_padding(
             public_inputs.len(),
             &RandomSeed::random(&mut rng),
         );
         public_inputs.extend(padding);
-        public_inputs.into()
+        Ok(public_inputs.into())
     }
 
     fn get_owned_resource_id(&self) -> pallas::Base {
@@ -250,10 +256,10 @@ fn test_halo2_cascade_intent_vp_circuit() {
     // Test serialization
     let circuit = {
         let circuit_bytes = circuit.to_bytes();
-        CascadeIntentValidityPredicateCircuit::from_bytes(&circuit_bytes)
+        CascadeIntentValidityPredicateCircuit::from_bytes(&circuit_bytes).unwrap()
     };
 
-    let public_inputs = circuit.get_public_inputs(&mut rng);
+    let public_inputs = circuit.get_public_inputs(&mut rng).unwrap();
 
     let prover = MockProver::<pallas::Base>::run(
         VP_CIRCUIT_PARAMS_SIZE,
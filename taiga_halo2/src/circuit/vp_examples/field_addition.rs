@@ -1,6 +1,6 @@
 use crate::{
     circuit::{
-        blake2s::publicize_default_dynamic_vp_commitments,
+        blake2s::publicize_default_dynamic_vp_commitment,
         gadgets::{
             add::{AddChip, AddInstructions},
             assign_free_advice,
@@ -19,7 +19,7 @@ use crate::{
 };
 use halo2_proofs::{
     circuit::{floor_planner, Layouter, Value},
-    plonk::{keygen_pk, keygen_vk, Circuit, ConstraintSystem, Error},
+    plonk::{Circuit, ConstraintSystem, Error},
 };
 use pasta_curves::pallas;
 use rand::rngs::OsRng;
@@ -67,8 +67,8 @@ impl ValidityPredicateCircuit for FieldAdditionValidityPredicateCircuit {
             VP_CIRCUIT_CUSTOM_PUBLIC_INPUT_BEGIN_IDX,
         )?;
 
-        // Publicize the dynamic vp commitments with default value
-        publicize_default_dynamic_vp_commitments(
+        // Publicize the dynamic vp commitment with default value
+        publicize_default_dynamic_vp_commitment(
             &mut layouter,
             config.advices[0],
             config.instances,
@@ -85,19 +85,21 @@ impl ValidityPredicateCircuit for FieldAdditionValidityPredicateCircuit {
         &self.output_resources
     }
 
-    fn get_public_inputs(&self, mut rng: impl RngCore) -> ValidityPredicatePublicInputs {
-        let mut public_inputs = self.get_mandatory_public_inputs();
+    fn get_public_inputs(
+        &self,
+        mut rng: impl RngCore,
+    ) -> Result<ValidityPredicatePublicInputs, TransactionError> {
+        let mut public_inputs = self.get_mandatory_public_inputs()?;
         let default_vp_cm: [pallas::Base; 2] =
             ValidityPredicateCommitment::default().to_public_inputs();
         public_inputs.extend(default_vp_cm);
-        public_inputs.extend(default_vp_cm);
         public_inputs.push(self.a + self.b);
         let padding = ValidityPredicatePublicInputs::get_public_input_padding(
             public_inputs.len(),
             &RandomSeed::random(&mut rng),
         );
         public_inputs.extend(padding);
-        public_inputs.into()
+        Ok(public_inputs.into())
     }
 
     fn get_owned_resource_id(&self) -> pallas::Base {
@@ -131,7 +133,7 @@ fn test_halo2_addition_vp_circuit() {
             b,
         }
     };
-    let public_inputs = circuit.get_public_inputs(&mut rng);
+    let public_inputs = circuit.get_public_inputs(&mut rng).unwrap();
 
     let prover = MockProver::<pallas::Base>::run(
         VP_CIRCUIT_PARAMS_SIZE,
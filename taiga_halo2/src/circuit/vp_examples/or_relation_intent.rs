@@ -4,7 +4,7 @@
 ///
 use crate::{
     circuit::{
-        blake2s::publicize_default_dynamic_vp_commitments,
+        blake2s::publicize_default_dynamic_vp_commitment,
         gadgets::{
             assign_free_advice,
             poseidon_hash::poseidon_hash_gadget,
@@ -16,6 +16,7 @@ use crate::{
             ValidityPredicateConfig, ValidityPredicatePublicInputs, ValidityPredicateVerifyingInfo,
         },
         vp_examples::token::{Token, TOKEN_VK},
+        vp_registry::OR_RELATION_INTENT_VP_CIRCUIT_ID,
     },
     constant::{NUM_RESOURCE, SETUP_PARAMS_MAP},
     error::TransactionError,
@@ -31,7 +32,7 @@ use borsh::{BorshDeserialize, BorshSerialize};
 use halo2_proofs::arithmetic::Field;
 use halo2_proofs::{
     circuit::{floor_planner, Layouter, Value},
-    plonk::{keygen_pk, keygen_vk, Circuit, ConstraintSystem, Error},
+    plonk::{Circuit, ConstraintSystem, Error},
 };
 use lazy_static::lazy_static;
 use pasta_curves::{group::ff::PrimeField, pallas};
@@ -40,7 +41,9 @@ use rand::RngCore;
 
 lazy_static! {
     pub static ref OR_RELATION_INTENT_VK: ValidityPredicateVerifyingKey =
-        OrRelationIntentValidityPredicateCircuit::default().get_vp_vk();
+        OrRelationIntentValidityPredicateCircuit::default()
+            .get_vp_vk()
+            .unwrap();
     pub static ref COMPRESSED_OR_RELATION_INTENT_VK: pallas::Base =
         OR_RELATION_INTENT_VK.get_compressed();
 }
@@ -81,7 +84,7 @@ impl OrRelationIntentValidityPredicateCircuit {
 
     pub fn to_bytecode(&self) -> ValidityPredicateByteCode {
         ValidityPredicateByteCode::new(
-            ValidityPredicateRepresentation::OrRelationIntent,
+            ValidityPredicateRepresentation::Native(OR_RELATION_INTENT_VP_CIRCUIT_ID.to_string()),
             self.to_bytes(),
         )
     }
@@ -90,8 +93,9 @@ impl OrRelationIntentValidityPredicateCircuit {
         borsh::to_vec(&self).unwrap()
     }
 
-    pub fn from_bytes(bytes: &Vec<u8>) -> Self {
-        BorshDeserialize::deserialize(&mut bytes.as_ref()).unwrap()
+    pub fn from_bytes(bytes: &Vec<u8>) -> Result<Self, TransactionError> {
+        BorshDeserialize::try_from_slice(bytes)
+            .map_err(|e| TransactionError::InvalidValidityPredicateInputs(e.to_string()))
     }
 }
 
@@ -255,8 +259,8 @@ impl ValidityPredicateCircuit for OrRelationIntentValidityPredicateCircuit {
             },
         )?;
 
-        // Publicize the dynamic vp commitments with default value
-        publicize_default_dynamic_vp_commitments(
+        // Publicize the dynamic vp commitment with default value
+        publicize_default_dynamic_vp_commitment(
             &mut layouter,
             config.advices[0],
             config.instances,
@@ -273,18 +277,20 @@ impl ValidityPredicateCircuit for OrRelationIntentValidityPredicateCircuit {
         &self.output_resources
     }
 
-    fn get_public_inputs(&self, mut rng: impl RngCore) -> ValidityPredicatePublicInputs {
-        let mut public_inputs = self.get_mandatory_public_inputs();
+    fn get_public_inputs(
+        &self,
+        mut rng: impl RngCore,
+    ) -> Result<ValidityPredicatePublicInputs, TransactionError> {
+        let mut public_inputs = self.get_mandatory_public_inputs()?;
         let default_vp_cm: [pallas::Base; 2] =
             ValidityPredicateCommitment::default().to_public_inputs();
         public_inputs.extend(default_vp_cm);
-        public_inputs.extend(default_vp_cm);
         let padding = ValidityPredicatePublicInputs::get_public_input_padding(
             public_inputs.len(),
             &RandomSeed::random(&mut rng),
         );
         public_inputs.extend(padding);
-        public_inputs.into()
+        Ok(public_inputs.into())
     }
 
     fn get_owned_resource_id(&self) -> pallas::Base {
@@ -414,10 +420,10 @@ fn test_halo2_or_relation_intent_vp_circuit() {
     // Test serialization
     let circuit = {
         let circuit_bytes = circuit.to_bytes();
-        OrRelationIntentValidityPredicateCircuit::from_bytes(&circuit_bytes)
+        OrRelationIntentValidityPredicateCircuit::from_bytes(&circuit_bytes).unwrap()
     };
 
-    let public_inputs = circuit.get_public_inputs(&mut rng);
+    let public_inputs = circuit.get_public_inputs(&mut rng).unwrap();
 
     let prover = MockProver::<pallas::Base>::run(
         VP_CIRCUIT_PARAMS_SIZE,
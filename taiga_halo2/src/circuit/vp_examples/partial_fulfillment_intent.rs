@@ -4,7 +4,7 @@
 ///
 use crate::{
     circuit::{
-        blake2s::publicize_default_dynamic_vp_commitments,
+        blake2s::publicize_default_dynamic_vp_commitment,
         gadgets::{
             assign_free_constant,
             mul::MulChip,
@@ -16,6 +16,7 @@ use crate::{
             BasicValidityPredicateVariables, VPVerifyingInfo, ValidityPredicateCircuit,
             ValidityPredicateConfig, ValidityPredicatePublicInputs, ValidityPredicateVerifyingInfo,
         },
+        vp_registry::PARTIAL_FULFILLMENT_INTENT_VP_CIRCUIT_ID,
     },
     constant::{NUM_RESOURCE, SETUP_PARAMS_MAP},
     error::TransactionError,
@@ -28,7 +29,7 @@ use crate::{
 use borsh::{BorshDeserialize, BorshSerialize};
 use halo2_proofs::{
     circuit::{floor_planner, Layouter},
-    plonk::{keygen_pk, keygen_vk, Circuit, ConstraintSystem, Error},
+    plonk::{Circuit, ConstraintSystem, Error},
 };
 use lazy_static::lazy_static;
 use pasta_curves::{group::ff::PrimeField, pallas};
@@ -43,7 +44,9 @@ use label::PartialFulfillmentIntentLabel;
 
 lazy_static! {
     pub static ref PARTIAL_FULFILLMENT_INTENT_VK: ValidityPredicateVerifyingKey =
-        PartialFulfillmentIntentValidityPredicateCircuit::default().get_vp_vk();
+        PartialFulfillmentIntentValidityPredicateCircuit::default()
+            .get_vp_vk()
+            .unwrap();
     pub static ref COMPRESSED_PARTIAL_FULFILLMENT_INTENT_VK: pallas::Base =
         PARTIAL_FULFILLMENT_INTENT_VK.get_compressed();
 }
@@ -60,7 +63,9 @@ pub struct PartialFulfillmentIntentValidityPredicateCircuit {
 impl PartialFulfillmentIntentValidityPredicateCircuit {
     pub fn to_bytecode(&self) -> ValidityPredicateByteCode {
         ValidityPredicateByteCode::new(
-            ValidityPredicateRepresentation::PartialFulfillmentIntent,
+            ValidityPredicateRepresentation::Native(
+                PARTIAL_FULFILLMENT_INTENT_VP_CIRCUIT_ID.to_string(),
+            ),
             self.to_bytes(),
         )
     }
@@ -69,8 +74,9 @@ impl PartialFulfillmentIntentValidityPredicateCircuit {
         borsh::to_vec(&self).unwrap()
     }
 
-    pub fn from_bytes(bytes: &Vec<u8>) -> Self {
-        BorshDeserialize::deserialize(&mut bytes.as_ref()).unwrap()
+    pub fn from_bytes(bytes: &Vec<u8>) -> Result<Self, TransactionError> {
+        BorshDeserialize::try_from_slice(bytes)
+            .map_err(|e| TransactionError::InvalidValidityPredicateInputs(e.to_string()))
     }
 }
 
@@ -158,8 +164,8 @@ impl ValidityPredicateCircuit for PartialFulfillmentIntentValidityPredicateCircu
             layouter.namespace(|| "is_partial_fulfillment checks"),
         )?;
 
-        // Publicize the dynamic vp commitments with default value
-        publicize_default_dynamic_vp_commitments(
+        // Publicize the dynamic vp commitment with default value
+        publicize_default_dynamic_vp_commitment(
             &mut layouter,
             config.advices[0],
             config.instances,
@@ -176,18 +182,20 @@ impl ValidityPredicateCircuit for PartialFulfillmentIntentValidityPredicateCircu
         &self.output_resources
     }
 
-    fn get_public_inputs(&self, mut rng: impl RngCore) -> ValidityPredicatePublicInputs {
-        let mut public_inputs = self.get_mandatory_public_inputs();
+    fn get_public_inputs(
+        &self,
+        mut rng: impl RngCore,
+    ) -> Result<ValidityPredicatePublicInputs, TransactionError> {
+        let mut public_inputs = self.get_mandatory_public_inputs()?;
         let default_vp_cm: [pallas::Base; 2] =
             ValidityPredicateCommitment::default().to_public_inputs();
         public_inputs.extend(default_vp_cm);
-        public_inputs.extend(default_vp_cm);
         let padding = ValidityPredicatePublicInputs::get_public_input_padding(
             public_inputs.len(),
             &RandomSeed::random(&mut rng),
         );
         public_inputs.extend(padding);
-        public_inputs.into()
+        Ok(public_inputs.into())
     }
 
     fn get_owned_resource_id(&self) -> pallas::Base {
@@ -276,7 +284,7 @@ mod tests {
             output_resources,
             swap,
         };
-        let public_inputs = circuit.get_public_inputs(&mut rng);
+        let public_inputs = circuit.get_public_inputs(&mut rng).unwrap();
 
         let prover = MockProver::<pallas::Base>::run(
             VP_CIRCUIT_PARAMS_SIZE,
@@ -305,7 +313,7 @@ mod tests {
             output_resources,
             swap,
         };
-        let public_inputs = circuit.get_public_inputs(&mut rng);
+        let public_inputs = circuit.get_public_inputs(&mut rng).unwrap();
 
         let prover = MockProver::<pallas::Base>::run(
             VP_CIRCUIT_PARAMS_SIZE,
@@ -338,10 +346,10 @@ mod tests {
         // Test serialization
         let circuit = {
             let circuit_bytes = circuit.to_bytes();
-            PartialFulfillmentIntentValidityPredicateCircuit::from_bytes(&circuit_bytes)
+            PartialFulfillmentIntentValidityPredicateCircuit::from_bytes(&circuit_bytes).unwrap()
         };
 
-        let public_inputs = circuit.get_public_inputs(&mut rng);
+        let public_inputs = circuit.get_public_inputs(&mut rng).unwrap();
 
         let prover = MockProver::<pallas::Base>::run(
             VP_CIRCUIT_PARAMS_SIZE,
@@ -1,6 +1,6 @@
 use crate::{
     circuit::{
-        blake2s::publicize_default_dynamic_vp_commitments,
+        blake2s::publicize_default_dynamic_vp_commitment,
         gadgets::{
             add::AddChip, assign_free_advice, poseidon_hash::poseidon_hash_gadget,
             target_resource_variable::get_owned_resource_variable,
@@ -12,6 +12,7 @@ use crate::{
             ValidityPredicateConfig, ValidityPredicatePublicInputs, ValidityPredicateVerifyingInfo,
         },
         vp_examples::signature_verification::COMPRESSED_TOKEN_AUTH_VK,
+        vp_registry::RECEIVER_VP_CIRCUIT_ID,
     },
     constant::{GENERATOR, NUM_RESOURCE, SETUP_PARAMS_MAP},
     error::TransactionError,
@@ -28,7 +29,7 @@ use halo2_gadgets::ecc::{chip::EccChip, NonIdentityPoint};
 use halo2_proofs::{
     arithmetic::CurveAffine,
     circuit::{floor_planner, Layouter, Value},
-    plonk::{keygen_pk, keygen_vk, Circuit, ConstraintSystem, Error},
+    plonk::{Circuit, ConstraintSystem, Error},
 };
 use lazy_static::lazy_static;
 use pasta_curves::pallas;
@@ -39,7 +40,9 @@ const CIPHER_LEN: usize = 9;
 
 lazy_static! {
     pub static ref RECEIVER_VK: ValidityPredicateVerifyingKey =
-        ReceiverValidityPredicateCircuit::default().get_vp_vk();
+        ReceiverValidityPredicateCircuit::default()
+            .get_vp_vk()
+            .unwrap();
     pub static ref COMPRESSED_RECEIVER_VK: pallas::Base = RECEIVER_VK.get_compressed();
 }
 
@@ -54,19 +57,25 @@ pub struct ReceiverValidityPredicateCircuit {
     pub sk: pallas::Base,
     pub rcv_pk: pallas::Point,
     pub auth_vp_vk: pallas::Base,
+    // The digest of the extra dynamic vps the owned resource requires, part of the value encoding.
+    pub extra_dynamic_vps_digest: pallas::Base,
 }
 
 impl ReceiverValidityPredicateCircuit {
     pub fn to_bytecode(&self) -> ValidityPredicateByteCode {
-        ValidityPredicateByteCode::new(ValidityPredicateRepresentation::Receiver, self.to_bytes())
+        ValidityPredicateByteCode::new(
+            ValidityPredicateRepresentation::Native(RECEIVER_VP_CIRCUIT_ID.to_string()),
+            self.to_bytes(),
+        )
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
         borsh::to_vec(&self).unwrap()
     }
 
-    pub fn from_bytes(bytes: &Vec<u8>) -> Self {
-        BorshDeserialize::deserialize(&mut bytes.as_ref()).unwrap()
+    pub fn from_bytes(bytes: &Vec<u8>) -> Result<Self, TransactionError> {
+        BorshDeserialize::try_from_slice(bytes)
+            .map_err(|e| TransactionError::InvalidValidityPredicateInputs(e.to_string()))
     }
 }
 
@@ -81,6 +90,7 @@ impl Default for ReceiverValidityPredicateCircuit {
             sk: pallas::Base::zero(),
             rcv_pk: pallas::Point::generator(),
             auth_vp_vk: pallas::Base::zero(),
+            extra_dynamic_vps_digest: pallas::Base::zero(),
         }
     }
 }
@@ -132,6 +142,11 @@ impl ValidityPredicateCircuit for ReceiverValidityPredicateCircuit {
             config.advices[0],
             Value::known(self.vp_vk),
         )?;
+        let extra_dynamic_vps_digest = assign_free_advice(
+            layouter.namespace(|| "witness extra dynamic vps digest"),
+            config.advices[0],
+            Value::known(self.extra_dynamic_vps_digest),
+        )?;
 
         // Decode the value, and check the value encoding
         let encoded_value = poseidon_hash_gadget(
@@ -142,6 +157,7 @@ impl ValidityPredicateCircuit for ReceiverValidityPredicateCircuit {
                 rcv_pk.inner().y(),
                 auth_vp_vk,
                 receiver_vp_vk,
+                extra_dynamic_vps_digest,
             ],
         )?;
 
@@ -229,8 +245,8 @@ impl ValidityPredicateCircuit for ReceiverValidityPredicateCircuit {
             &mut message,
         )?;
 
-        // Publicize the dynamic vp commitments with default value
-        publicize_default_dynamic_vp_commitments(
+        // Publicize the dynamic vp commitment with default value
+        publicize_default_dynamic_vp_commitment(
             &mut layouter,
             config.advices[0],
             config.instances,
@@ -247,26 +263,26 @@ impl ValidityPredicateCircuit for ReceiverValidityPredicateCircuit {
         &self.output_resources
     }
 
-    fn get_public_inputs(&self, rng: impl RngCore) -> ValidityPredicatePublicInputs {
-        let mut public_inputs = self.get_mandatory_public_inputs();
+    fn get_public_inputs(
+        &self,
+        rng: impl RngCore,
+    ) -> Result<ValidityPredicatePublicInputs, TransactionError> {
+        let mut public_inputs = self.get_mandatory_public_inputs()?;
         let default_vp_cm: [pallas::Base; 2] =
             ValidityPredicateCommitment::default().to_public_inputs();
         public_inputs.extend(default_vp_cm);
-        public_inputs.extend(default_vp_cm);
         let custom_public_input_padding =
             ValidityPredicatePublicInputs::get_custom_public_input_padding(
                 public_inputs.len(),
                 &RandomSeed::random(rng),
             );
         public_inputs.extend(custom_public_input_padding.iter());
-        assert_eq!(NUM_RESOURCE, 2);
-        let target_resource = if self.get_owned_resource_id()
-            == self.get_output_resources()[0].commitment().inner()
-        {
-            self.get_output_resources()[0]
-        } else {
-            self.get_output_resources()[1]
-        };
+        let target_resource = self
+            .get_output_resources()
+            .iter()
+            .find(|resource| resource.commitment().inner() == self.get_owned_resource_id())
+            .copied()
+            .unwrap_or(self.get_output_resources()[NUM_RESOURCE - 1]);
         let message = vec![
             target_resource.kind.logic,
             target_resource.kind.label,
@@ -287,7 +303,7 @@ impl ValidityPredicateCircuit for ReceiverValidityPredicateCircuit {
         let pk_coord = pk.to_affine().coordinates().unwrap();
         public_inputs.push(*pk_coord.x());
         public_inputs.push(*pk_coord.y());
-        public_inputs.into()
+        Ok(public_inputs.into())
     }
 
     fn get_owned_resource_id(&self) -> pallas::Base {
@@ -314,6 +330,7 @@ impl BorshSerialize for ReceiverValidityPredicateCircuit {
         writer.write_all(&self.sk.to_repr())?;
         writer.write_all(&self.rcv_pk.to_bytes())?;
         writer.write_all(&self.auth_vp_vk.to_repr())?;
+        writer.write_all(&self.extra_dynamic_vps_digest.to_repr())?;
 
         Ok(())
     }
@@ -333,6 +350,7 @@ impl BorshDeserialize for ReceiverValidityPredicateCircuit {
         let sk = read_base_field(reader)?;
         let rcv_pk = read_point(reader)?;
         let auth_vp_vk = read_base_field(reader)?;
+        let extra_dynamic_vps_digest = read_base_field(reader)?;
         Ok(Self {
             owned_resource_id,
             input_resources: input_resources.try_into().unwrap(),
@@ -342,6 +360,7 @@ impl BorshDeserialize for ReceiverValidityPredicateCircuit {
             sk,
             rcv_pk,
             auth_vp_vk,
+            extra_dynamic_vps_digest,
         })
     }
 }
@@ -369,6 +388,7 @@ fn test_halo2_receiver_vp_circuit() {
             *rcv_pk_coord.y(),
             *COMPRESSED_TOKEN_AUTH_VK,
             *COMPRESSED_RECEIVER_VK,
+            pallas::Base::zero(),
         ]);
         let owned_resource_id = output_resources[0].commitment().inner();
         (
@@ -381,6 +401,7 @@ fn test_halo2_receiver_vp_circuit() {
                 sk,
                 rcv_pk,
                 auth_vp_vk: *COMPRESSED_TOKEN_AUTH_VK,
+                extra_dynamic_vps_digest: pallas::Base::zero(),
             },
             rcv_sk,
         )
@@ -389,10 +410,10 @@ fn test_halo2_receiver_vp_circuit() {
     // Test serialization
     let circuit = {
         let circuit_bytes = circuit.to_bytes();
-        ReceiverValidityPredicateCircuit::from_bytes(&circuit_bytes)
+        ReceiverValidityPredicateCircuit::from_bytes(&circuit_bytes).unwrap()
     };
 
-    let public_inputs = circuit.get_public_inputs(&mut rng);
+    let public_inputs = circuit.get_public_inputs(&mut rng).unwrap();
 
     let prover = MockProver::<pallas::Base>::run(
         VP_CIRCUIT_PARAMS_SIZE,
@@ -1,6 +1,6 @@
 use crate::{
     circuit::{
-        blake2s::publicize_default_dynamic_vp_commitments,
+        blake2s::publicize_default_dynamic_vp_commitment,
         gadgets::{
             assign_free_advice, poseidon_hash::poseidon_hash_gadget,
             target_resource_variable::get_owned_resource_variable,
@@ -10,6 +10,7 @@ use crate::{
             BasicValidityPredicateVariables, VPVerifyingInfo, ValidityPredicateCircuit,
             ValidityPredicateConfig, ValidityPredicatePublicInputs, ValidityPredicateVerifyingInfo,
         },
+        vp_registry::SIGNATURE_VERIFICATION_VP_CIRCUIT_ID,
     },
     constant::{TaigaFixedBasesFull, NUM_RESOURCE, SETUP_PARAMS_MAP},
     error::TransactionError,
@@ -24,7 +25,7 @@ use halo2_gadgets::ecc::{chip::EccChip, FixedPoint, NonIdentityPoint, ScalarFixe
 use halo2_proofs::{
     arithmetic::Field,
     circuit::{floor_planner, Layouter, Value},
-    plonk::{keygen_pk, keygen_vk, Circuit, ConstraintSystem, Error},
+    plonk::{Circuit, ConstraintSystem, Error},
 };
 use lazy_static::lazy_static;
 use pasta_curves::{
@@ -40,7 +41,9 @@ const MESSAGE_LEN: usize = NUM_RESOURCE * 2;
 const POSEIDON_HASH_LEN: usize = MESSAGE_LEN + 4;
 lazy_static! {
     pub static ref TOKEN_AUTH_VK: ValidityPredicateVerifyingKey =
-        SignatureVerificationValidityPredicateCircuit::default().get_vp_vk();
+        SignatureVerificationValidityPredicateCircuit::default()
+            .get_vp_vk()
+            .unwrap();
     pub static ref COMPRESSED_TOKEN_AUTH_VK: pallas::Base = TOKEN_AUTH_VK.get_compressed();
 }
 
@@ -76,16 +79,15 @@ impl SchnorrSignature {
         let r_coord = r.to_affine().coordinates().unwrap();
         // Compute: s = z + Hash(r||P||m)*sk
         assert_eq!(message.len(), MESSAGE_LEN);
-        let h = mod_r_p(poseidon_hash_n::<POSEIDON_HASH_LEN>([
+        let mut poseidon_msg = [pallas::Base::zero(); POSEIDON_HASH_LEN];
+        poseidon_msg[..4].copy_from_slice(&[
             *r_coord.x(),
             *r_coord.y(),
             *pk_coord.x(),
             *pk_coord.y(),
-            message[0],
-            message[1],
-            message[2],
-            message[3],
-        ]));
+        ]);
+        poseidon_msg[4..].copy_from_slice(&message);
+        let h = mod_r_p(poseidon_hash_n::<POSEIDON_HASH_LEN>(poseidon_msg));
         let s = z + h * sk;
         Self { pk, r, s }
     }
@@ -100,6 +102,8 @@ pub struct SignatureVerificationValidityPredicateCircuit {
     pub vp_vk: pallas::Base,
     pub signature: SchnorrSignature,
     pub receiver_vp_vk: pallas::Base,
+    // The digest of the extra dynamic vps the owned resource requires, part of the value encoding.
+    pub extra_dynamic_vps_digest: pallas::Base,
 }
 
 impl SignatureVerificationValidityPredicateCircuit {
@@ -110,6 +114,7 @@ impl SignatureVerificationValidityPredicateCircuit {
         vp_vk: pallas::Base,
         signature: SchnorrSignature,
         receiver_vp_vk: pallas::Base,
+        extra_dynamic_vps_digest: pallas::Base,
     ) -> Self {
         Self {
             owned_resource_id,
@@ -118,6 +123,7 @@ impl SignatureVerificationValidityPredicateCircuit {
             vp_vk,
             signature,
             receiver_vp_vk,
+            extra_dynamic_vps_digest,
         }
     }
 
@@ -129,8 +135,8 @@ impl SignatureVerificationValidityPredicateCircuit {
         vp_vk: pallas::Base,
         sk: pallas::Scalar,
         receiver_vp_vk: pallas::Base,
+        extra_dynamic_vps_digest: pallas::Base,
     ) -> Self {
-        assert_eq!(NUM_RESOURCE, 2);
         let mut message = vec![];
         input_resources
             .iter()
@@ -149,12 +155,15 @@ impl SignatureVerificationValidityPredicateCircuit {
             vp_vk,
             signature,
             receiver_vp_vk,
+            extra_dynamic_vps_digest,
         }
     }
 
     pub fn to_bytecode(&self) -> ValidityPredicateByteCode {
         ValidityPredicateByteCode::new(
-            ValidityPredicateRepresentation::SignatureVerification,
+            ValidityPredicateRepresentation::Native(
+                SIGNATURE_VERIFICATION_VP_CIRCUIT_ID.to_string(),
+            ),
             self.to_bytes(),
         )
     }
@@ -163,8 +172,9 @@ impl SignatureVerificationValidityPredicateCircuit {
         borsh::to_vec(&self).unwrap()
     }
 
-    pub fn from_bytes(bytes: &Vec<u8>) -> Self {
-        BorshDeserialize::deserialize(&mut bytes.as_ref()).unwrap()
+    pub fn from_bytes(bytes: &Vec<u8>) -> Result<Self, TransactionError> {
+        BorshDeserialize::try_from_slice(bytes)
+            .map_err(|e| TransactionError::InvalidValidityPredicateInputs(e.to_string()))
     }
 }
 
@@ -204,12 +214,23 @@ impl ValidityPredicateCircuit for SignatureVerificationValidityPredicateCircuit
             config.advices[0],
             Value::known(self.receiver_vp_vk),
         )?;
+        let extra_dynamic_vps_digest = assign_free_advice(
+            layouter.namespace(|| "witness extra dynamic vps digest"),
+            config.advices[0],
+            Value::known(self.extra_dynamic_vps_digest),
+        )?;
 
         // Decode the value, and check the value encoding
         let encoded_value = poseidon_hash_gadget(
             config.poseidon_config.clone(),
             layouter.namespace(|| "value encoding"),
-            [pk.inner().x(), pk.inner().y(), auth_vp_vk, receiver_vp_vk],
+            [
+                pk.inner().x(),
+                pk.inner().y(),
+                auth_vp_vk,
+                receiver_vp_vk,
+                extra_dynamic_vps_digest,
+            ],
         )?;
 
         layouter.assign_region(
@@ -238,20 +259,15 @@ impl ValidityPredicateCircuit for SignatureVerificationValidityPredicateCircuit
         let h_scalar = {
             let nfs = basic_variables.get_input_resource_nfs();
             let cms = basic_variables.get_output_resource_cms();
-            assert_eq!(NUM_RESOURCE, 2);
-            let h = poseidon_hash_gadget(
+            let poseidon_msg: Vec<_> =
+                [r.inner().x(), r.inner().y(), pk.inner().x(), pk.inner().y()]
+                    .into_iter()
+                    .chain(nfs.into_iter().zip(cms).flat_map(|(nf, cm)| [nf, cm]))
+                    .collect();
+            let h = poseidon_hash_gadget::<POSEIDON_HASH_LEN>(
                 config.poseidon_config,
                 layouter.namespace(|| "Poseidon_hash(r, P, m)"),
-                [
-                    r.inner().x(),
-                    r.inner().y(),
-                    pk.inner().x(),
-                    pk.inner().y(),
-                    nfs[0].clone(),
-                    cms[0].clone(),
-                    nfs[1].clone(),
-                    cms[1].clone(),
-                ],
+                poseidon_msg.try_into().unwrap(),
             )?;
 
             ScalarVar::from_base(ecc_chip, layouter.namespace(|| "ScalarVar from_base"), &h)?
@@ -265,8 +281,8 @@ impl ValidityPredicateCircuit for SignatureVerificationValidityPredicateCircuit
 
         s_g.constrain_equal(layouter.namespace(|| "s*G = R + Hash(r||P||m)*P"), &rhs)?;
 
-        // Publicize the dynamic vp commitments with default value
-        publicize_default_dynamic_vp_commitments(
+        // Publicize the dynamic vp commitment with default value
+        publicize_default_dynamic_vp_commitment(
             &mut layouter,
             config.advices[0],
             config.instances,
@@ -283,18 +299,20 @@ impl ValidityPredicateCircuit for SignatureVerificationValidityPredicateCircuit
         &self.output_resources
     }
 
-    fn get_public_inputs(&self, mut rng: impl RngCore) -> ValidityPredicatePublicInputs {
-        let mut public_inputs = self.get_mandatory_public_inputs();
+    fn get_public_inputs(
+        &self,
+        mut rng: impl RngCore,
+    ) -> Result<ValidityPredicatePublicInputs, TransactionError> {
+        let mut public_inputs = self.get_mandatory_public_inputs()?;
         let default_vp_cm: [pallas::Base; 2] =
             ValidityPredicateCommitment::default().to_public_inputs();
         public_inputs.extend(default_vp_cm);
-        public_inputs.extend(default_vp_cm);
         let padding = ValidityPredicatePublicInputs::get_public_input_padding(
             public_inputs.len(),
             &RandomSeed::random(&mut rng),
         );
         public_inputs.extend(padding);
-        public_inputs.into()
+        Ok(public_inputs.into())
     }
 
     fn get_owned_resource_id(&self) -> pallas::Base {
@@ -319,6 +337,7 @@ impl BorshSerialize for SignatureVerificationValidityPredicateCircuit {
         writer.write_all(&self.vp_vk.to_repr())?;
         self.signature.serialize(writer)?;
         writer.write_all(&self.receiver_vp_vk.to_repr())?;
+        writer.write_all(&self.extra_dynamic_vps_digest.to_repr())?;
 
         Ok(())
     }
@@ -336,6 +355,7 @@ impl BorshDeserialize for SignatureVerificationValidityPredicateCircuit {
         let vp_vk = read_base_field(reader)?;
         let signature = SchnorrSignature::deserialize_reader(reader)?;
         let receiver_vp_vk = read_base_field(reader)?;
+        let extra_dynamic_vps_digest = read_base_field(reader)?;
         Ok(Self {
             owned_resource_id,
             input_resources: input_resources.try_into().unwrap(),
@@ -343,6 +363,7 @@ impl BorshDeserialize for SignatureVerificationValidityPredicateCircuit {
             vp_vk,
             signature,
             receiver_vp_vk,
+            extra_dynamic_vps_digest,
         })
     }
 }
@@ -393,16 +414,17 @@ fn test_halo2_sig_verification_vp_circuit() {
             auth_vk,
             sk,
             *COMPRESSED_RECEIVER_VK,
+            pallas::Base::zero(),
         )
     };
 
     // Test serialization
     let circuit = {
         let circuit_bytes = circuit.to_bytes();
-        SignatureVerificationValidityPredicateCircuit::from_bytes(&circuit_bytes)
+        SignatureVerificationValidityPredicateCircuit::from_bytes(&circuit_bytes).unwrap()
     };
 
-    let public_inputs = circuit.get_public_inputs(&mut rng);
+    let public_inputs = circuit.get_public_inputs(&mut rng).unwrap();
 
     let prover = MockProver::<pallas::Base>::run(
         VP_CIRCUIT_PARAMS_SIZE,
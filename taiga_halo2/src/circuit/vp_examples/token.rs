@@ -15,18 +15,17 @@ use crate::{
         vp_examples::signature_verification::{
             SignatureVerificationValidityPredicateCircuit, COMPRESSED_TOKEN_AUTH_VK,
         },
+        vp_registry::TOKEN_VP_CIRCUIT_ID,
     },
     constant::{
-        NUM_RESOURCE, PRF_EXPAND_DYNAMIC_VP_1_CM_R, SETUP_PARAMS_MAP,
-        VP_CIRCUIT_FIRST_DYNAMIC_VP_CM_1, VP_CIRCUIT_FIRST_DYNAMIC_VP_CM_2,
-        VP_CIRCUIT_SECOND_DYNAMIC_VP_CM_1, VP_CIRCUIT_SECOND_DYNAMIC_VP_CM_2,
+        NUM_RESOURCE, SETUP_PARAMS_MAP, VP_CIRCUIT_DYNAMIC_VP_CM_1, VP_CIRCUIT_DYNAMIC_VP_CM_2,
     },
     error::TransactionError,
     nullifier::Nullifier,
     proof::Proof,
     resource::{RandomSeed, Resource, ResourceValidityPredicates},
     utils::{poseidon_hash_n, read_base_field, read_point},
-    vp_commitment::ValidityPredicateCommitment,
+    vp_commitment::{dynamic_vps_digest, ValidityPredicateCommitment},
     vp_vk::ValidityPredicateVerifyingKey,
 };
 use borsh::{BorshDeserialize, BorshSerialize};
@@ -35,7 +34,7 @@ use group::{Curve, Group, GroupEncoding};
 use halo2_gadgets::ecc::{chip::EccChip, NonIdentityPoint};
 use halo2_proofs::{
     circuit::{floor_planner, Layouter, Value},
-    plonk::{keygen_pk, keygen_vk, Circuit, ConstraintSystem, Error},
+    plonk::{Circuit, ConstraintSystem, Error},
 };
 use lazy_static::lazy_static;
 use pasta_curves::arithmetic::CurveAffine;
@@ -44,7 +43,9 @@ use rand::{rngs::OsRng, Rng, RngCore};
 
 lazy_static! {
     pub static ref TOKEN_VK: ValidityPredicateVerifyingKey =
-        TokenValidityPredicateCircuit::default().get_vp_vk();
+        TokenValidityPredicateCircuit::default()
+            .get_vp_vk()
+            .unwrap();
     pub static ref COMPRESSED_TOKEN_VK: pallas::Base = TOKEN_VK.get_compressed();
 }
 
@@ -199,7 +200,7 @@ impl TokenResource {
             token_name: token_name.clone(),
             auth,
             receiver_vp_vk: *COMPRESSED_RECEIVER_VK,
-            rseed: RandomSeed::random(&mut rng),
+            extra_dynamic_vps: vec![],
         };
 
         // token auth VP
@@ -211,6 +212,7 @@ impl TokenResource {
             auth.vk,
             auth_sk,
             *COMPRESSED_RECEIVER_VK,
+            dynamic_vps_digest(&[]),
         );
 
         ResourceValidityPredicates::new(Box::new(token_vp), vec![Box::new(token_auth_vp)])
@@ -237,7 +239,7 @@ impl TokenResource {
             token_name: token_name.clone(),
             auth,
             receiver_vp_vk: *COMPRESSED_RECEIVER_VK,
-            rseed: RandomSeed::random(&mut rng),
+            extra_dynamic_vps: vec![],
         };
 
         // receiver VP
@@ -250,6 +252,7 @@ impl TokenResource {
             sk: pallas::Base::random(&mut rng),
             rcv_pk: auth.pk,
             auth_vp_vk: *COMPRESSED_TOKEN_AUTH_VK,
+            extra_dynamic_vps_digest: dynamic_vps_digest(&[]),
         };
 
         ResourceValidityPredicates::new(Box::new(token_vp), vec![Box::new(receiver_vp)])
@@ -267,8 +270,10 @@ pub struct TokenValidityPredicateCircuit {
     // The auth goes to value and defines how to consume and create the resource.
     pub auth: TokenAuthorization,
     pub receiver_vp_vk: pallas::Base,
-    // rseed is to generate the randomness for vp commitment
-    pub rseed: RandomSeed,
+    // The compressed vks of the dynamic vps required on top of the auth vp or the receiver vp,
+    // e.g. a blacklist check or a rate limiter. They follow it in the committed dynamic vp list,
+    // and their digest is part of the value so the resource owner fixes them.
+    pub extra_dynamic_vps: Vec<pallas::Base>,
 }
 
 #[derive(Clone, Debug, Copy)]
@@ -288,15 +293,19 @@ impl Default for TokenAuthorization {
 
 impl TokenValidityPredicateCircuit {
     pub fn to_bytecode(&self) -> ValidityPredicateByteCode {
-        ValidityPredicateByteCode::new(ValidityPredicateRepresentation::Token, self.to_bytes())
+        ValidityPredicateByteCode::new(
+            ValidityPredicateRepresentation::Native(TOKEN_VP_CIRCUIT_ID.to_string()),
+            self.to_bytes(),
+        )
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
         borsh::to_vec(&self).unwrap()
     }
 
-    pub fn from_bytes(bytes: &Vec<u8>) -> Self {
-        BorshDeserialize::deserialize(&mut bytes.as_ref()).unwrap()
+    pub fn from_bytes(bytes: &Vec<u8>) -> Result<Self, TransactionError> {
+        BorshDeserialize::try_from_slice(bytes)
+            .map_err(|e| TransactionError::InvalidValidityPredicateInputs(e.to_string()))
     }
 }
 
@@ -309,7 +318,7 @@ impl Default for TokenValidityPredicateCircuit {
             token_name: TokenName("Token_name".to_string()),
             auth: TokenAuthorization::default(),
             receiver_vp_vk: pallas::Base::zero(),
-            rseed: RandomSeed::default(),
+            extra_dynamic_vps: vec![],
         }
     }
 }
@@ -375,6 +384,12 @@ impl ValidityPredicateCircuit for TokenValidityPredicateCircuit {
             Value::known(self.receiver_vp_vk),
         )?;
 
+        let extra_dynamic_vps_digest = assign_free_advice(
+            layouter.namespace(|| "witness extra dynamic vps digest"),
+            config.advices[0],
+            Value::known(dynamic_vps_digest(&self.extra_dynamic_vps)),
+        )?;
+
         // Decode the value, and check the value encoding
         let encoded_value = poseidon_hash_gadget(
             config.poseidon_config,
@@ -384,6 +399,7 @@ impl ValidityPredicateCircuit for TokenValidityPredicateCircuit {
                 pk.inner().y(),
                 auth_vp_vk.clone(),
                 receiver_vp_vk.clone(),
+                extra_dynamic_vps_digest.clone(),
             ],
         )?;
 
@@ -410,8 +426,8 @@ impl ValidityPredicateCircuit for TokenValidityPredicateCircuit {
         )?;
 
         // VP Commitment
-        // Commt the sender(authorization method included) vp if it's an input resource;
-        // Commit the receiver(resource encryption constraints included) vp if it's an output resource.
+        // Commit to the dynamic vp list of the resource. It starts with the sender(authorization method included) vp
+        // if it's an input resource, or the receiver(resource encryption constraints included) vp if it's an output resource.
         let first_dynamic_vp = {
             let is_input_resource = get_is_input_resource_flag(
                 config.get_is_input_resource_flag_config,
@@ -434,50 +450,31 @@ impl ValidityPredicateCircuit for TokenValidityPredicateCircuit {
             )?
         };
 
-        // Construct a blake2s chip
-        let blake2s_chip = Blake2sChip::construct(config.blake2s_config);
-        let vp_cm_r = assign_free_advice(
-            layouter.namespace(|| "vp_cm_r"),
-            config.advices[0],
-            Value::known(self.rseed.get_vp_cm_r(PRF_EXPAND_DYNAMIC_VP_1_CM_R)),
-        )?;
-        let first_dynamic_vp_cm =
-            vp_commitment_gadget(&mut layouter, &blake2s_chip, first_dynamic_vp, vp_cm_r)?;
-
-        layouter.constrain_instance(
-            first_dynamic_vp_cm[0].cell(),
-            config.instances,
-            VP_CIRCUIT_FIRST_DYNAMIC_VP_CM_1,
-        )?;
-        layouter.constrain_instance(
-            first_dynamic_vp_cm[1].cell(),
-            config.instances,
-            VP_CIRCUIT_FIRST_DYNAMIC_VP_CM_2,
+        // The digest of the extra dynamic vps is bound by the value encoding above.
+        let dynamic_vps_digest = poseidon_hash_gadget(
+            config.poseidon_config,
+            layouter.namespace(|| "dynamic vps digest"),
+            [first_dynamic_vp, extra_dynamic_vps_digest],
         )?;
 
-        // Publicize the second dynamic vp commitment with default value
-        let vp_cm_fields: [pallas::Base; 2] =
-            ValidityPredicateCommitment::default().to_public_inputs();
-        let vp_cm_1 = assign_free_advice(
-            layouter.namespace(|| "vp_cm 1"),
-            config.advices[0],
-            Value::known(vp_cm_fields[0]),
-        )?;
-        let vp_cm_2 = assign_free_advice(
-            layouter.namespace(|| "vp_cm 2"),
-            config.advices[0],
-            Value::known(vp_cm_fields[1]),
+        // Construct a blake2s chip
+        let blake2s_chip = Blake2sChip::construct(config.blake2s_config);
+        let dynamic_vp_cm = vp_commitment_gadget(
+            &mut layouter,
+            &blake2s_chip,
+            dynamic_vps_digest,
+            constant_zero,
         )?;
 
         layouter.constrain_instance(
-            vp_cm_1.cell(),
+            dynamic_vp_cm[0].cell(),
             config.instances,
-            VP_CIRCUIT_SECOND_DYNAMIC_VP_CM_1,
+            VP_CIRCUIT_DYNAMIC_VP_CM_1,
         )?;
         layouter.constrain_instance(
-            vp_cm_2.cell(),
+            dynamic_vp_cm[1].cell(),
             config.instances,
-            VP_CIRCUIT_SECOND_DYNAMIC_VP_CM_2,
+            VP_CIRCUIT_DYNAMIC_VP_CM_2,
         )?;
 
         Ok(())
@@ -491,30 +488,34 @@ impl ValidityPredicateCircuit for TokenValidityPredicateCircuit {
         &self.output_resources
     }
 
-    fn get_public_inputs(&self, mut rng: impl RngCore) -> ValidityPredicatePublicInputs {
-        let mut public_inputs = self.get_mandatory_public_inputs();
-        let dynamic_vp = if self.owned_resource_id == self.output_resources[0].commitment().inner()
-            || self.owned_resource_id == self.output_resources[1].commitment().inner()
+    fn get_public_inputs(
+        &self,
+        mut rng: impl RngCore,
+    ) -> Result<ValidityPredicatePublicInputs, TransactionError> {
+        let mut public_inputs = self.get_mandatory_public_inputs()?;
+        let dynamic_vp = if self
+            .output_resources
+            .iter()
+            .any(|resource| self.owned_resource_id == resource.commitment().inner())
         {
             self.receiver_vp_vk
         } else {
             self.auth.vk
         };
 
-        let vp_com_r = self.rseed.get_vp_cm_r(PRF_EXPAND_DYNAMIC_VP_1_CM_R);
-        let vp_com: [pallas::Base; 2] =
-            ValidityPredicateCommitment::commit(&dynamic_vp, &vp_com_r).to_public_inputs();
+        let dynamic_vps: Vec<_> = std::iter::once(dynamic_vp)
+            .chain(self.extra_dynamic_vps.iter().copied())
+            .collect();
 
+        let vp_com: [pallas::Base; 2] =
+            ValidityPredicateCommitment::commit_dynamic_vps(&dynamic_vps).to_public_inputs();
         public_inputs.extend(vp_com);
-        let default_vp_cm: [pallas::Base; 2] =
-            ValidityPredicateCommitment::default().to_public_inputs();
-        public_inputs.extend(default_vp_cm);
         let padding = ValidityPredicatePublicInputs::get_public_input_padding(
             public_inputs.len(),
             &RandomSeed::random(&mut rng),
         );
         public_inputs.extend(padding);
-        public_inputs.into()
+        Ok(public_inputs.into())
     }
 
     fn get_owned_resource_id(&self) -> pallas::Base {
@@ -539,7 +540,10 @@ impl BorshSerialize for TokenValidityPredicateCircuit {
         self.token_name.serialize(writer)?;
         self.auth.serialize(writer)?;
         writer.write_all(&self.receiver_vp_vk.to_repr())?;
-        self.rseed.serialize(writer)?;
+        (self.extra_dynamic_vps.len() as u32).serialize(writer)?;
+        for vp in self.extra_dynamic_vps.iter() {
+            writer.write_all(&vp.to_repr())?;
+        }
 
         Ok(())
     }
@@ -557,7 +561,10 @@ impl BorshDeserialize for TokenValidityPredicateCircuit {
         let token_name = TokenName::deserialize_reader(reader)?;
         let auth = TokenAuthorization::deserialize_reader(reader)?;
         let receiver_vp_vk = read_base_field(reader)?;
-        let rseed = RandomSeed::deserialize_reader(reader)?;
+        let extra_dynamic_vps_len = u32::deserialize_reader(reader)?;
+        let extra_dynamic_vps = (0..extra_dynamic_vps_len)
+            .map(|_| read_base_field(reader))
+            .collect::<Result<_, _>>()?;
         Ok(Self {
             owned_resource_id,
             input_resources: input_resources.try_into().unwrap(),
@@ -565,7 +572,7 @@ impl BorshDeserialize for TokenValidityPredicateCircuit {
             token_name,
             auth,
             receiver_vp_vk,
-            rseed,
+            extra_dynamic_vps,
         })
     }
 }
@@ -600,12 +607,21 @@ impl TokenAuthorization {
     }
 
     pub fn to_value(&self) -> pallas::Base {
+        self.to_value_with_extra_dynamic_vps(&[])
+    }
+
+    /// The value of a token resource that also requires the `extra_dynamic_vps`.
+    pub fn to_value_with_extra_dynamic_vps(
+        &self,
+        extra_dynamic_vps: &[pallas::Base],
+    ) -> pallas::Base {
         let pk_coord = self.pk.to_affine().coordinates().unwrap();
-        poseidon_hash_n::<4>([
+        poseidon_hash_n::<5>([
             *pk_coord.x(),
             *pk_coord.y(),
             self.vk,
             *COMPRESSED_RECEIVER_VK,
+            dynamic_vps_digest(extra_dynamic_vps),
         ])
     }
 
@@ -630,7 +646,11 @@ fn test_halo2_token_vp_circuit() {
         let token_name = TokenName("Token_name".to_string());
         let auth = TokenAuthorization::random(&mut rng);
         input_resources[0].kind.label = token_name.encode();
-        input_resources[0].value = auth.to_value();
+        let extra_dynamic_vps = vec![
+            pallas::Base::random(&mut rng),
+            pallas::Base::random(&mut rng),
+        ];
+        input_resources[0].value = auth.to_value_with_extra_dynamic_vps(&extra_dynamic_vps);
         TokenValidityPredicateCircuit {
             owned_resource_id: input_resources[0].get_nf().unwrap().inner(),
             input_resources,
@@ -638,17 +658,17 @@ fn test_halo2_token_vp_circuit() {
             token_name,
             auth,
             receiver_vp_vk: *COMPRESSED_RECEIVER_VK,
-            rseed: RandomSeed::random(&mut rng),
+            extra_dynamic_vps,
         }
     };
 
     // Test serialization
     let circuit = {
         let circuit_bytes = circuit.to_bytes();
-        TokenValidityPredicateCircuit::from_bytes(&circuit_bytes)
+        TokenValidityPredicateCircuit::from_bytes(&circuit_bytes).unwrap()
     };
 
-    let public_inputs = circuit.get_public_inputs(&mut rng);
+    let public_inputs = circuit.get_public_inputs(&mut rng).unwrap();
 
     let prover = MockProver::<pallas::Base>::run(
         VP_CIRCUIT_PARAMS_SIZE,
@@ -658,3 +678,48 @@ fn test_halo2_token_vp_circuit() {
     .unwrap();
     assert_eq!(prover.verify(), Ok(()));
 }
+
+#[test]
+fn test_token_ptx_dynamic_vps() {
+    use crate::{
+        executable::Executable,
+        ledger::{tests::commit_resources, InMemoryLedgerState},
+        ptx_builder::PartialTransactionBuilder,
+    };
+    use rand::rngs::OsRng;
+
+    let mut rng = OsRng;
+    let auth_sk = pallas::Scalar::random(&mut rng);
+    let auth = TokenAuthorization::from_sk_vk(&auth_sk, &COMPRESSED_TOKEN_AUTH_VK);
+    let token = Token::new("btc".to_string(), 1u64);
+    let input_resource =
+        token.create_random_input_token_resource(&mut rng, pallas::Base::random(&mut rng), &auth);
+    let mut state = InMemoryLedgerState::new();
+    let merkle_path = commit_resources(&mut state, &[input_resource.resource])
+        .pop()
+        .unwrap();
+
+    let mut builder = PartialTransactionBuilder::new();
+    builder.add_input(
+        input_resource.resource,
+        merkle_path,
+        |_, input_resources, output_resources| {
+            input_resource.generate_input_token_vps(
+                OsRng,
+                auth,
+                auth_sk,
+                input_resources,
+                output_resources,
+            )
+        },
+    );
+    let mut ptx = builder.build(&mut rng).unwrap();
+    ptx.execute().unwrap();
+
+    // The token vp commits to the auth vp, so the ptx can't omit it.
+    ptx.inputs[0].app_dynamic_vp_verifying_info.clear();
+    assert!(matches!(
+        ptx.execute(),
+        Err(TransactionError::InconsistentDynamicVPCommitment)
+    ));
+}
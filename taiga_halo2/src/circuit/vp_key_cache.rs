@@ -0,0 +1,230 @@
+//! Process-wide cache of the VP circuit keys.
+//!
+//! The vk of a native VP circuit only depends on the circuit type and the params, so it is
+//! generated once per type and params size. Circuits whose constraint system depends on more than
+//! their type, like vamp-ir VPs, are cached under an id of their own. Proving keys are cached by
+//! compressed vk and, if a cache directory is set, persisted to disk so that they survive process
+//! restarts.
+
+use crate::{error::TransactionError, vp_vk::ValidityPredicateVerifyingKey};
+use ff::PrimeField;
+use halo2_proofs::{
+    plonk::{keygen_pk, keygen_vk, Circuit, ProvingKey, VerifyingKey},
+    poly::commitment::Params,
+};
+use lazy_static::lazy_static;
+use pasta_curves::{pallas, vesta};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+lazy_static! {
+    static ref VP_KEY_CACHE: VPKeyCache = VPKeyCache::new();
+}
+
+/// Persists the proving keys of the process-wide cache to `dir`, and loads them from it.
+pub fn set_vp_key_cache_dir(dir: impl Into<PathBuf>) {
+    VP_KEY_CACHE.set_dir(dir);
+}
+
+/// Returns the vk of a circuit whose constraint system only depends on its type.
+pub fn get_vp_circuit_vk<C: Circuit<pallas::Base>>(
+    params: &Params<vesta::Affine>,
+    circuit: &C,
+) -> Result<VerifyingKey<vesta::Affine>, TransactionError> {
+    VP_KEY_CACHE.get_vk(params, circuit)
+}
+
+/// Returns the vk of a circuit whose constraint system is identified by `id`.
+pub fn get_vp_circuit_vk_with_id<C: Circuit<pallas::Base>>(
+    params: &Params<vesta::Affine>,
+    id: &str,
+    circuit: &C,
+) -> Result<VerifyingKey<vesta::Affine>, TransactionError> {
+    VP_KEY_CACHE.get_vk_with_id(params, id, circuit)
+}
+
+/// Returns the pk of `circuit`, whose vk is `vk`.
+pub fn get_vp_circuit_pk<C: Circuit<pallas::Base>>(
+    params: &Params<vesta::Affine>,
+    vk: &VerifyingKey<vesta::Affine>,
+    circuit: &C,
+) -> Result<Arc<ProvingKey<vesta::Affine>>, TransactionError> {
+    VP_KEY_CACHE.get_pk(params, vk, circuit)
+}
+
+// The locks are not held during key generation: synthesizing a circuit may need the vk of
+// another circuit, e.g. the token VP embeds the compressed vk of the token auth VP.
+#[derive(Debug, Default)]
+pub struct VPKeyCache {
+    dir: Mutex<Option<PathBuf>>,
+    // Keyed by circuit id and params size.
+    vks: Mutex<HashMap<(String, u32), VerifyingKey<vesta::Affine>>>,
+    pks: Mutex<HashMap<[u8; 32], Arc<ProvingKey<vesta::Affine>>>>,
+}
+
+impl VPKeyCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_dir(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: Mutex::new(Some(dir.into())),
+            ..Default::default()
+        }
+    }
+
+    pub fn set_dir(&self, dir: impl Into<PathBuf>) {
+        *self.dir.lock().unwrap() = Some(dir.into());
+    }
+
+    pub fn dir(&self) -> Option<PathBuf> {
+        self.dir.lock().unwrap().clone()
+    }
+
+    /// Returns the vk of a circuit whose constraint system only depends on its type.
+    pub fn get_vk<C: Circuit<pallas::Base>>(
+        &self,
+        params: &Params<vesta::Affine>,
+        circuit: &C,
+    ) -> Result<VerifyingKey<vesta::Affine>, TransactionError> {
+        self.get_vk_with_id(params, std::any::type_name::<C>(), circuit)
+    }
+
+    /// Returns the vk of a circuit whose constraint system is identified by `id`. Key generation
+    /// fails on circuits that don't fit in the params, e.g. a vamp-ir VP from an untrusted source.
+    pub fn get_vk_with_id<C: Circuit<pallas::Base>>(
+        &self,
+        params: &Params<vesta::Affine>,
+        id: &str,
+        circuit: &C,
+    ) -> Result<VerifyingKey<vesta::Affine>, TransactionError> {
+        let key = (id.to_string(), params.k());
+        if let Some(vk) = self.vks.lock().unwrap().get(&key) {
+            return Ok(vk.clone());
+        }
+        let vk = keygen_vk(params, circuit)?;
+        self.vks.lock().unwrap().insert(key, vk.clone());
+        Ok(vk)
+    }
+
+    /// Returns the pk of `circuit`, whose vk is `vk`. The pk is looked up in memory, then on
+    /// disk, and is only generated if both miss.
+    pub fn get_pk<C: Circuit<pallas::Base>>(
+        &self,
+        params: &Params<vesta::Affine>,
+        vk: &VerifyingKey<vesta::Affine>,
+        circuit: &C,
+    ) -> Result<Arc<ProvingKey<vesta::Affine>>, TransactionError> {
+        let key = compressed_vk_bytes(vk);
+        if let Some(pk) = self.pks.lock().unwrap().get(&key) {
+            return Ok(pk.clone());
+        }
+
+        let pk = match self.read_pk::<C>(params, &key) {
+            Some(pk) => pk,
+            None => {
+                let pk = keygen_pk(params, vk.clone(), circuit)?;
+                // The cache is only an optimization, so failing to persist the pk is not an error.
+                let _ = self.write_pk(&key, &pk);
+                pk
+            }
+        };
+        let pk = Arc::new(pk);
+        self.pks.lock().unwrap().insert(key, pk.clone());
+        Ok(pk)
+    }
+
+    fn pk_path(&self, key: &[u8; 32]) -> Option<PathBuf> {
+        let name: String = key.iter().map(|b| format!("{:02x}", b)).collect();
+        self.dir().map(|dir| dir.join(format!("{name}.pk")))
+    }
+
+    // Reads the pk stored under `key`. A missing, unreadable or mismatched file is a cache miss.
+    fn read_pk<C: Circuit<pallas::Base>>(
+        &self,
+        params: &Params<vesta::Affine>,
+        key: &[u8; 32],
+    ) -> Option<ProvingKey<vesta::Affine>> {
+        let file = File::open(self.pk_path(key)?).ok()?;
+        let pk = ProvingKey::read::<_, C>(&mut BufReader::new(file), params).ok()?;
+        (compressed_vk_bytes(pk.get_vk()) == *key).then_some(pk)
+    }
+
+    // Writes the pk to a temporary file first, so that readers never see a partial key.
+    fn write_pk(&self, key: &[u8; 32], pk: &ProvingKey<vesta::Affine>) -> io::Result<()> {
+        let path = match self.pk_path(key) {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        fs::create_dir_all(path.parent().unwrap())?;
+        let tmp_path = path.with_extension("pk.tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        pk.write(&mut writer)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        fs::rename(tmp_path, path)
+    }
+}
+
+fn compressed_vk_bytes(vk: &VerifyingKey<vesta::Affine>) -> [u8; 32] {
+    ValidityPredicateVerifyingKey::from_vk(vk.clone())
+        .get_compressed()
+        .to_repr()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::VPKeyCache;
+    use crate::circuit::vp_examples::TrivialValidityPredicateCircuit;
+    use crate::constant::{SETUP_PARAMS_MAP, VP_CIRCUIT_PARAMS_SIZE};
+    use crate::vp_vk::ValidityPredicateVerifyingKey;
+    use halo2_proofs::poly::commitment::Params;
+    use pasta_curves::vesta;
+    use rand::{rngs::OsRng, RngCore};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_vp_key_cache() {
+        let params = SETUP_PARAMS_MAP.get(&VP_CIRCUIT_PARAMS_SIZE).unwrap();
+        let circuit = TrivialValidityPredicateCircuit::default();
+        let dir = std::env::temp_dir().join(format!("taiga_vp_keys_{}", OsRng.next_u64()));
+
+        let cache = VPKeyCache::with_dir(&dir);
+        let vk = cache.get_vk(params, &circuit).unwrap();
+        let pk = cache.get_pk(params, &vk, &circuit).unwrap();
+        assert!(Arc::ptr_eq(
+            &pk,
+            &cache.get_pk(params, &vk, &circuit).unwrap()
+        ));
+
+        // A new cache loads the pk from disk.
+        let cache = VPKeyCache::with_dir(&dir);
+        let loaded_pk = cache.get_pk(params, &vk, &circuit).unwrap();
+        assert_eq!(
+            ValidityPredicateVerifyingKey::from_vk(loaded_pk.get_vk().clone()).get_compressed(),
+            ValidityPredicateVerifyingKey::from_vk(vk).get_compressed()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_vp_key_cache_params() {
+        let params = SETUP_PARAMS_MAP.get(&VP_CIRCUIT_PARAMS_SIZE).unwrap();
+        let larger_params = Params::<vesta::Affine>::new(VP_CIRCUIT_PARAMS_SIZE + 1);
+        let circuit = TrivialValidityPredicateCircuit::default();
+
+        // The vk generated with other params is not served.
+        let cache = VPKeyCache::new();
+        let vk = cache.get_vk(params, &circuit).unwrap();
+        let larger_vk = cache.get_vk(&larger_params, &circuit).unwrap();
+        assert_ne!(
+            ValidityPredicateVerifyingKey::from_vk(vk).get_compressed(),
+            ValidityPredicateVerifyingKey::from_vk(larger_vk).get_compressed()
+        );
+    }
+}
@@ -0,0 +1,297 @@
+//! Process-wide registry of the native VP circuits.
+//!
+//! Native halo2 circuits don't have a unified representation, so a `ValidityPredicateByteCode`
+//! refers to its circuit by an identifier. A crate registers its VP circuit type under an
+//! identifier with the functions to decode it from the bytecode inputs, to prove it and to verify
+//! it transparently, and the bytecode is dispatched through the registry.
+
+#[cfg(feature = "borsh")]
+use crate::circuit::vp_examples::TrivialValidityPredicateCircuit;
+#[cfg(feature = "examples")]
+use crate::circuit::vp_examples::{
+    cascade_intent::CascadeIntentValidityPredicateCircuit,
+    or_relation_intent::OrRelationIntentValidityPredicateCircuit,
+    partial_fulfillment_intent::PartialFulfillmentIntentValidityPredicateCircuit,
+    receiver_vp::ReceiverValidityPredicateCircuit,
+    signature_verification::SignatureVerificationValidityPredicateCircuit,
+    token::TokenValidityPredicateCircuit,
+};
+use crate::{
+    circuit::vp_circuit::{
+        VPVerifyingInfo, ValidityPredicatePublicInputs, ValidityPredicateVerifyingInfo,
+    },
+    error::TransactionError,
+};
+use lazy_static::lazy_static;
+use rand::RngCore;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+pub const TRIVIAL_VP_CIRCUIT_ID: &str = "Trivial";
+pub const TOKEN_VP_CIRCUIT_ID: &str = "Token";
+pub const SIGNATURE_VERIFICATION_VP_CIRCUIT_ID: &str = "SignatureVerification";
+pub const RECEIVER_VP_CIRCUIT_ID: &str = "Receiver";
+pub const PARTIAL_FULFILLMENT_INTENT_VP_CIRCUIT_ID: &str = "PartialFulfillmentIntent";
+pub const OR_RELATION_INTENT_VP_CIRCUIT_ID: &str = "OrRelationIntent";
+pub const CASCADE_INTENT_VP_CIRCUIT_ID: &str = "CascadeIntent";
+
+lazy_static! {
+    static ref VP_CIRCUIT_REGISTRY: VPCircuitRegistry = VPCircuitRegistry::with_examples();
+}
+
+/// Registers `circuit` under `id` in the process-wide registry.
+pub fn register_vp_circuit(
+    id: impl Into<String>,
+    circuit: NativeVPCircuit,
+) -> Result<(), TransactionError> {
+    VP_CIRCUIT_REGISTRY.register(id, circuit)
+}
+
+/// Returns the circuit registered under `id` in the process-wide registry.
+pub fn get_vp_circuit(id: &str) -> Result<NativeVPCircuit, TransactionError> {
+    VP_CIRCUIT_REGISTRY.get(id)
+}
+
+pub type DecodeVPCircuitFn =
+    fn(&[u8]) -> Result<Box<dyn ValidityPredicateVerifyingInfo>, TransactionError>;
+pub type ProveVPCircuitFn = fn(
+    &dyn ValidityPredicateVerifyingInfo,
+    &mut dyn RngCore,
+) -> Result<VPVerifyingInfo, TransactionError>;
+pub type VerifyVPCircuitTransparentlyFn =
+    fn(
+        &dyn ValidityPredicateVerifyingInfo,
+    ) -> Result<ValidityPredicatePublicInputs, TransactionError>;
+
+#[derive(Clone, Copy)]
+pub struct NativeVPCircuit {
+    /// Decodes the circuit from the bytecode inputs.
+    pub decode: DecodeVPCircuitFn,
+    /// Creates the proof of the decoded circuit.
+    pub prove: ProveVPCircuitFn,
+    /// Checks the decoded circuit without a proof and returns its public inputs.
+    pub verify_transparently: VerifyVPCircuitTransparentlyFn,
+}
+
+impl NativeVPCircuit {
+    /// A circuit proven and verified through its `ValidityPredicateVerifyingInfo` impl.
+    pub fn new(decode: DecodeVPCircuitFn) -> Self {
+        Self {
+            decode,
+            prove: |vp, rng| vp.get_verifying_info_with_rng(rng),
+            verify_transparently: |vp| vp.verify_transparently(),
+        }
+    }
+
+    pub fn generate_proof_with_rng(
+        &self,
+        inputs: &[u8],
+        rng: &mut dyn RngCore,
+    ) -> Result<VPVerifyingInfo, TransactionError> {
+        let vp = (self.decode)(inputs)?;
+        (self.prove)(vp.as_ref(), rng)
+    }
+
+    pub fn verify_transparently(
+        &self,
+        inputs: &[u8],
+    ) -> Result<ValidityPredicatePublicInputs, TransactionError> {
+        let vp = (self.decode)(inputs)?;
+        (self.verify_transparently)(vp.as_ref())
+    }
+}
+
+impl std::fmt::Debug for NativeVPCircuit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NativeVPCircuit").finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct VPCircuitRegistry {
+    circuits: RwLock<HashMap<String, NativeVPCircuit>>,
+}
+
+impl VPCircuitRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry with the vp examples enabled by the features.
+    pub fn with_examples() -> Self {
+        #[allow(unused_mut)]
+        let mut circuits: HashMap<String, NativeVPCircuit> = HashMap::new();
+        #[cfg(feature = "borsh")]
+        circuits.insert(
+            TRIVIAL_VP_CIRCUIT_ID.to_string(),
+            NativeVPCircuit::new(|bytes| {
+                Ok(Box::new(TrivialValidityPredicateCircuit::from_bytes(
+                    &bytes.to_vec(),
+                )?))
+            }),
+        );
+        #[cfg(feature = "examples")]
+        {
+            circuits.insert(
+                TOKEN_VP_CIRCUIT_ID.to_string(),
+                NativeVPCircuit::new(|bytes| {
+                    Ok(Box::new(TokenValidityPredicateCircuit::from_bytes(
+                        &bytes.to_vec(),
+                    )?))
+                }),
+            );
+            circuits.insert(
+                SIGNATURE_VERIFICATION_VP_CIRCUIT_ID.to_string(),
+                NativeVPCircuit::new(|bytes| {
+                    Ok(Box::new(
+                        SignatureVerificationValidityPredicateCircuit::from_bytes(&bytes.to_vec())?,
+                    ))
+                }),
+            );
+            circuits.insert(
+                RECEIVER_VP_CIRCUIT_ID.to_string(),
+                NativeVPCircuit::new(|bytes| {
+                    Ok(Box::new(ReceiverValidityPredicateCircuit::from_bytes(
+                        &bytes.to_vec(),
+                    )?))
+                }),
+            );
+            circuits.insert(
+                PARTIAL_FULFILLMENT_INTENT_VP_CIRCUIT_ID.to_string(),
+                NativeVPCircuit::new(|bytes| {
+                    Ok(Box::new(
+                        PartialFulfillmentIntentValidityPredicateCircuit::from_bytes(
+                            &bytes.to_vec(),
+                        )?,
+                    ))
+                }),
+            );
+            circuits.insert(
+                OR_RELATION_INTENT_VP_CIRCUIT_ID.to_string(),
+                NativeVPCircuit::new(|bytes| {
+                    Ok(Box::new(
+                        OrRelationIntentValidityPredicateCircuit::from_bytes(&bytes.to_vec())?,
+                    ))
+                }),
+            );
+            circuits.insert(
+                CASCADE_INTENT_VP_CIRCUIT_ID.to_string(),
+                NativeVPCircuit::new(|bytes| {
+                    Ok(Box::new(CascadeIntentValidityPredicateCircuit::from_bytes(
+                        &bytes.to_vec(),
+                    )?))
+                }),
+            );
+        }
+        Self {
+            circuits: RwLock::new(circuits),
+        }
+    }
+
+    /// Registers `circuit` under `id`. An id can't be registered twice, so that a crate can't
+    /// replace the circuit of another one.
+    pub fn register(
+        &self,
+        id: impl Into<String>,
+        circuit: NativeVPCircuit,
+    ) -> Result<(), TransactionError> {
+        let id = id.into();
+        let mut circuits = self.circuits.write().unwrap();
+        if circuits.contains_key(&id) {
+            return Err(TransactionError::DuplicateValidityPredicateCircuit(id));
+        }
+        circuits.insert(id, circuit);
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Result<NativeVPCircuit, TransactionError> {
+        self.circuits
+            .read()
+            .unwrap()
+            .get(id)
+            .copied()
+            .ok_or_else(|| TransactionError::UnknownValidityPredicateCircuit(id.to_string()))
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.circuits.read().unwrap().contains_key(id)
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "borsh")]
+pub mod tests {
+    use super::*;
+    use crate::circuit::vp_bytecode::{ValidityPredicateByteCode, ValidityPredicateRepresentation};
+    use crate::circuit::vp_examples::tests::random_trivial_vp_circuit;
+    use crate::constant::VP_CIRCUIT_OWNED_RESOURCE_ID_PUBLIC_INPUT_IDX;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn test_register_vp_circuit() {
+        let mut rng = OsRng;
+        let registry = VPCircuitRegistry::new();
+        assert!(!registry.contains(TRIVIAL_VP_CIRCUIT_ID));
+        assert!(matches!(
+            registry.get(TRIVIAL_VP_CIRCUIT_ID),
+            Err(TransactionError::UnknownValidityPredicateCircuit(_))
+        ));
+
+        let circuit = NativeVPCircuit::new(|bytes| {
+            Ok(Box::new(TrivialValidityPredicateCircuit::from_bytes(
+                &bytes.to_vec(),
+            )?))
+        });
+        registry.register("Custom", circuit).unwrap();
+        assert!(matches!(
+            registry.register("Custom", circuit),
+            Err(TransactionError::DuplicateValidityPredicateCircuit(_))
+        ));
+
+        let vp = random_trivial_vp_circuit(&mut rng);
+        let public_inputs = registry
+            .get("Custom")
+            .unwrap()
+            .verify_transparently(&vp.to_bytes())
+            .unwrap();
+        assert_eq!(
+            public_inputs.get_from_index(VP_CIRCUIT_OWNED_RESOURCE_ID_PUBLIC_INPUT_IDX),
+            vp.owned_resource_id
+        );
+    }
+
+    #[test]
+    fn test_bytecode_dispatch() {
+        let mut rng = OsRng;
+        register_vp_circuit(
+            "test_bytecode_dispatch",
+            get_vp_circuit(TRIVIAL_VP_CIRCUIT_ID).unwrap(),
+        )
+        .unwrap();
+
+        let vp = random_trivial_vp_circuit(&mut rng);
+        let bytecode = ValidityPredicateByteCode::new(
+            ValidityPredicateRepresentation::Native("test_bytecode_dispatch".to_string()),
+            vp.to_bytes(),
+        );
+        bytecode.generate_proof_with_rng(&mut rng).unwrap();
+
+        let unknown = ValidityPredicateByteCode::new(
+            ValidityPredicateRepresentation::Native("Unknown".to_string()),
+            vp.to_bytes(),
+        );
+        assert!(matches!(
+            unknown.generate_proof_with_rng(&mut rng),
+            Err(TransactionError::UnknownValidityPredicateCircuit(_))
+        ));
+
+        let malformed = ValidityPredicateByteCode::new(
+            ValidityPredicateRepresentation::Native(TRIVIAL_VP_CIRCUIT_ID.to_string()),
+            vec![1, 2, 3],
+        );
+        assert!(matches!(
+            malformed.verify_transparently(&[], &[]),
+            Err(TransactionError::InvalidValidityPredicateInputs(_))
+        ));
+    }
+}
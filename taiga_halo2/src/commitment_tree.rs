@@ -0,0 +1,431 @@
+//! An incremental, append-only resource commitment tree.
+//!
+//! The tree only keeps its frontier (the left siblings that are still waiting for a right
+//! sibling) and the roots of empty subtrees, so appending a commitment is O(depth) regardless
+//! of how many commitments have been appended. Leaves can be marked when they are appended;
+//! the tree then keeps their `MerklePath` witnesses up to date as new commitments arrive.
+use crate::{
+    constant::TAIGA_COMMITMENT_TREE_DEPTH,
+    merkle_tree::{Anchor, MerklePath, Node, LR},
+    resource::ResourceCommitment,
+    transaction::TransactionResult,
+};
+use lazy_static::lazy_static;
+use pasta_curves::pallas;
+use std::collections::{BTreeMap, VecDeque};
+
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+
+lazy_static! {
+    /// The roots of empty subtrees indexed by height, `EMPTY_ROOTS[0]` is the empty leaf.
+    pub static ref EMPTY_ROOTS: Vec<Node> = {
+        let mut roots = vec![Node::from(pallas::Base::zero())];
+        for height in 0..TAIGA_COMMITMENT_TREE_DEPTH {
+            let node = roots[height];
+            roots.push(Node::combine(&node, &node));
+        }
+        roots
+    };
+}
+
+/// The position of a leaf in the commitment tree.
+pub type Position = u64;
+
+/// The identifier of a checkpoint, e.g. the block height it was taken at.
+pub type CheckpointId = u64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommitmentTreeError {
+    /// The tree already holds 2^TAIGA_COMMITMENT_TREE_DEPTH commitments.
+    TreeFull,
+    /// There is no leaf to mark.
+    EmptyTree,
+    /// Checkpoint ids must be strictly increasing.
+    CheckpointOutOfOrder(CheckpointId),
+    /// The checkpoint doesn't exist or has been pruned.
+    UnknownCheckpoint(CheckpointId),
+}
+
+/// The authentication path of a marked leaf.
+///
+/// `siblings[i]` is the sibling at height `i` once it is known: left siblings are known when the
+/// leaf is appended, right siblings once their subtree is complete.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+struct Witness {
+    cm: ResourceCommitment,
+    siblings: Vec<Option<Node>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+pub struct CommitmentTree {
+    // The number of appended leaves.
+    size: u64,
+    // ommers[i] is the complete left subtree at height i waiting for its right sibling.
+    // It is set iff the i-th bit of `size` is one.
+    ommers: Vec<Option<Node>>,
+    // The left siblings of the latest leaf, kept so that it can be marked after appending.
+    latest: Option<(ResourceCommitment, Vec<Option<Node>>)>,
+    witnesses: BTreeMap<Position, Witness>,
+    // Checkpoints in increasing id order.
+    checkpoints: VecDeque<Checkpoint>,
+}
+
+/// A snapshot of the tree state, including the marked-leaf witnesses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+struct Checkpoint {
+    id: CheckpointId,
+    root: Anchor,
+    size: u64,
+    ommers: Vec<Option<Node>>,
+    latest: Option<(ResourceCommitment, Vec<Option<Node>>)>,
+    witnesses: BTreeMap<Position, Witness>,
+}
+
+impl CommitmentTree {
+    pub fn new() -> Self {
+        Self {
+            size: 0,
+            ommers: vec![None; TAIGA_COMMITMENT_TREE_DEPTH],
+            latest: None,
+            witnesses: BTreeMap::new(),
+            checkpoints: VecDeque::new(),
+        }
+    }
+
+    /// Returns the number of appended commitments.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Appends a resource commitment and returns its position.
+    pub fn append(&mut self, cm: ResourceCommitment) -> Result<Position, CommitmentTreeError> {
+        if self.size >= 1u64 << TAIGA_COMMITMENT_TREE_DEPTH {
+            return Err(CommitmentTreeError::TreeFull);
+        }
+        let position = self.size;
+        // The current ommers are exactly the left siblings of the new leaf.
+        self.latest = Some((cm, self.ommers.clone()));
+
+        let mut cur = Node::from(cm);
+        for height in 0..TAIGA_COMMITMENT_TREE_DEPTH {
+            // `cur` is the complete subtree at `height` that ends with the new leaf.
+            // Fill it in as the right sibling of any marked leaf waiting for it.
+            let index = position >> height;
+            if index & 1 == 1 {
+                let sibling_index = index ^ 1;
+                for (pos, witness) in self.witnesses.range_mut(..position) {
+                    if pos >> height == sibling_index {
+                        witness.siblings[height] = Some(cur);
+                    }
+                }
+                let left = self.ommers[height]
+                    .take()
+                    .expect("the left ommer exists when the bit is set");
+                cur = Node::combine(&left, &cur);
+            } else {
+                self.ommers[height] = Some(cur);
+                break;
+            }
+        }
+        self.size += 1;
+
+        Ok(position)
+    }
+
+    /// Appends the output resource commitments of an executed transaction.
+    pub fn append_result(
+        &mut self,
+        result: &TransactionResult,
+    ) -> Result<Vec<Position>, CommitmentTreeError> {
+        result
+            .output_cms
+            .iter()
+            .map(|cm| self.append(*cm))
+            .collect()
+    }
+
+    /// Appends a resource commitment and marks it so that its witness is kept up to date.
+    pub fn append_marked(
+        &mut self,
+        cm: ResourceCommitment,
+    ) -> Result<Position, CommitmentTreeError> {
+        self.append(cm)?;
+        self.mark()
+    }
+
+    /// Marks the latest appended leaf and returns its position.
+    pub fn mark(&mut self) -> Result<Position, CommitmentTreeError> {
+        let (cm, left_siblings) = self.latest.clone().ok_or(CommitmentTreeError::EmptyTree)?;
+        let position = self.size - 1;
+        self.witnesses.entry(position).or_insert_with(|| Witness {
+            cm,
+            siblings: left_siblings,
+        });
+        Ok(position)
+    }
+
+    /// Stops tracking the witness of a marked leaf. Returns false if the leaf was not marked.
+    pub fn remove_mark(&mut self, position: Position) -> bool {
+        self.witnesses.remove(&position).is_some()
+    }
+
+    /// Returns the positions of all marked leaves.
+    pub fn marked_positions(&self) -> Vec<Position> {
+        self.witnesses.keys().cloned().collect()
+    }
+
+    /// Returns the current root of the tree.
+    pub fn root(&self) -> Anchor {
+        self.partial_root(TAIGA_COMMITMENT_TREE_DEPTH).into()
+    }
+
+    /// Returns the up-to-date Merkle path of a marked leaf.
+    pub fn witness(&self, position: Position) -> Option<MerklePath> {
+        let witness = self.witnesses.get(&position)?;
+        let path = (0..TAIGA_COMMITMENT_TREE_DEPTH)
+            .map(|height| {
+                if (position >> height) & 1 == 1 {
+                    let left = witness.siblings[height].expect("left siblings are always known");
+                    (left, LR::L)
+                } else {
+                    let right = witness.siblings[height]
+                        .unwrap_or_else(|| self.incomplete_right_sibling(position, height));
+                    (right, LR::R)
+                }
+            })
+            .collect();
+        Some(MerklePath::from_path(path))
+    }
+
+    /// Returns the commitment of a marked leaf.
+    pub fn marked_commitment(&self, position: Position) -> Option<ResourceCommitment> {
+        self.witnesses.get(&position).map(|witness| witness.cm)
+    }
+
+    /// Records the current state of the tree under `id`.
+    pub fn checkpoint(&mut self, id: CheckpointId) -> Result<(), CommitmentTreeError> {
+        if let Some(last) = self.checkpoints.back() {
+            if id <= last.id {
+                return Err(CommitmentTreeError::CheckpointOutOfOrder(id));
+            }
+        }
+        self.checkpoints.push_back(Checkpoint {
+            id,
+            root: self.root(),
+            size: self.size,
+            ommers: self.ommers.clone(),
+            latest: self.latest.clone(),
+            witnesses: self.witnesses.clone(),
+        });
+        Ok(())
+    }
+
+    /// Restores the tree, including marks and witnesses, to the state recorded by checkpoint `id`.
+    /// All checkpoints taken after `id` are discarded, the checkpoint `id` itself is kept.
+    pub fn rewind(&mut self, id: CheckpointId) -> Result<(), CommitmentTreeError> {
+        let index = self
+            .checkpoints
+            .iter()
+            .position(|checkpoint| checkpoint.id == id)
+            .ok_or(CommitmentTreeError::UnknownCheckpoint(id))?;
+        self.checkpoints.truncate(index + 1);
+        let checkpoint = &self.checkpoints[index];
+        self.size = checkpoint.size;
+        self.ommers = checkpoint.ommers.clone();
+        self.latest = checkpoint.latest.clone();
+        self.witnesses = checkpoint.witnesses.clone();
+        Ok(())
+    }
+
+    /// Drops all checkpoints older than `id`. They can no longer be rewound to.
+    pub fn prune_checkpoints(&mut self, id: CheckpointId) {
+        while let Some(checkpoint) = self.checkpoints.front() {
+            if checkpoint.id >= id {
+                break;
+            }
+            self.checkpoints.pop_front();
+        }
+    }
+
+    /// Returns the ids of the retained checkpoints in increasing order.
+    pub fn checkpoint_ids(&self) -> Vec<CheckpointId> {
+        self.checkpoints
+            .iter()
+            .map(|checkpoint| checkpoint.id)
+            .collect()
+    }
+
+    /// Returns the root of the tree at checkpoint `id`.
+    pub fn checkpoint_root(&self, id: CheckpointId) -> Option<Anchor> {
+        self.checkpoints
+            .iter()
+            .find(|checkpoint| checkpoint.id == id)
+            .map(|checkpoint| checkpoint.root)
+    }
+
+    /// Returns the roots of the retained checkpoints, oldest first.
+    pub fn checkpoint_roots(&self) -> Vec<Anchor> {
+        self.checkpoints
+            .iter()
+            .map(|checkpoint| checkpoint.root)
+            .collect()
+    }
+
+    // The root of the subtree at `height` that contains the next free position.
+    fn partial_root(&self, height: usize) -> Node {
+        let mut node = EMPTY_ROOTS[0];
+        for (h, ommer) in self.ommers.iter().enumerate().take(height) {
+            node = match ommer {
+                Some(left) => Node::combine(left, &node),
+                None => Node::combine(&node, &EMPTY_ROOTS[h]),
+            };
+        }
+        node
+    }
+
+    // The right sibling at `height` of the leaf at `position` before that subtree is complete.
+    fn incomplete_right_sibling(&self, position: Position, height: usize) -> Node {
+        let sibling_start = ((position >> height) ^ 1) << height;
+        if self.size <= sibling_start {
+            EMPTY_ROOTS[height]
+        } else {
+            self.partial_root(height)
+        }
+    }
+}
+
+impl Default for CommitmentTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{CommitmentTree, CommitmentTreeError, EMPTY_ROOTS};
+    use crate::constant::TAIGA_COMMITMENT_TREE_DEPTH;
+    use crate::merkle_tree::{Anchor, Node};
+    use crate::resource::ResourceCommitment;
+    use halo2_proofs::arithmetic::Field;
+    use pasta_curves::pallas;
+    use rand::rngs::OsRng;
+    use rand::RngCore;
+
+    pub fn random_commitment<R: RngCore>(mut rng: R) -> ResourceCommitment {
+        ResourceCommitment::from(pallas::Base::random(&mut rng))
+    }
+
+    // Computes the root of the tree by hashing every leaf, for comparison.
+    fn naive_root(leaves: &[ResourceCommitment]) -> Anchor {
+        let mut layer: Vec<Node> = leaves.iter().map(|cm| Node::from(*cm)).collect();
+        for height in 0..TAIGA_COMMITMENT_TREE_DEPTH {
+            if layer.len() % 2 == 1 {
+                layer.push(EMPTY_ROOTS[height]);
+            }
+            layer = layer
+                .chunks(2)
+                .map(|pair| Node::combine(&pair[0], &pair[1]))
+                .collect();
+        }
+        layer[0].into()
+    }
+
+    #[test]
+    fn test_commitment_tree_root() {
+        let mut rng = OsRng;
+        let mut tree = CommitmentTree::new();
+        assert_eq!(
+            tree.root(),
+            Anchor::from(EMPTY_ROOTS[TAIGA_COMMITMENT_TREE_DEPTH])
+        );
+
+        let mut leaves = vec![];
+        for _ in 0..13 {
+            let cm = random_commitment(&mut rng);
+            leaves.push(cm);
+            tree.append(cm).unwrap();
+            assert_eq!(tree.root(), naive_root(&leaves));
+        }
+    }
+
+    #[test]
+    fn test_commitment_tree_witness() {
+        let mut rng = OsRng;
+        let mut tree = CommitmentTree::new();
+        let mut marked = vec![];
+        for i in 0..17 {
+            let cm = random_commitment(&mut rng);
+            if i % 3 == 0 {
+                let position = tree.append_marked(cm).unwrap();
+                marked.push((position, cm));
+            } else {
+                tree.append(cm).unwrap();
+            }
+
+            // Every witness must be consistent with the current root.
+            let root = tree.root();
+            for (position, cm) in marked.iter() {
+                let path = tree.witness(*position).unwrap();
+                assert_eq!(path.root(Node::from(*cm)), root);
+            }
+        }
+
+        let (position, _) = marked[0];
+        assert!(tree.remove_mark(position));
+        assert!(tree.witness(position).is_none());
+    }
+
+    #[test]
+    fn test_commitment_tree_rewind() {
+        let mut rng = OsRng;
+        let mut tree = CommitmentTree::new();
+        let position = tree.append_marked(random_commitment(&mut rng)).unwrap();
+        tree.append(random_commitment(&mut rng)).unwrap();
+        tree.checkpoint(1).unwrap();
+        let root_1 = tree.root();
+        let path_1 = tree.witness(position).unwrap();
+
+        tree.append(random_commitment(&mut rng)).unwrap();
+        let later = tree.append_marked(random_commitment(&mut rng)).unwrap();
+        tree.checkpoint(2).unwrap();
+        tree.append(random_commitment(&mut rng)).unwrap();
+        assert_ne!(tree.root(), root_1);
+        assert_eq!(
+            tree.checkpoint(2),
+            Err(CommitmentTreeError::CheckpointOutOfOrder(2))
+        );
+
+        tree.rewind(1).unwrap();
+        assert_eq!(tree.root(), root_1);
+        assert_eq!(tree.size(), 2);
+        assert_eq!(tree.witness(position).unwrap(), path_1);
+        assert!(tree.witness(later).is_none());
+        assert_eq!(tree.checkpoint_ids(), vec![1]);
+        assert_eq!(tree.checkpoint_roots(), vec![root_1]);
+
+        // The tree keeps working after a rewind.
+        let cm = random_commitment(&mut rng);
+        let new_position = tree.append_marked(cm).unwrap();
+        assert_eq!(new_position, 2);
+        assert_eq!(
+            tree.witness(new_position).unwrap().root(Node::from(cm)),
+            tree.root()
+        );
+
+        tree.checkpoint(3).unwrap();
+        tree.prune_checkpoints(3);
+        assert_eq!(tree.checkpoint_ids(), vec![3]);
+        assert_eq!(
+            tree.rewind(1),
+            Err(CommitmentTreeError::UnknownCheckpoint(1))
+        );
+    }
+}
@@ -7,11 +7,13 @@ use crate::{
     circuit::compliance_circuit::ComplianceCircuit,
     constant::{PRF_EXPAND_INPUT_VP_CM_R, PRF_EXPAND_OUTPUT_VP_CM_R},
     delta_commitment::DeltaCommitment,
-    merkle_tree::{Anchor, MerklePath},
+    error::TransactionError,
+    merkle_tree::{Anchor, MerklePath, LR},
     nullifier::Nullifier,
     resource::{RandomSeed, Resource, ResourceCommitment},
     vp_commitment::ValidityPredicateCommitment,
 };
+use ff::PrimeField;
 use pasta_curves::pallas;
 use rand::RngCore;
 
@@ -49,12 +51,12 @@ pub struct CompliancePublicInputs {
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
 pub struct ComplianceInfo {
-    input_resource: Resource,
-    input_merkle_path: MerklePath,
-    input_anchor: Anchor,
-    output_resource: Resource,
+    pub(crate) input_resource: Resource,
+    pub(crate) input_merkle_path: MerklePath,
+    pub(crate) input_anchor: Anchor,
+    pub(crate) output_resource: Resource,
     // rseed is to generate the randomness of the delta commitment and vp commitments
-    rseed: RandomSeed,
+    pub(crate) rseed: RandomSeed,
 }
 
 impl CompliancePublicInputs {
@@ -149,6 +151,31 @@ impl ComplianceInfo {
         }
     }
 
+    // Ephemeral input resources are not checked against the commitment tree in the compliance circuit,
+    // but their anchor is still public and must be one of the recent roots for the tx to be accepted.
+    // Use the latest root as the anchor. The merkle path is a placeholder.
+    pub fn new_ephemeral_input<R: RngCore>(
+        input_resource: Resource,
+        anchor: Anchor,
+        output_resource: &mut Resource,
+        rng: R,
+    ) -> Result<Self, TransactionError> {
+        if !input_resource.is_ephemeral {
+            return Err(TransactionError::NonEphemeralInputResource);
+        }
+        Ok(Self::new(
+            input_resource,
+            MerklePath::default(),
+            Some(anchor),
+            output_resource,
+            rng,
+        ))
+    }
+
+    pub fn is_input_ephemeral(&self) -> bool {
+        self.input_resource.is_ephemeral
+    }
+
     // Get the randomness of delta commitment
     pub fn get_rcv(&self) -> pallas::Scalar {
         self.rseed.get_rcv()
@@ -174,16 +201,37 @@ impl ComplianceInfo {
         DeltaCommitment::commit(&self.input_resource, &self.output_resource, blind_r)
     }
 
-    pub fn get_input_resource_nullifier(&self) -> Nullifier {
-        self.input_resource.get_nf().unwrap()
+    // The nullifier can only be derived if the input resource carries its nullifier key.
+    pub fn get_input_resource_nullifier(&self) -> Result<Nullifier, TransactionError> {
+        self.input_resource
+            .get_nf()
+            .ok_or(TransactionError::MissingTransparentResourceNullifierKey)
     }
 
     pub fn get_output_resource_cm(&self) -> ResourceCommitment {
         self.output_resource.commitment()
     }
 
-    pub fn build(&self) -> (CompliancePublicInputs, ComplianceCircuit) {
-        let nf = self.get_input_resource_nullifier();
+    /// The encoding of the whole compliance, which is public in a transparent ptx.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.input_resource.to_bytes();
+        let path = self.input_merkle_path.get_path();
+        bytes.extend_from_slice(&(path.len() as u32).to_le_bytes());
+        for (node, lr) in path {
+            bytes.extend_from_slice(&node.to_repr());
+            bytes.push(match lr {
+                LR::L => 0,
+                LR::R => 1,
+            });
+        }
+        bytes.extend_from_slice(&self.input_anchor.to_bytes());
+        bytes.extend_from_slice(&self.output_resource.to_bytes());
+        bytes.extend_from_slice(&self.rseed.to_bytes());
+        bytes
+    }
+
+    pub fn build(&self) -> Result<(CompliancePublicInputs, ComplianceCircuit), TransactionError> {
+        let nf = self.get_input_resource_nullifier()?;
         assert_eq!(
             nf, self.output_resource.nonce,
             "The nf of input resource must be equal to the nonce of output resource"
@@ -220,7 +268,7 @@ impl ComplianceInfo {
             output_vp_cm_r,
         };
 
-        (compliance, compliance_circuit)
+        Ok((compliance, compliance_circuit))
     }
 }
 
@@ -228,8 +276,11 @@ impl ComplianceInfo {
 pub mod tests {
     use super::ComplianceInfo;
     use crate::constant::TAIGA_COMMITMENT_TREE_DEPTH;
+    use crate::error::TransactionError;
+    use crate::ledger::InMemoryLedgerState;
     use crate::merkle_tree::MerklePath;
     use crate::resource::tests::random_resource;
+    use rand::rngs::OsRng;
     use rand::RngCore;
 
     pub fn random_compliance_info<R: RngCore>(mut rng: R) -> ComplianceInfo {
@@ -244,4 +295,36 @@ pub mod tests {
             &mut rng,
         )
     }
+
+    #[test]
+    fn test_new_ephemeral_input() {
+        let mut rng = OsRng;
+        let anchor = InMemoryLedgerState::new().root();
+        let mut input_resource = random_resource(&mut rng);
+        let mut output_resource = random_resource(&mut rng);
+
+        input_resource.is_ephemeral = false;
+        assert!(matches!(
+            ComplianceInfo::new_ephemeral_input(
+                input_resource,
+                anchor,
+                &mut output_resource,
+                &mut rng
+            ),
+            Err(TransactionError::NonEphemeralInputResource)
+        ));
+
+        input_resource.is_ephemeral = true;
+        let compliance = ComplianceInfo::new_ephemeral_input(
+            input_resource,
+            anchor,
+            &mut output_resource,
+            &mut rng,
+        )
+        .unwrap();
+        assert!(compliance.is_input_ephemeral());
+        let (public_inputs, _) = compliance.build().unwrap();
+        assert_eq!(public_inputs.anchor, anchor);
+        assert_eq!(output_resource.nonce, input_resource.get_nf().unwrap());
+    }
 }
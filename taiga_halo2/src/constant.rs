@@ -1,4 +1,5 @@
 use crate::circuit::compliance_circuit::ComplianceCircuit;
+use crate::setup_params::load_setup_params;
 use crate::utils::to_field_elements;
 use group::Group;
 use halo2_gadgets::{
@@ -24,8 +25,24 @@ pub const RESOURCE_COMMITMENT_PERSONALIZATION: &str = "Taiga-NoteCommit";
 
 pub const TRANSACTION_BINDING_HASH_PERSONALIZATION: &[u8; 16] = b"TxBindingSigHash";
 
+pub const TRANSACTION_ID_PERSONALIZATION: &[u8; 16] = b"Taiga_TxId______";
+
+pub const TRANSACTION_AUTH_DIGEST_PERSONALIZATION: &[u8; 16] = b"Taiga_TxAuthData";
+
+pub const SHIELDED_PTX_ID_PERSONALIZATION: &[u8; 16] = b"Taiga_PtxId_____";
+
+pub const SHIELDED_PTX_PROOFS_DIGEST_PERSONALIZATION: &[u8; 16] = b"Taiga_PtxProofs_";
+
+pub const TRANSPARENT_PTX_ID_PERSONALIZATION: &[u8; 16] = b"Taiga_TPtxId____";
+
 pub const VP_COMMITMENT_PERSONALIZATION: &[u8; 8] = b"VPCommit";
 
+pub const LEDGER_LOG_CHECKSUM_PERSONALIZATION: &[u8; 16] = b"Taiga_LedgerLog_";
+
+pub const PARAMS_CHECKSUM_PERSONALIZATION: &[u8; 16] = b"Taiga_ParamsFile";
+
+pub const VAMP_IR_SOURCE_DIGEST_PERSONALIZATION: &[u8; 16] = b"Taiga_VampIRSrc_";
+
 pub const PRF_EXPAND_PERSONALIZATION: &[u8; 16] = b"Taiga_ExpandSeed";
 lazy_static! {
     pub static ref PRF_EXPAND_PERSONALIZATION_TO_FIELD: pallas::Base =
@@ -38,15 +55,27 @@ pub const PRF_EXPAND_PUBLIC_INPUT_PADDING: u8 = 2;
 pub const PRF_EXPAND_VCM_R: u8 = 3;
 pub const PRF_EXPAND_INPUT_VP_CM_R: u8 = 4;
 pub const PRF_EXPAND_OUTPUT_VP_CM_R: u8 = 5;
-pub const PRF_EXPAND_DYNAMIC_VP_1_CM_R: u8 = 6;
-pub const PRF_EXPAND_DYNAMIC_VP_2_CM_R: u8 = 7;
 
 /// Commitment merkle tree depth
 pub const TAIGA_COMMITMENT_TREE_DEPTH: usize = 32;
 
+/// The number of recent commitment tree roots that are accepted as anchors.
+pub const ROOT_HISTORY_SIZE: usize = 100;
+
+/// The number of transaction results a persistent ledger state logs before writing a new snapshot.
+pub const LEDGER_SNAPSHOT_INTERVAL: u64 = 1000;
+
 pub const BASE_BITS_NUM: usize = 255;
 
-/// The number of resources in a (partial)tx.
+/// The number of input resources and of output resources in a (partial)tx. Every ptx has exactly
+/// NUM_RESOURCE compliances, input resource VPs and output resource VPs. `PartialTransactionBuilder`
+/// fills the slots a ptx doesn't use with padding resources, which still take compliance and VP
+/// proofs. The VP circuits and their mandatory public inputs are laid out from it, so changing it
+/// changes the vks of all the VPs. The VP circuit layout supports at most 4, and the vamp-ir
+/// prelude checks every resource, so a larger value makes vamp-ir VPs larger.
+// TODO: support ptxs with fewer resources than NUM_RESOURCE without padding proofs, e.g. with a
+// per-slot enabled flag in the compliance and VP circuits. An output without an input in its
+// compliance then needs another source for its nonce.
 pub const NUM_RESOURCE: usize = 2;
 
 pub const COMPLIANCE_NF_PUBLIC_INPUT_ROW_IDX: usize = 0;
@@ -66,27 +95,35 @@ pub const VALUE_BASE_DOMAIN_POSTFIX: &str = "Taiga-NoteType";
 pub const VP_CIRCUIT_PUBLIC_INPUT_NUM: usize = VP_CIRCUIT_MANDATORY_PUBLIC_INPUT_NUM
     + VP_CIRCUIT_CUSTOM_PUBLIC_INPUT_NUM
     + VP_CIRCUIT_RESOURCE_ENCRYPTION_PUBLIC_INPUT_NUM;
-pub const VP_CIRCUIT_MANDATORY_PUBLIC_INPUT_NUM: usize = 9;
+// nfs and output cms(2 * NUM_RESOURCE) + owned_resource_id(1) + dynamic vp commitment(2)
+pub const VP_CIRCUIT_MANDATORY_PUBLIC_INPUT_NUM: usize = 2 * NUM_RESOURCE + 3;
 pub const VP_CIRCUIT_CUSTOM_PUBLIC_INPUT_NUM: usize = 2;
 pub const VP_CIRCUIT_RESOURCE_ENCRYPTION_PUBLIC_INPUT_NUM: usize =
     RESOURCE_ENCRYPTION_CIPHERTEXT_NUM + 2; // ciphertext(12) + public_key(2)
 
-pub const VP_CIRCUIT_NULLIFIER_ONE_PUBLIC_INPUT_IDX: usize = 0;
-pub const VP_CIRCUIT_OUTPUT_CM_ONE_PUBLIC_INPUT_IDX: usize = 1;
-pub const VP_CIRCUIT_NULLIFIER_TWO_PUBLIC_INPUT_IDX: usize = 2;
-pub const VP_CIRCUIT_OUTPUT_CM_TWO_PUBLIC_INPUT_IDX: usize = 3;
-pub const VP_CIRCUIT_OWNED_RESOURCE_ID_PUBLIC_INPUT_IDX: usize = 4;
-pub const VP_CIRCUIT_FIRST_DYNAMIC_VP_CM_1: usize = 5;
-pub const VP_CIRCUIT_FIRST_DYNAMIC_VP_CM_2: usize = 6;
-pub const VP_CIRCUIT_SECOND_DYNAMIC_VP_CM_1: usize = 7;
-pub const VP_CIRCUIT_SECOND_DYNAMIC_VP_CM_2: usize = 8;
+/// The public input index of the nullifier of the `i`-th input resource in VP circuits.
+pub const fn vp_circuit_nullifier_public_input_idx(i: usize) -> usize {
+    2 * i
+}
+/// The public input index of the commitment of the `i`-th output resource in VP circuits.
+pub const fn vp_circuit_output_cm_public_input_idx(i: usize) -> usize {
+    2 * i + 1
+}
+pub const VP_CIRCUIT_OWNED_RESOURCE_ID_PUBLIC_INPUT_IDX: usize = 2 * NUM_RESOURCE;
+// The commitment to the digest of all the dynamic vps of the owned resource, see `dynamic_vps_digest`.
+pub const VP_CIRCUIT_DYNAMIC_VP_CM_1: usize = VP_CIRCUIT_OWNED_RESOURCE_ID_PUBLIC_INPUT_IDX + 1;
+pub const VP_CIRCUIT_DYNAMIC_VP_CM_2: usize = VP_CIRCUIT_OWNED_RESOURCE_ID_PUBLIC_INPUT_IDX + 2;
 pub const VP_CIRCUIT_CUSTOM_PUBLIC_INPUT_BEGIN_IDX: usize = VP_CIRCUIT_MANDATORY_PUBLIC_INPUT_NUM;
 pub const VP_CIRCUIT_RESOURCE_ENCRYPTION_PUBLIC_INPUT_BEGIN_IDX: usize =
     VP_CIRCUIT_MANDATORY_PUBLIC_INPUT_NUM + VP_CIRCUIT_CUSTOM_PUBLIC_INPUT_NUM;
-pub const VP_CIRCUIT_RESOURCE_ENCRYPTION_NONCE_IDX: usize = 21;
-pub const VP_CIRCUIT_RESOURCE_ENCRYPTION_MAC_IDX: usize = 22;
-pub const VP_CIRCUIT_RESOURCE_ENCRYPTION_PK_X_IDX: usize = 23;
-pub const VP_CIRCUIT_RESOURCE_ENCRYPTION_PK_Y_IDX: usize = 24;
+pub const VP_CIRCUIT_RESOURCE_ENCRYPTION_NONCE_IDX: usize =
+    VP_CIRCUIT_RESOURCE_ENCRYPTION_PUBLIC_INPUT_BEGIN_IDX + RESOURCE_ENCRYPTION_PLAINTEXT_NUM;
+pub const VP_CIRCUIT_RESOURCE_ENCRYPTION_MAC_IDX: usize =
+    VP_CIRCUIT_RESOURCE_ENCRYPTION_NONCE_IDX + 1;
+pub const VP_CIRCUIT_RESOURCE_ENCRYPTION_PK_X_IDX: usize =
+    VP_CIRCUIT_RESOURCE_ENCRYPTION_PUBLIC_INPUT_BEGIN_IDX + RESOURCE_ENCRYPTION_CIPHERTEXT_NUM;
+pub const VP_CIRCUIT_RESOURCE_ENCRYPTION_PK_Y_IDX: usize =
+    VP_CIRCUIT_RESOURCE_ENCRYPTION_PK_X_IDX + 1;
 
 // Resource encryption
 pub const RESOURCE_ENCRYPTION_PLAINTEXT_NUM: usize = 10;
@@ -121,14 +158,12 @@ pub const PARAMS_SIZE: u32 = 15;
 pub const COMPLIANCE_CIRCUIT_PARAMS_SIZE: u32 = PARAMS_SIZE;
 pub const VP_CIRCUIT_PARAMS_SIZE: u32 = PARAMS_SIZE;
 
-// Setup params map
+// Setup params map, loaded from the configured params source
 lazy_static! {
     pub static ref SETUP_PARAMS_MAP: HashMap<u32, Params<vesta::Affine>> = {
         let mut m = HashMap::new();
-        let params_15 = {
-            let bytes = include_bytes!("../params/params_15");
-            Params::<vesta::Affine>::read(&mut &bytes[..]).unwrap()
-        };
+        let params_15 = load_setup_params(PARAMS_SIZE)
+            .unwrap_or_else(|err| panic!("cannot load params_15 with {}", err));
 
         m.insert(PARAMS_SIZE, params_15);
         m
@@ -6097,8 +6132,6 @@ impl FixedPoint<pallas::Affine> for Short {
     }
 }
 
-pub const MAX_DYNAMIC_VP_NUM: usize = 2;
-
 #[ignore]
 #[test]
 fn r_u_z_generate() {
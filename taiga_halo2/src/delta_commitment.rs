@@ -1,16 +1,19 @@
 use crate::constant::RESOURCE_COMMITMENT_R_GENERATOR;
-use crate::resource::Resource;
+use crate::resource::{Resource, ResourceKind};
 use halo2_proofs::arithmetic::CurveAffine;
 use pasta_curves::group::cofactor::CofactorCurveAffine;
-use pasta_curves::group::{Curve, Group, GroupEncoding};
+use pasta_curves::group::{ff::PrimeField, Curve, Group, GroupEncoding};
 use pasta_curves::pallas;
 #[cfg(feature = "nif")]
-use rustler::NifTuple;
+use rustler::{NifStruct, NifTuple};
 use subtle::CtOption;
 
 #[cfg(feature = "serde")]
 use serde;
 
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+
 #[derive(Copy, Clone, Debug)]
 #[cfg_attr(feature = "nif", derive(NifTuple))]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -59,3 +62,60 @@ impl DeltaCommitment {
         pallas::Point::from_bytes(&bytes).map(DeltaCommitment)
     }
 }
+
+/// A quantity of a resource kind that is balanced in the clear, e.g. a fee. A positive quantity
+/// is consumed by the transaction (paid or burnt) and a negative one is created by it (minted).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "nif", derive(NifStruct))]
+#[cfg_attr(feature = "nif", module = "Taiga.PublicDelta")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PublicDelta {
+    pub kind: ResourceKind,
+    pub quantity: i64,
+}
+
+impl PublicDelta {
+    pub fn new(kind: ResourceKind, quantity: i64) -> Self {
+        Self { kind, quantity }
+    }
+
+    /// The unblinded delta commitment of the public quantity.
+    pub fn commit(&self) -> DeltaCommitment {
+        let quantity = if self.quantity >= 0 {
+            pallas::Scalar::from(self.quantity as u64)
+        } else {
+            -pallas::Scalar::from(self.quantity.unsigned_abs())
+        };
+        DeltaCommitment(self.kind.derive_kind() * quantity)
+    }
+
+    pub fn to_bytes(&self) -> [u8; 72] {
+        let mut bytes = [0u8; 72];
+        bytes[0..32].copy_from_slice(&self.kind.logic.to_repr());
+        bytes[32..64].copy_from_slice(&self.kind.label.to_repr());
+        bytes[64..72].copy_from_slice(&self.quantity.to_le_bytes());
+        bytes
+    }
+}
+
+#[cfg(feature = "borsh")]
+impl BorshSerialize for PublicDelta {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(&self.to_bytes())
+    }
+}
+
+#[cfg(feature = "borsh")]
+impl BorshDeserialize for PublicDelta {
+    fn deserialize_reader<R: std::io::Read>(reader: &mut R) -> std::io::Result<Self> {
+        use crate::utils::read_base_field;
+        use byteorder::{LittleEndian, ReadBytesExt};
+        let logic = read_base_field(reader)?;
+        let label = read_base_field(reader)?;
+        let quantity = reader.read_i64::<LittleEndian>()?;
+        Ok(Self {
+            kind: ResourceKind { logic, label },
+            quantity,
+        })
+    }
+}
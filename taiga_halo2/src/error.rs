@@ -1,5 +1,18 @@
+use crate::{
+    circuit::{
+        vp_circuit::VampIRCircuitError,
+        vp_diagnostics::{ResourceSlot, VPConstraintFailureReport},
+    },
+    commitment_tree::CommitmentTreeError,
+    constant::NUM_RESOURCE,
+    merkle_tree::Anchor,
+    nullifier::Nullifier,
+    resource::ResourceCommitment,
+    transaction::PartialTxIndex,
+};
 use core::fmt;
 use halo2_proofs::plonk::Error as PlonkError;
+use pasta_curves::pallas;
 use std::fmt::Display;
 
 #[derive(Debug)]
@@ -16,6 +29,8 @@ pub enum TransactionError {
     InconsistentOutputResourceCommitment,
     /// Owned resource id is not consistent between the compliance and the vp.
     InconsistentOwnedResourceID,
+    /// Dynamic vps are not the ones the app vp commits to.
+    InconsistentDynamicVPCommitment,
     /// IO error
     IoError(std::io::Error),
     /// Transparent resource nullifier key is missing
@@ -24,8 +39,51 @@ pub enum TransactionError {
     MissingTransparentResourceMerklePath,
     /// Shielded partial Tx binding signature r is missing
     MissingPartialTxBindingSignatureR,
-    /// ValidityPredicateRepresentation is not valid
-    InvalidValidityPredicateRepresentation,
+    /// No native VP circuit is registered under the identifier.
+    UnknownValidityPredicateCircuit(String),
+    /// A native VP circuit is already registered under the identifier.
+    DuplicateValidityPredicateCircuit(String),
+    /// The nullifier has already been spent in the ledger state.
+    SpentNullifier(Nullifier),
+    /// The anchor is not one of the recent roots of the commitment tree.
+    UnknownAnchor(Anchor),
+    /// The input resource is not ephemeral, so it must be proven with a merkle path.
+    NonEphemeralInputResource,
+    /// Commitment tree error
+    CommitmentTree(CommitmentTreeError),
+    /// The nullifier is revealed by two partial transactions(or twice by the same one).
+    DuplicateNullifier(Nullifier, PartialTxIndex, PartialTxIndex),
+    /// The output resource commitment is created by two partial transactions(or twice by the same one).
+    DuplicateOutputResourceCommitment(ResourceCommitment, PartialTxIndex, PartialTxIndex),
+    /// The transaction is for another chain: (the chain id of the tx, the current chain id).
+    ChainIdMismatch(u64, u64),
+    /// The transaction has expired: (the expiry height of the tx, the current height).
+    TransactionExpired(u64, u64),
+    /// The compressed vp vk is not in the vk registry.
+    UnknownVerifyingKey(pallas::Base),
+    /// The vamp-ir VP can't be built from its source and assignments.
+    VampIRCircuit(VampIRCircuitError),
+    /// The vamp-ir source can't be parsed.
+    VampIRParse(String),
+    /// The named vamp-ir variable has no assignment.
+    VampIRMissingAssignment(String),
+    /// The VP circuit doesn't satisfy its constraints.
+    ConstraintFailure(VPConstraintFailureReport),
+    /// The VP circuit can't be decoded from the bytecode inputs.
+    InvalidValidityPredicateInputs(String),
+    /// The numbers of compliances, input resource VPs and output resource VPs of the partial
+    /// transaction are not all NUM_RESOURCE.
+    InvalidPartialTxSize(usize, usize, usize),
+}
+
+impl TransactionError {
+    /// Attributes a VP constraint failure to the resource whose VP fails.
+    pub fn with_vp_owner(self, owner: ResourceSlot) -> Self {
+        match self {
+            Self::ConstraintFailure(report) => Self::ConstraintFailure(report.with_owner(owner)),
+            e => e,
+        }
+    }
 }
 
 impl Display for TransactionError {
@@ -44,6 +102,9 @@ impl Display for TransactionError {
             InconsistentOwnedResourceID => {
                 f.write_str("Owned resource id is not consistent between the compliance and the vp")
             }
+            InconsistentDynamicVPCommitment => {
+                f.write_str("The dynamic vps are not the ones the app vp commits to")
+            }
             IoError(e) => f.write_str(&format!("IoError error: {e}")),
             MissingTransparentResourceNullifierKey => {
                 f.write_str("Transparent resource nullifier key is missing")
@@ -54,9 +115,49 @@ impl Display for TransactionError {
             MissingPartialTxBindingSignatureR => {
                 f.write_str("Shielded partial Tx binding signature r is missing")
             }
-            InvalidValidityPredicateRepresentation => {
-                f.write_str("ValidityPredicateRepresentation is not valid, add borsh feature if using native vp examples ")
+            UnknownValidityPredicateCircuit(id) => f.write_str(&format!(
+                "No VP circuit is registered under {id}, add the examples feature if using native vp examples"
+            )),
+            DuplicateValidityPredicateCircuit(id) => {
+                f.write_str(&format!("A VP circuit is already registered under {id}"))
+            }
+            SpentNullifier(nf) => {
+                f.write_str(&format!("Nullifier {:?} has already been spent", nf.inner()))
+            }
+            UnknownAnchor(anchor) => {
+                f.write_str(&format!("Anchor {:?} is not a recent root", anchor.inner()))
+            }
+            NonEphemeralInputResource => f.write_str("The input resource is not ephemeral"),
+            CommitmentTree(e) => f.write_str(&format!("Commitment tree error: {e:?}")),
+            DuplicateNullifier(nf, first, second) => f.write_str(&format!(
+                "Nullifier {:?} is revealed by both {first:?} and {second:?}",
+                nf.inner()
+            )),
+            DuplicateOutputResourceCommitment(cm, first, second) => f.write_str(&format!(
+                "Output resource commitment {:?} is created by both {first:?} and {second:?}",
+                cm.inner()
+            )),
+            ChainIdMismatch(tx_chain_id, chain_id) => f.write_str(&format!(
+                "Transaction is for chain {tx_chain_id}, not for chain {chain_id}"
+            )),
+            TransactionExpired(expiry_height, height) => f.write_str(&format!(
+                "Transaction expired at height {expiry_height}, the current height is {height}"
+            )),
+            UnknownVerifyingKey(vk) => {
+                f.write_str(&format!("Verifying key {vk:?} is not in the registry"))
+            }
+            VampIRCircuit(e) => f.write_str(&format!("Vamp-IR circuit error: {e:?}")),
+            VampIRParse(e) => f.write_str(&format!("Vamp-IR source can't be parsed: {e}")),
+            VampIRMissingAssignment(name) => {
+                f.write_str(&format!("Vamp-IR variable {name} is not assigned"))
             }
+            ConstraintFailure(report) => f.write_str(&format!("{report}")),
+            InvalidValidityPredicateInputs(e) => {
+                f.write_str(&format!("VP inputs can't be decoded: {e}"))
+            }
+            InvalidPartialTxSize(compliances, inputs, outputs) => f.write_str(&format!(
+                "The partial transaction has {compliances} compliances, {inputs} input and {outputs} output resource VPs instead of {NUM_RESOURCE} each, unused slots hold padding resources"
+            )),
         }
     }
 }
@@ -72,3 +173,21 @@ impl From<std::io::Error> for TransactionError {
         TransactionError::IoError(e)
     }
 }
+
+impl From<VampIRCircuitError> for TransactionError {
+    fn from(e: VampIRCircuitError) -> Self {
+        match e {
+            VampIRCircuitError::SourceParsingError(e) => TransactionError::VampIRParse(e),
+            VampIRCircuitError::MissingAssignment(name) => {
+                TransactionError::VampIRMissingAssignment(name)
+            }
+            e => TransactionError::VampIRCircuit(e),
+        }
+    }
+}
+
+impl From<CommitmentTreeError> for TransactionError {
+    fn from(e: CommitmentTreeError) -> Self {
+        TransactionError::CommitmentTree(e)
+    }
+}
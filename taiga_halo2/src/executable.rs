@@ -6,7 +6,7 @@ use crate::{
 // Executable is an unified interface for partial transaction, which is the atomic executable uinit.
 pub trait Executable {
     fn execute(&self) -> Result<(), TransactionError>;
-    fn get_nullifiers(&self) -> Vec<Nullifier>;
+    fn get_nullifiers(&self) -> Result<Vec<Nullifier>, TransactionError>;
     fn get_output_cms(&self) -> Vec<ResourceCommitment>;
     fn get_delta_commitments(&self) -> Vec<DeltaCommitment>;
     fn get_anchors(&self) -> Vec<Anchor>;
@@ -0,0 +1,801 @@
+//! A JSON representation of resources, ptxs, transactions and their results, meant to be read by
+//! people, e.g. in a block explorer or in test fixtures.
+//!
+//! Encoding rules:
+//! - Byte strings are lowercase hex with a `0x` prefix. Decoding also accepts uppercase digits.
+//! - Field elements(pallas::Base and pallas::Scalar) are the hex of their canonical 32-byte
+//!   little-endian representation. Non-canonical encodings are rejected.
+//! - Points(delta commitments) are the hex of their 32-byte compressed encoding.
+//! - Quantities, heights and chain ids are decimal strings, since JSON numbers lose precision
+//!   above 2^53 in many decoders.
+//! - Proofs are the hex of the proof bytes, and verifying keys the hex of `VerifyingKey::write`.
+//! - VP bytecode inputs are hex bytes, vamp-ir sources and native circuit ids plain strings.
+//!
+//! Every JSON type converts losslessly to and from the native type. Decoding returns an
+//! `InvalidData` io error on malformed input.
+
+use crate::{
+    binding_signature::BindingSignature,
+    circuit::{
+        vp_bytecode::{
+            ApplicationByteCode, ValidityPredicateByteCode, ValidityPredicateRepresentation,
+        },
+        vp_circuit::{VPVerifyingInfo, ValidityPredicatePublicInputs},
+    },
+    compliance::{ComplianceInfo, CompliancePublicInputs},
+    constant::{SETUP_PARAMS_MAP, VP_CIRCUIT_PARAMS_SIZE, VP_CIRCUIT_PUBLIC_INPUT_NUM},
+    delta_commitment::{DeltaCommitment, PublicDelta},
+    merkle_tree::{Anchor, MerklePath, Node, LR},
+    nullifier::{Nullifier, NullifierKeyContainer},
+    proof::Proof,
+    resource::{RandomSeed, Resource, ResourceCommitment, ResourceKind},
+    shielded_ptx::{
+        ComplianceVerifyingInfo, ResourceVPVerifyingInfoSet, ShieldedPartialTransaction,
+    },
+    transaction::{
+        ShieldedPartialTxBundle, Transaction, TransactionResult, TransparentPartialTxBundle,
+    },
+    transparent_ptx::TransparentPartialTransaction,
+    vp_commitment::ValidityPredicateCommitment,
+};
+use ff::PrimeField;
+use halo2_proofs::plonk::VerifyingKey;
+use pasta_curves::{pallas, vesta};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::io;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonResource {
+    pub logic: String,
+    pub label: String,
+    pub value: String,
+    pub quantity: String,
+    pub nk_container: JsonNullifierKeyContainer,
+    pub nonce: String,
+    pub is_ephemeral: bool,
+    pub rseed: String,
+}
+
+/// Either `{"key": nk}` or `{"public_key": npk}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonNullifierKeyContainer {
+    Key(String),
+    PublicKey(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonCompliancePublicInputs {
+    pub anchor: String,
+    pub nf: String,
+    pub cm: String,
+    pub delta: String,
+    pub input_vp_commitment: String,
+    pub output_vp_commitment: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonComplianceVerifyingInfo {
+    pub proof: String,
+    pub public_inputs: JsonCompliancePublicInputs,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonVPVerifyingInfo {
+    pub vk: String,
+    pub proof: String,
+    /// The VP_CIRCUIT_PUBLIC_INPUT_NUM public inputs of the vp proof.
+    pub public_inputs: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonResourceVPVerifyingInfoSet {
+    pub app_vp: JsonVPVerifyingInfo,
+    pub dynamic_vps: Vec<JsonVPVerifyingInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonShieldedPartialTransaction {
+    pub compliances: Vec<JsonComplianceVerifyingInfo>,
+    pub inputs: Vec<JsonResourceVPVerifyingInfoSet>,
+    pub outputs: Vec<JsonResourceVPVerifyingInfoSet>,
+    pub binding_sig_r: Option<String>,
+    pub hints: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonPublicDelta {
+    pub logic: String,
+    pub label: String,
+    /// A signed decimal quantity.
+    pub quantity: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonMerklePathNode {
+    pub sibling: String,
+    /// Whether the sibling is the left child of its parent.
+    pub sibling_is_left: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonComplianceInfo {
+    pub input_resource: JsonResource,
+    pub input_merkle_path: Vec<JsonMerklePathNode>,
+    pub input_anchor: String,
+    pub output_resource: JsonResource,
+    pub rseed: String,
+}
+
+/// Either `{"vamp_ir": source}` or `{"native": circuit id}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonValidityPredicateRepresentation {
+    VampIr(String),
+    Native(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonValidityPredicateByteCode {
+    pub circuit: JsonValidityPredicateRepresentation,
+    pub inputs: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonApplicationByteCode {
+    pub app_vp: JsonValidityPredicateByteCode,
+    pub dynamic_vps: Vec<JsonValidityPredicateByteCode>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonTransparentPartialTransaction {
+    pub compliances: Vec<JsonComplianceInfo>,
+    pub inputs: Vec<JsonApplicationByteCode>,
+    pub outputs: Vec<JsonApplicationByteCode>,
+    pub hints: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonTransaction {
+    pub shielded_ptxs: Vec<JsonShieldedPartialTransaction>,
+    pub transparent_ptxs: Vec<JsonTransparentPartialTransaction>,
+    pub public_delta: Vec<JsonPublicDelta>,
+    pub chain_id: String,
+    pub expiry_height: Option<String>,
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonTransactionResult {
+    pub anchors: Vec<String>,
+    pub nullifiers: Vec<String>,
+    pub output_cms: Vec<String>,
+    pub public_delta: Vec<JsonPublicDelta>,
+}
+
+impl From<&Resource> for JsonResource {
+    fn from(resource: &Resource) -> Self {
+        Self {
+            logic: field_to_hex(&resource.kind.logic),
+            label: field_to_hex(&resource.kind.label),
+            value: field_to_hex(&resource.value),
+            quantity: resource.quantity.to_string(),
+            nk_container: match resource.nk_container {
+                NullifierKeyContainer::Key(nk) => JsonNullifierKeyContainer::Key(field_to_hex(&nk)),
+                NullifierKeyContainer::PublicKey(npk) => {
+                    JsonNullifierKeyContainer::PublicKey(field_to_hex(&npk))
+                }
+            },
+            nonce: field_to_hex(&resource.nonce.inner()),
+            is_ephemeral: resource.is_ephemeral,
+            rseed: field_to_hex(&resource.rseed),
+        }
+    }
+}
+
+impl TryFrom<JsonResource> for Resource {
+    type Error = io::Error;
+
+    fn try_from(json: JsonResource) -> io::Result<Self> {
+        Ok(Resource {
+            kind: ResourceKind::new(field_from_hex(&json.logic)?, field_from_hex(&json.label)?),
+            value: field_from_hex(&json.value)?,
+            quantity: parse_decimal(&json.quantity)?,
+            nk_container: match json.nk_container {
+                JsonNullifierKeyContainer::Key(nk) => {
+                    NullifierKeyContainer::Key(field_from_hex(&nk)?)
+                }
+                JsonNullifierKeyContainer::PublicKey(npk) => {
+                    NullifierKeyContainer::PublicKey(field_from_hex(&npk)?)
+                }
+            },
+            nonce: Nullifier::from(field_from_hex::<pallas::Base>(&json.nonce)?),
+            is_ephemeral: json.is_ephemeral,
+            rseed: field_from_hex(&json.rseed)?,
+        })
+    }
+}
+
+impl From<&CompliancePublicInputs> for JsonCompliancePublicInputs {
+    fn from(public_inputs: &CompliancePublicInputs) -> Self {
+        Self {
+            anchor: field_to_hex(&public_inputs.anchor.inner()),
+            nf: field_to_hex(&public_inputs.nf.inner()),
+            cm: field_to_hex(&public_inputs.cm.inner()),
+            delta: to_hex(&public_inputs.delta.to_bytes()),
+            input_vp_commitment: to_hex(&public_inputs.input_vp_commitment.to_bytes()),
+            output_vp_commitment: to_hex(&public_inputs.output_vp_commitment.to_bytes()),
+        }
+    }
+}
+
+impl TryFrom<JsonCompliancePublicInputs> for CompliancePublicInputs {
+    type Error = io::Error;
+
+    fn try_from(json: JsonCompliancePublicInputs) -> io::Result<Self> {
+        let delta = Option::<DeltaCommitment>::from(DeltaCommitment::from_bytes(from_hex_array(
+            &json.delta,
+        )?))
+        .ok_or_else(|| invalid(format!("{} is not a delta commitment", json.delta)))?;
+        Ok(CompliancePublicInputs {
+            anchor: Anchor::from(field_from_hex::<pallas::Base>(&json.anchor)?),
+            nf: Nullifier::from(field_from_hex::<pallas::Base>(&json.nf)?),
+            cm: ResourceCommitment::from(field_from_hex::<pallas::Base>(&json.cm)?),
+            delta,
+            input_vp_commitment: ValidityPredicateCommitment::from_bytes(from_hex_array(
+                &json.input_vp_commitment,
+            )?),
+            output_vp_commitment: ValidityPredicateCommitment::from_bytes(from_hex_array(
+                &json.output_vp_commitment,
+            )?),
+        })
+    }
+}
+
+impl From<&ComplianceVerifyingInfo> for JsonComplianceVerifyingInfo {
+    fn from(info: &ComplianceVerifyingInfo) -> Self {
+        Self {
+            proof: to_hex(&info.compliance_proof.inner()),
+            public_inputs: (&info.compliance_instance).into(),
+        }
+    }
+}
+
+impl TryFrom<JsonComplianceVerifyingInfo> for ComplianceVerifyingInfo {
+    type Error = io::Error;
+
+    fn try_from(json: JsonComplianceVerifyingInfo) -> io::Result<Self> {
+        Ok(ComplianceVerifyingInfo {
+            compliance_proof: Proof::new(from_hex(&json.proof)?),
+            compliance_instance: json.public_inputs.try_into()?,
+        })
+    }
+}
+
+impl From<&VPVerifyingInfo> for JsonVPVerifyingInfo {
+    fn from(info: &VPVerifyingInfo) -> Self {
+        let mut vk = vec![];
+        info.vk
+            .write(&mut vk)
+            .expect("writing a vk to memory should not fail");
+        Self {
+            vk: to_hex(&vk),
+            proof: to_hex(&info.proof.inner()),
+            public_inputs: info
+                .public_inputs
+                .inner()
+                .iter()
+                .map(field_to_hex)
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<JsonVPVerifyingInfo> for VPVerifyingInfo {
+    type Error = io::Error;
+
+    fn try_from(json: JsonVPVerifyingInfo) -> io::Result<Self> {
+        use crate::circuit::vp_examples::TrivialValidityPredicateCircuit;
+        let params = SETUP_PARAMS_MAP.get(&VP_CIRCUIT_PARAMS_SIZE).unwrap();
+        let vk = VerifyingKey::<vesta::Affine>::read::<_, TrivialValidityPredicateCircuit>(
+            &mut from_hex(&json.vk)?.as_slice(),
+            params,
+        )?;
+        if json.public_inputs.len() != VP_CIRCUIT_PUBLIC_INPUT_NUM {
+            return Err(invalid(format!(
+                "a vp proof has {VP_CIRCUIT_PUBLIC_INPUT_NUM} public inputs, found {}",
+                json.public_inputs.len()
+            )));
+        }
+        let public_inputs: Vec<pallas::Base> = json
+            .public_inputs
+            .iter()
+            .map(|x| field_from_hex(x))
+            .collect::<io::Result<_>>()?;
+        Ok(VPVerifyingInfo {
+            vk,
+            proof: Proof::new(from_hex(&json.proof)?),
+            public_inputs: ValidityPredicatePublicInputs::from(public_inputs),
+        })
+    }
+}
+
+impl From<&ResourceVPVerifyingInfoSet> for JsonResourceVPVerifyingInfoSet {
+    fn from(info: &ResourceVPVerifyingInfoSet) -> Self {
+        Self {
+            app_vp: (&info.app_vp_verifying_info).into(),
+            dynamic_vps: info
+                .app_dynamic_vp_verifying_info
+                .iter()
+                .map(Into::into)
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<JsonResourceVPVerifyingInfoSet> for ResourceVPVerifyingInfoSet {
+    type Error = io::Error;
+
+    fn try_from(json: JsonResourceVPVerifyingInfoSet) -> io::Result<Self> {
+        Ok(ResourceVPVerifyingInfoSet::new(
+            json.app_vp.try_into()?,
+            json.dynamic_vps
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<io::Result<_>>()?,
+        ))
+    }
+}
+
+impl From<&ShieldedPartialTransaction> for JsonShieldedPartialTransaction {
+    fn from(ptx: &ShieldedPartialTransaction) -> Self {
+        Self {
+            compliances: ptx.compliances.iter().map(Into::into).collect(),
+            inputs: ptx.inputs.iter().map(Into::into).collect(),
+            outputs: ptx.outputs.iter().map(Into::into).collect(),
+            binding_sig_r: ptx.binding_sig_r.as_ref().map(field_to_hex),
+            hints: to_hex(&ptx.hints),
+        }
+    }
+}
+
+impl TryFrom<JsonShieldedPartialTransaction> for ShieldedPartialTransaction {
+    type Error = io::Error;
+
+    fn try_from(json: JsonShieldedPartialTransaction) -> io::Result<Self> {
+        Ok(ShieldedPartialTransaction {
+            compliances: try_into_resource_array(json.compliances)?,
+            inputs: try_into_resource_array(json.inputs)?,
+            outputs: try_into_resource_array(json.outputs)?,
+            binding_sig_r: json.binding_sig_r.map(|r| field_from_hex(&r)).transpose()?,
+            hints: from_hex(&json.hints)?,
+        })
+    }
+}
+
+impl From<&ComplianceInfo> for JsonComplianceInfo {
+    fn from(compliance: &ComplianceInfo) -> Self {
+        Self {
+            input_resource: (&compliance.input_resource).into(),
+            input_merkle_path: compliance
+                .input_merkle_path
+                .get_path()
+                .iter()
+                .map(|(sibling, lr)| JsonMerklePathNode {
+                    sibling: field_to_hex(sibling),
+                    sibling_is_left: *lr == LR::L,
+                })
+                .collect(),
+            input_anchor: field_to_hex(&compliance.input_anchor.inner()),
+            output_resource: (&compliance.output_resource).into(),
+            rseed: to_hex(&compliance.rseed.to_bytes()),
+        }
+    }
+}
+
+impl TryFrom<JsonComplianceInfo> for ComplianceInfo {
+    type Error = io::Error;
+
+    fn try_from(json: JsonComplianceInfo) -> io::Result<Self> {
+        let merkle_path = json
+            .input_merkle_path
+            .iter()
+            .map(|node| {
+                let lr = if node.sibling_is_left { LR::L } else { LR::R };
+                Ok((
+                    Node::from(field_from_hex::<pallas::Base>(&node.sibling)?),
+                    lr,
+                ))
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(ComplianceInfo {
+            input_resource: json.input_resource.try_into()?,
+            input_merkle_path: MerklePath::from_path(merkle_path),
+            input_anchor: Anchor::from(field_from_hex::<pallas::Base>(&json.input_anchor)?),
+            output_resource: json.output_resource.try_into()?,
+            rseed: RandomSeed::from_bytes(from_hex_array(&json.rseed)?),
+        })
+    }
+}
+
+impl From<&ValidityPredicateByteCode> for JsonValidityPredicateByteCode {
+    fn from(bytecode: &ValidityPredicateByteCode) -> Self {
+        Self {
+            circuit: match &bytecode.circuit {
+                ValidityPredicateRepresentation::VampIR(source) => {
+                    JsonValidityPredicateRepresentation::VampIr(source.clone())
+                }
+                ValidityPredicateRepresentation::Native(id) => {
+                    JsonValidityPredicateRepresentation::Native(id.clone())
+                }
+            },
+            inputs: to_hex(&bytecode.inputs),
+        }
+    }
+}
+
+impl TryFrom<JsonValidityPredicateByteCode> for ValidityPredicateByteCode {
+    type Error = io::Error;
+
+    fn try_from(json: JsonValidityPredicateByteCode) -> io::Result<Self> {
+        let circuit = match json.circuit {
+            JsonValidityPredicateRepresentation::VampIr(source) => {
+                ValidityPredicateRepresentation::VampIR(source)
+            }
+            JsonValidityPredicateRepresentation::Native(id) => {
+                ValidityPredicateRepresentation::Native(id)
+            }
+        };
+        Ok(ValidityPredicateByteCode::new(
+            circuit,
+            from_hex(&json.inputs)?,
+        ))
+    }
+}
+
+impl From<&ApplicationByteCode> for JsonApplicationByteCode {
+    fn from(app: &ApplicationByteCode) -> Self {
+        Self {
+            app_vp: (&app.app_vp_bytecode).into(),
+            dynamic_vps: app.dynamic_vp_bytecode.iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TryFrom<JsonApplicationByteCode> for ApplicationByteCode {
+    type Error = io::Error;
+
+    fn try_from(json: JsonApplicationByteCode) -> io::Result<Self> {
+        Ok(ApplicationByteCode::new(
+            json.app_vp.try_into()?,
+            json.dynamic_vps
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<io::Result<_>>()?,
+        ))
+    }
+}
+
+impl From<&TransparentPartialTransaction> for JsonTransparentPartialTransaction {
+    fn from(ptx: &TransparentPartialTransaction) -> Self {
+        Self {
+            compliances: ptx.compliances.iter().map(Into::into).collect(),
+            inputs: ptx.input_resource_app.iter().map(Into::into).collect(),
+            outputs: ptx.output_resource_app.iter().map(Into::into).collect(),
+            hints: to_hex(&ptx.hints),
+        }
+    }
+}
+
+impl TryFrom<JsonTransparentPartialTransaction> for TransparentPartialTransaction {
+    type Error = io::Error;
+
+    fn try_from(json: JsonTransparentPartialTransaction) -> io::Result<Self> {
+        let items = |xs: Vec<JsonApplicationByteCode>| -> io::Result<Vec<ApplicationByteCode>> {
+            xs.into_iter().map(TryInto::try_into).collect()
+        };
+        TransparentPartialTransaction::new(
+            json.compliances
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<io::Result<_>>()?,
+            items(json.inputs)?,
+            items(json.outputs)?,
+            from_hex(&json.hints)?,
+        )
+        .map_err(|e| invalid(e.to_string()))
+    }
+}
+
+impl From<&PublicDelta> for JsonPublicDelta {
+    fn from(delta: &PublicDelta) -> Self {
+        Self {
+            logic: field_to_hex(&delta.kind.logic),
+            label: field_to_hex(&delta.kind.label),
+            quantity: delta.quantity.to_string(),
+        }
+    }
+}
+
+impl TryFrom<JsonPublicDelta> for PublicDelta {
+    type Error = io::Error;
+
+    fn try_from(json: JsonPublicDelta) -> io::Result<Self> {
+        Ok(PublicDelta::new(
+            ResourceKind::new(field_from_hex(&json.logic)?, field_from_hex(&json.label)?),
+            parse_decimal(&json.quantity)?,
+        ))
+    }
+}
+
+impl From<&Transaction> for JsonTransaction {
+    fn from(tx: &Transaction) -> Self {
+        Self {
+            shielded_ptxs: tx.shielded_ptx_bundle.0.iter().map(Into::into).collect(),
+            transparent_ptxs: tx.transparent_ptx_bundle.0.iter().map(Into::into).collect(),
+            public_delta: tx.public_delta.iter().map(Into::into).collect(),
+            chain_id: tx.chain_id.to_string(),
+            expiry_height: tx.expiry_height.map(|height| height.to_string()),
+            signature: to_hex(&tx.signature.to_bytes()),
+        }
+    }
+}
+
+impl TryFrom<JsonTransaction> for Transaction {
+    type Error = io::Error;
+
+    fn try_from(json: JsonTransaction) -> io::Result<Self> {
+        Ok(Transaction {
+            shielded_ptx_bundle: ShieldedPartialTxBundle::new(
+                json.shielded_ptxs
+                    .into_iter()
+                    .map(TryInto::try_into)
+                    .collect::<io::Result<_>>()?,
+            ),
+            transparent_ptx_bundle: TransparentPartialTxBundle::new(
+                json.transparent_ptxs
+                    .into_iter()
+                    .map(TryInto::try_into)
+                    .collect::<io::Result<_>>()?,
+            ),
+            public_delta: json
+                .public_delta
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<io::Result<_>>()?,
+            chain_id: parse_decimal(&json.chain_id)?,
+            expiry_height: json
+                .expiry_height
+                .map(|height| parse_decimal(&height))
+                .transpose()?,
+            signature: BindingSignature::from_bytes(from_hex_array(&json.signature)?),
+        })
+    }
+}
+
+impl From<&TransactionResult> for JsonTransactionResult {
+    fn from(result: &TransactionResult) -> Self {
+        Self {
+            anchors: result
+                .anchors
+                .iter()
+                .map(|anchor| field_to_hex(&anchor.inner()))
+                .collect(),
+            nullifiers: result
+                .nullifiers
+                .iter()
+                .map(|nf| field_to_hex(&nf.inner()))
+                .collect(),
+            output_cms: result
+                .output_cms
+                .iter()
+                .map(|cm| field_to_hex(&cm.inner()))
+                .collect(),
+            public_delta: result.public_delta.iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TryFrom<JsonTransactionResult> for TransactionResult {
+    type Error = io::Error;
+
+    fn try_from(json: JsonTransactionResult) -> io::Result<Self> {
+        let fields = |xs: Vec<String>| -> io::Result<Vec<pallas::Base>> {
+            xs.iter().map(|x| field_from_hex(x)).collect()
+        };
+        Ok(TransactionResult {
+            anchors: fields(json.anchors)?
+                .into_iter()
+                .map(Anchor::from)
+                .collect(),
+            nullifiers: fields(json.nullifiers)?
+                .into_iter()
+                .map(Nullifier::from)
+                .collect(),
+            output_cms: fields(json.output_cms)?
+                .into_iter()
+                .map(ResourceCommitment::from)
+                .collect(),
+            public_delta: json
+                .public_delta
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<io::Result<_>>()?,
+        })
+    }
+}
+
+pub fn resource_to_json(resource: &Resource) -> String {
+    to_json_string(&JsonResource::from(resource))
+}
+
+pub fn resource_from_json(json: &str) -> io::Result<Resource> {
+    serde_json::from_str::<JsonResource>(json)?.try_into()
+}
+
+pub fn partial_transaction_to_json(ptx: &ShieldedPartialTransaction) -> String {
+    to_json_string(&JsonShieldedPartialTransaction::from(ptx))
+}
+
+pub fn partial_transaction_from_json(json: &str) -> io::Result<ShieldedPartialTransaction> {
+    serde_json::from_str::<JsonShieldedPartialTransaction>(json)?.try_into()
+}
+
+pub fn transaction_to_json(tx: &Transaction) -> String {
+    to_json_string(&JsonTransaction::from(tx))
+}
+
+pub fn transaction_from_json(json: &str) -> io::Result<Transaction> {
+    serde_json::from_str::<JsonTransaction>(json)?.try_into()
+}
+
+pub fn transaction_result_to_json(result: &TransactionResult) -> String {
+    to_json_string(&JsonTransactionResult::from(result))
+}
+
+pub fn transaction_result_from_json(json: &str) -> io::Result<TransactionResult> {
+    serde_json::from_str::<JsonTransactionResult>(json)?.try_into()
+}
+
+fn to_json_string<T: Serialize>(value: &T) -> String {
+    serde_json::to_string_pretty(value).expect("the json types should serialize")
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(2 + 2 * bytes.len());
+    s.push_str("0x");
+    for b in bytes {
+        write!(s, "{:02x}", b).unwrap();
+    }
+    s
+}
+
+pub fn from_hex(s: &str) -> io::Result<Vec<u8>> {
+    let digits = s
+        .strip_prefix("0x")
+        .ok_or_else(|| invalid(format!("{s} is not 0x-prefixed hex")))?;
+    if digits.len() % 2 != 0 || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(invalid(format!("{s} is not 0x-prefixed hex")));
+    }
+    Ok((0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap())
+        .collect())
+}
+
+fn from_hex_array<const N: usize>(s: &str) -> io::Result<[u8; N]> {
+    from_hex(s)?
+        .try_into()
+        .map_err(|_| invalid(format!("{s} is not {N} bytes long")))
+}
+
+pub fn field_to_hex<F: PrimeField<Repr = [u8; 32]>>(x: &F) -> String {
+    to_hex(&x.to_repr())
+}
+
+pub fn field_from_hex<F: PrimeField<Repr = [u8; 32]>>(s: &str) -> io::Result<F> {
+    Option::<F>::from(F::from_repr(from_hex_array(s)?))
+        .ok_or_else(|| invalid(format!("{s} is not a canonical field element")))
+}
+
+fn parse_decimal<T: std::str::FromStr>(s: &str) -> io::Result<T> {
+    s.parse()
+        .map_err(|_| invalid(format!("{s} is not a valid decimal number")))
+}
+
+// The ptxs hold exactly NUM_RESOURCE compliances, inputs and outputs.
+fn try_into_resource_array<J, T, const N: usize>(json: Vec<J>) -> io::Result<[T; N]>
+where
+    T: TryFrom<J, Error = io::Error>,
+{
+    let len = json.len();
+    let items: Vec<T> = json
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<io::Result<_>>()?;
+    items
+        .try_into()
+        .map_err(|_| invalid(format!("expected {N} items per ptx, found {len}")))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::resource::tests::random_resource;
+    use crate::transaction::testing::create_shielded_ptx_bundle;
+    #[cfg(feature = "borsh")]
+    use crate::transparent_ptx::testing::create_transparent_ptx;
+    use halo2_proofs::arithmetic::Field;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn test_hex_field_encoding() {
+        assert_eq!(
+            field_to_hex(&pallas::Base::one()),
+            format!("0x01{}", "00".repeat(31))
+        );
+        let x = pallas::Base::random(OsRng);
+        assert_eq!(
+            field_from_hex::<pallas::Base>(&field_to_hex(&x)).unwrap(),
+            x
+        );
+        assert_eq!(
+            field_from_hex::<pallas::Base>(&field_to_hex(&x).to_uppercase().replace("0X", "0x"))
+                .unwrap(),
+            x
+        );
+
+        // Non-canonical and malformed encodings are rejected.
+        assert!(field_from_hex::<pallas::Base>(&format!("0x{}", "ff".repeat(32))).is_err());
+        assert!(field_from_hex::<pallas::Base>(&"00".repeat(32)).is_err());
+        assert!(field_from_hex::<pallas::Base>("0x+f").is_err());
+        assert!(field_from_hex::<pallas::Base>("0x01").is_err());
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let mut rng = OsRng;
+        let resource = random_resource(&mut rng);
+        assert_eq!(
+            resource_from_json(&resource_to_json(&resource)).unwrap(),
+            resource
+        );
+
+        #[cfg(feature = "borsh")]
+        let transparent_ptx_bundle =
+            TransparentPartialTxBundle::new(vec![create_transparent_ptx()]);
+        #[cfg(not(feature = "borsh"))]
+        let transparent_ptx_bundle = TransparentPartialTxBundle::default();
+
+        let tx = Transaction::build(
+            &mut rng,
+            create_shielded_ptx_bundle(1),
+            transparent_ptx_bundle,
+            vec![],
+            1,
+            Some(100),
+        )
+        .unwrap();
+        let json = transaction_to_json(&tx);
+        let de_tx = transaction_from_json(&json).unwrap();
+        assert_eq!(de_tx.id().unwrap(), tx.id().unwrap());
+        assert_eq!(de_tx.auth_digest(), tx.auth_digest());
+        assert_eq!(transaction_to_json(&de_tx), json);
+
+        let result = TransactionResult {
+            anchors: vec![Anchor::from(pallas::Base::random(&mut rng))],
+            nullifiers: vec![Nullifier::from(pallas::Base::random(&mut rng))],
+            output_cms: vec![ResourceCommitment::from(pallas::Base::random(&mut rng))],
+            public_delta: vec![PublicDelta::new(resource.kind, -5)],
+        };
+        assert_eq!(
+            transaction_result_from_json(&transaction_result_to_json(&result)).unwrap(),
+            result
+        );
+    }
+}
@@ -0,0 +1,314 @@
+use crate::{
+    commitment_tree::{CommitmentTree, CommitmentTreeError},
+    constant::{ROOT_HISTORY_SIZE, TAIGA_COMMITMENT_TREE_DEPTH},
+    error::TransactionError,
+    merkle_tree::Anchor,
+    nullifier::Nullifier,
+    transaction::TransactionResult,
+};
+use std::collections::{HashSet, VecDeque};
+
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+
+/// The ledger state a transaction is executed against: the known commitment tree roots,
+/// the spent nullifiers and the resource commitment tree.
+pub trait LedgerState {
+    /// Returns true if `anchor` is a root of the commitment tree that transactions may refer to.
+    fn is_known_anchor(&self, anchor: &Anchor) -> bool;
+
+    /// Returns true if `nf` has already been revealed by an applied transaction.
+    fn is_nullifier_spent(&self, nf: &Nullifier) -> bool;
+
+    /// Marks the nullifiers as spent and appends the output commitments to the commitment tree.
+    /// It must either apply the whole result or leave the state untouched.
+    fn apply(&mut self, result: &TransactionResult) -> Result<(), TransactionError>;
+
+    /// Checks that the result only spends unspent nullifiers and only refers to known anchors.
+    fn validate(&self, result: &TransactionResult) -> Result<(), TransactionError> {
+        if let Some(anchor) = result
+            .anchors
+            .iter()
+            .find(|anchor| !self.is_known_anchor(anchor))
+        {
+            return Err(TransactionError::UnknownAnchor(*anchor));
+        }
+
+        if let Some(nf) = result
+            .nullifiers
+            .iter()
+            .find(|nf| self.is_nullifier_spent(nf))
+        {
+            return Err(TransactionError::SpentNullifier(*nf));
+        }
+
+        Ok(())
+    }
+}
+
+/// The last `capacity` roots of the commitment tree. Only these roots are accepted as anchors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+pub struct RootHistory {
+    capacity: usize,
+    roots: VecDeque<Anchor>,
+}
+
+impl RootHistory {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0);
+        Self {
+            capacity,
+            roots: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Records a new root, evicting the oldest one if the window is full.
+    pub fn push(&mut self, root: Anchor) {
+        if self.roots.back() == Some(&root) {
+            return;
+        }
+        if self.roots.len() == self.capacity {
+            self.roots.pop_front();
+        }
+        self.roots.push_back(root);
+    }
+
+    pub fn contains(&self, anchor: &Anchor) -> bool {
+        self.roots.contains(anchor)
+    }
+
+    pub fn latest(&self) -> Option<Anchor> {
+        self.roots.back().cloned()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the roots in the window, oldest first.
+    pub fn roots(&self) -> Vec<Anchor> {
+        self.roots.iter().cloned().collect()
+    }
+}
+
+impl Default for RootHistory {
+    fn default() -> Self {
+        Self::new(ROOT_HISTORY_SIZE)
+    }
+}
+
+/// A LedgerState kept in memory. The last `ROOT_HISTORY_SIZE` roots (by default) are known anchors.
+#[derive(Debug, Clone)]
+pub struct InMemoryLedgerState {
+    tree: CommitmentTree,
+    root_history: RootHistory,
+    nullifiers: HashSet<Nullifier>,
+}
+
+impl InMemoryLedgerState {
+    pub fn new() -> Self {
+        Self::with_root_history_size(ROOT_HISTORY_SIZE)
+    }
+
+    pub fn with_root_history_size(size: usize) -> Self {
+        let tree = CommitmentTree::new();
+        let mut root_history = RootHistory::new(size);
+        root_history.push(tree.root());
+        Self {
+            tree,
+            root_history,
+            nullifiers: HashSet::new(),
+        }
+    }
+
+    pub fn from_parts(
+        tree: CommitmentTree,
+        root_history: RootHistory,
+        nullifiers: HashSet<Nullifier>,
+    ) -> Self {
+        Self {
+            tree,
+            root_history,
+            nullifiers,
+        }
+    }
+
+    pub fn get_root_history(&self) -> &RootHistory {
+        &self.root_history
+    }
+
+    pub fn get_nullifiers(&self) -> &HashSet<Nullifier> {
+        &self.nullifiers
+    }
+
+    pub fn get_tree(&self) -> &CommitmentTree {
+        &self.tree
+    }
+
+    pub fn get_tree_mut(&mut self) -> &mut CommitmentTree {
+        &mut self.tree
+    }
+
+    /// Returns the current root of the commitment tree.
+    pub fn root(&self) -> Anchor {
+        self.tree.root()
+    }
+
+    /// Checks that the output commitments of the result fit in the commitment tree.
+    pub fn check_capacity(&self, result: &TransactionResult) -> Result<(), TransactionError> {
+        if self.tree.size() + result.output_cms.len() as u64 > 1u64 << TAIGA_COMMITMENT_TREE_DEPTH {
+            return Err(CommitmentTreeError::TreeFull.into());
+        }
+        Ok(())
+    }
+}
+
+impl Default for InMemoryLedgerState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LedgerState for InMemoryLedgerState {
+    fn is_known_anchor(&self, anchor: &Anchor) -> bool {
+        self.root_history.contains(anchor)
+    }
+
+    fn is_nullifier_spent(&self, nf: &Nullifier) -> bool {
+        self.nullifiers.contains(nf)
+    }
+
+    fn apply(&mut self, result: &TransactionResult) -> Result<(), TransactionError> {
+        // Check the capacity first so that a full tree leaves the state untouched.
+        self.check_capacity(result)?;
+        self.tree.append_result(result)?;
+        self.root_history.push(self.tree.root());
+        self.nullifiers.extend(result.nullifiers.iter().cloned());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{InMemoryLedgerState, LedgerState, RootHistory};
+    use crate::commitment_tree::tests::random_commitment;
+    use crate::error::TransactionError;
+    use crate::merkle_tree::{Anchor, MerklePath};
+    use crate::nullifier::tests::random_nullifier;
+    use crate::resource::{tests::random_resource, Resource};
+    use crate::transaction::TransactionResult;
+    use halo2_proofs::arithmetic::Field;
+    use pasta_curves::pallas;
+    use rand::rngs::OsRng;
+
+    /// Commits the resources to the state as the outputs of a previous transaction, and returns
+    /// their merkle paths to the latest root.
+    pub fn commit_resources(
+        state: &mut InMemoryLedgerState,
+        resources: &[Resource],
+    ) -> Vec<MerklePath> {
+        let positions: Vec<_> = resources
+            .iter()
+            .map(|resource| {
+                let result = TransactionResult {
+                    anchors: vec![],
+                    nullifiers: vec![],
+                    output_cms: vec![resource.commitment()],
+                    public_delta: vec![],
+                };
+                state.apply(&result).unwrap();
+                state.get_tree_mut().mark().unwrap()
+            })
+            .collect();
+        positions
+            .into_iter()
+            .map(|position| state.get_tree().witness(position).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_in_memory_ledger_state() {
+        let mut rng = OsRng;
+        let mut state = InMemoryLedgerState::new();
+        let genesis_root = state.root();
+
+        let nf = random_nullifier(&mut rng);
+        let result = TransactionResult {
+            anchors: vec![genesis_root],
+            nullifiers: vec![nf],
+            output_cms: vec![random_commitment(&mut rng), random_commitment(&mut rng)],
+            public_delta: vec![],
+        };
+        state.validate(&result).unwrap();
+        state.apply(&result).unwrap();
+        assert_eq!(state.get_tree().size(), 2);
+        assert!(state.is_known_anchor(&state.root()));
+        assert!(state.is_nullifier_spent(&nf));
+
+        // Double spend
+        assert!(matches!(
+            state.validate(&result),
+            Err(TransactionError::SpentNullifier(spent)) if spent == nf
+        ));
+
+        // Unknown anchor
+        let unknown_anchor = Anchor::from(pallas::Base::random(&mut rng));
+        let result = TransactionResult {
+            anchors: vec![unknown_anchor],
+            nullifiers: vec![random_nullifier(&mut rng)],
+            output_cms: vec![],
+            public_delta: vec![],
+        };
+        assert!(matches!(
+            state.validate(&result),
+            Err(TransactionError::UnknownAnchor(anchor)) if anchor == unknown_anchor
+        ));
+    }
+
+    #[test]
+    fn test_root_history_window() {
+        let mut rng = OsRng;
+        let mut history = RootHistory::new(2);
+        let roots: Vec<Anchor> = (0..3)
+            .map(|_| Anchor::from(pallas::Base::random(&mut rng)))
+            .collect();
+        history.push(roots[0]);
+        history.push(roots[1]);
+        history.push(roots[1]);
+        assert!(history.contains(&roots[0]));
+        history.push(roots[2]);
+        assert!(!history.contains(&roots[0]));
+        assert_eq!(history.roots(), vec![roots[1], roots[2]]);
+        assert_eq!(history.latest(), Some(roots[2]));
+
+        // An anchor that falls out of the window is rejected.
+        let mut state = InMemoryLedgerState::with_root_history_size(1);
+        let genesis_root = state.root();
+        let result = TransactionResult {
+            anchors: vec![genesis_root],
+            nullifiers: vec![random_nullifier(&mut rng)],
+            output_cms: vec![random_commitment(&mut rng)],
+            public_delta: vec![],
+        };
+        state.validate(&result).unwrap();
+        state.apply(&result).unwrap();
+        assert!(matches!(
+            state.validate(&result),
+            Err(TransactionError::UnknownAnchor(anchor)) if anchor == genesis_root
+        ));
+    }
+
+    #[test]
+    fn test_commit_resources() {
+        let mut rng = OsRng;
+        let mut state = InMemoryLedgerState::new();
+        let resources = [random_resource(&mut rng), random_resource(&mut rng)];
+        let merkle_paths = commit_resources(&mut state, &resources);
+        for (resource, merkle_path) in resources.iter().zip(merkle_paths.iter()) {
+            let anchor = resource.calculate_root(merkle_path);
+            assert_eq!(anchor, state.root());
+            assert!(state.is_known_anchor(&anchor));
+        }
+    }
+}
@@ -0,0 +1,640 @@
+//! Persistent ledger state.
+//!
+//! A `PersistentLedgerState` keeps an `InMemoryLedgerState` and records every applied
+//! `TransactionResult` in a `LedgerStorage` before applying it. The storage holds the latest
+//! snapshot of the state and a log of the results applied after it, so reopening only replays
+//! the log tail instead of recomputing the commitment tree.
+
+use crate::{
+    commitment_tree::CommitmentTree,
+    constant::{LEDGER_LOG_CHECKSUM_PERSONALIZATION, LEDGER_SNAPSHOT_INTERVAL, ROOT_HISTORY_SIZE},
+    error::TransactionError,
+    ledger::{InMemoryLedgerState, LedgerState, RootHistory},
+    merkle_tree::Anchor,
+    nullifier::Nullifier,
+    transaction::TransactionResult,
+};
+use blake2b_simd::Params as Blake2bParams;
+use borsh::{BorshDeserialize, BorshSerialize};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const SNAPSHOT_FILE: &str = "snapshot";
+const SNAPSHOT_TMP_FILE: &str = "snapshot.tmp";
+const LOG_FILE: &str = "results.log";
+const CHECKSUM_SIZE: usize = 16;
+// The layout of the records, bumped whenever it or the borsh layout of TransactionResult changes.
+const LOG_RECORD_VERSION: u8 = 2;
+// version(u8) || seq(u64) || payload length(u32) || payload checksum || header checksum
+// The header checksum covers the fields before it, so a header that checks out can be trusted
+// to delimit the record.
+const RECORD_HEADER_SIZE: usize = 1 + 8 + 4 + CHECKSUM_SIZE + CHECKSUM_SIZE;
+
+/// The state of an `InMemoryLedgerState` after `applied` transaction results.
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct LedgerSnapshot {
+    pub applied: u64,
+    pub tree: CommitmentTree,
+    pub root_history: RootHistory,
+    pub nullifiers: Vec<Nullifier>,
+}
+
+impl LedgerSnapshot {
+    pub fn new(applied: u64, state: &InMemoryLedgerState) -> Self {
+        Self {
+            applied,
+            tree: state.get_tree().clone(),
+            root_history: state.get_root_history().clone(),
+            nullifiers: state.get_nullifiers().iter().cloned().collect(),
+        }
+    }
+
+    pub fn into_state(self) -> InMemoryLedgerState {
+        InMemoryLedgerState::from_parts(
+            self.tree,
+            self.root_history,
+            self.nullifiers.into_iter().collect(),
+        )
+    }
+}
+
+/// The storage backend of a `PersistentLedgerState`.
+pub trait LedgerStorage {
+    /// Returns the latest snapshot and the logged results with their sequence numbers.
+    /// Results already covered by the snapshot may be returned and are skipped on replay.
+    fn load(&mut self) -> io::Result<(Option<LedgerSnapshot>, Vec<(u64, TransactionResult)>)>;
+
+    /// Durably logs the `seq`-th result. The result must survive a crash once this returns.
+    fn append(&mut self, seq: u64, result: &TransactionResult) -> io::Result<()>;
+
+    /// Durably replaces the snapshot. The logged results covered by it can be discarded.
+    fn write_snapshot(&mut self, snapshot: &LedgerSnapshot) -> io::Result<()>;
+}
+
+/// A LedgerState that persists every applied result to a `LedgerStorage`.
+#[derive(Debug)]
+pub struct PersistentLedgerState<S: LedgerStorage> {
+    state: InMemoryLedgerState,
+    storage: S,
+    // The number of results applied since genesis.
+    applied: u64,
+    // The number of results logged since the last snapshot.
+    unsnapshotted: u64,
+    snapshot_interval: u64,
+    // The error of the last failed periodic snapshot, cleared by the next successful one.
+    snapshot_error: Option<TransactionError>,
+}
+
+impl<S: LedgerStorage> PersistentLedgerState<S> {
+    /// Restores the state from the storage, starting from genesis if the storage is empty.
+    pub fn open(storage: S) -> Result<Self, TransactionError> {
+        Self::open_with_root_history_size(storage, ROOT_HISTORY_SIZE)
+    }
+
+    /// Like `open`, with the root history size used when the storage is empty.
+    pub fn open_with_root_history_size(
+        mut storage: S,
+        root_history_size: usize,
+    ) -> Result<Self, TransactionError> {
+        let (snapshot, results) = storage.load()?;
+        let (mut state, mut applied) = match snapshot {
+            Some(snapshot) => {
+                let applied = snapshot.applied;
+                (snapshot.into_state(), applied)
+            }
+            None => (
+                InMemoryLedgerState::with_root_history_size(root_history_size),
+                0,
+            ),
+        };
+
+        let mut unsnapshotted = 0;
+        for (seq, result) in results {
+            if seq < applied {
+                continue;
+            }
+            if seq != applied {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("missing ledger log record {applied}, found {seq}"),
+                )
+                .into());
+            }
+            state.apply(&result)?;
+            applied += 1;
+            unsnapshotted += 1;
+        }
+
+        Ok(Self {
+            state,
+            storage,
+            applied,
+            unsnapshotted,
+            snapshot_interval: LEDGER_SNAPSHOT_INTERVAL,
+            snapshot_error: None,
+        })
+    }
+
+    /// Sets the number of results logged between two snapshots.
+    pub fn set_snapshot_interval(&mut self, interval: u64) {
+        assert!(interval > 0);
+        self.snapshot_interval = interval;
+    }
+
+    /// Writes a snapshot of the current state, so that reopening replays no result.
+    pub fn snapshot(&mut self) -> Result<(), TransactionError> {
+        self.storage
+            .write_snapshot(&LedgerSnapshot::new(self.applied, &self.state))?;
+        self.unsnapshotted = 0;
+        self.snapshot_error = None;
+        Ok(())
+    }
+
+    /// Returns the error of the last periodic snapshot if it failed. The results are still in the
+    /// log, and the snapshot is retried on the next apply.
+    pub fn snapshot_error(&self) -> Option<&TransactionError> {
+        self.snapshot_error.as_ref()
+    }
+
+    /// Returns the number of results applied since genesis.
+    pub fn applied(&self) -> u64 {
+        self.applied
+    }
+
+    pub fn get_state(&self) -> &InMemoryLedgerState {
+        &self.state
+    }
+
+    pub fn get_storage(&self) -> &S {
+        &self.storage
+    }
+
+    /// Returns the current root of the commitment tree.
+    pub fn root(&self) -> Anchor {
+        self.state.root()
+    }
+}
+
+impl<S: LedgerStorage> LedgerState for PersistentLedgerState<S> {
+    fn is_known_anchor(&self, anchor: &Anchor) -> bool {
+        self.state.is_known_anchor(anchor)
+    }
+
+    fn is_nullifier_spent(&self, nf: &Nullifier) -> bool {
+        self.state.is_nullifier_spent(nf)
+    }
+
+    fn apply(&mut self, result: &TransactionResult) -> Result<(), TransactionError> {
+        // The in-memory apply can only fail on a full tree, so check it before logging the result.
+        self.state.check_capacity(result)?;
+        self.storage.append(self.applied, result)?;
+        self.state.apply(result)?;
+        self.applied += 1;
+        self.unsnapshotted += 1;
+        // The result is logged and applied at this point, so a failed snapshot must not fail the
+        // apply, or the caller would retry an applied result.
+        if self.unsnapshotted >= self.snapshot_interval {
+            if let Err(e) = self.snapshot() {
+                self.snapshot_error = Some(e);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A LedgerStorage in a directory: a snapshot file replaced atomically and an append-only log
+/// of versioned, checksummed results. A torn record at the end of the log is discarded on load,
+/// any other bad record fails the load.
+#[derive(Debug)]
+pub struct FileLedgerStorage {
+    dir: PathBuf,
+    log: File,
+    // Set when a failed append could not be rolled back. The partial record is at the end of the
+    // log, so appends are refused until it is discarded by the next load.
+    poisoned: bool,
+    // Makes the next append fail after writing this many bytes of its record.
+    #[cfg(test)]
+    fail_append_after: Option<usize>,
+}
+
+// Why a record of the log can't be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecordError {
+    // The record is cut off by the end of the log, as left by a crash while appending it.
+    Torn,
+    Corrupted,
+}
+
+impl FileLedgerStorage {
+    /// Opens the storage in `dir`, creating the directory if needed.
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let log = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(dir.join(LOG_FILE))?;
+        Ok(Self {
+            dir,
+            log,
+            poisoned: false,
+            #[cfg(test)]
+            fail_append_after: None,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn checksum(bytes: &[u8]) -> [u8; CHECKSUM_SIZE] {
+        Blake2bParams::new()
+            .hash_length(CHECKSUM_SIZE)
+            .personal(LEDGER_LOG_CHECKSUM_PERSONALIZATION)
+            .hash(bytes)
+            .as_bytes()
+            .try_into()
+            .unwrap()
+    }
+
+    fn read_snapshot(&self) -> io::Result<Option<LedgerSnapshot>> {
+        match fs::read(self.dir.join(SNAPSHOT_FILE)) {
+            Ok(bytes) => Ok(Some(BorshDeserialize::deserialize(&mut bytes.as_ref())?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Reads the records of the log. A torn last record was left by a crash while appending it,
+    // so the log is truncated before it. Any other bad record is corruption: the records after
+    // it may be valid, so the log is left untouched.
+    fn read_log(&mut self) -> io::Result<Vec<(u64, TransactionResult)>> {
+        let mut bytes = vec![];
+        self.log.seek(SeekFrom::Start(0))?;
+        self.log.read_to_end(&mut bytes)?;
+
+        let mut results = vec![];
+        let mut valid_len = 0;
+        while valid_len < bytes.len() {
+            let rest = &bytes[valid_len..];
+            match Self::read_record(rest) {
+                Ok((seq, result, len)) => {
+                    results.push((seq, result));
+                    valid_len += len;
+                }
+                Err(RecordError::Torn) => break,
+                Err(RecordError::Corrupted) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("corrupted ledger log record at offset {valid_len}"),
+                    ))
+                }
+            }
+        }
+
+        if valid_len < bytes.len() {
+            self.log.set_len(valid_len as u64)?;
+            self.log.sync_data()?;
+        }
+        self.poisoned = false;
+        Ok(results)
+    }
+
+    // Returns the sequence number and the result of the record at the start of `bytes`, and the
+    // length of the record.
+    fn read_record(bytes: &[u8]) -> Result<(u64, TransactionResult, usize), RecordError> {
+        // A partial header can only be the end of the log.
+        if bytes.len() < RECORD_HEADER_SIZE {
+            return Err(RecordError::Torn);
+        }
+        let (header, rest) = bytes.split_at(RECORD_HEADER_SIZE);
+        let (fields, header_checksum) = header.split_at(RECORD_HEADER_SIZE - CHECKSUM_SIZE);
+        if header_checksum != Self::checksum(fields) {
+            return Err(RecordError::Corrupted);
+        }
+
+        let mut fields = fields;
+        let version = fields.read_u8().unwrap();
+        let seq = fields.read_u64::<LittleEndian>().unwrap();
+        let payload_len = fields.read_u32::<LittleEndian>().unwrap() as usize;
+        let payload_checksum = fields;
+        if version != LOG_RECORD_VERSION {
+            return Err(RecordError::Corrupted);
+        }
+        if rest.len() < payload_len {
+            return Err(RecordError::Torn);
+        }
+
+        // A bad payload that ends the log was not synced before a crash, unlike its header.
+        let payload = &rest[..payload_len];
+        let bad_payload = if payload_len == rest.len() {
+            RecordError::Torn
+        } else {
+            RecordError::Corrupted
+        };
+        if payload_checksum != Self::checksum(payload) {
+            return Err(bad_payload);
+        }
+        let result = TransactionResult::try_from_slice(payload).map_err(|_| bad_payload)?;
+        Ok((seq, result, RECORD_HEADER_SIZE + payload_len))
+    }
+
+    fn write_record(&mut self, record: &[u8]) -> io::Result<()> {
+        #[cfg(test)]
+        if let Some(len) = self.fail_append_after.take() {
+            self.log.write_all(&record[..len])?;
+            return Err(io::Error::other("injected append failure"));
+        }
+        self.log.write_all(record)?;
+        self.log.sync_data()
+    }
+
+    fn sync_dir(&self) -> io::Result<()> {
+        // Persist the directory entry of the renamed snapshot. Directories can't be opened on windows.
+        #[cfg(unix)]
+        File::open(&self.dir)?.sync_all()?;
+        Ok(())
+    }
+}
+
+impl LedgerStorage for FileLedgerStorage {
+    fn load(&mut self) -> io::Result<(Option<LedgerSnapshot>, Vec<(u64, TransactionResult)>)> {
+        let snapshot = self.read_snapshot()?;
+        let results = self.read_log()?;
+        Ok((snapshot, results))
+    }
+
+    fn append(&mut self, seq: u64, result: &TransactionResult) -> io::Result<()> {
+        if self.poisoned {
+            return Err(io::Error::other(
+                "the ledger log ends with a partial record, reload the storage",
+            ));
+        }
+
+        let payload = borsh::to_vec(result)?;
+        let payload_len = u32::try_from(payload.len()).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "ledger log record too large")
+        })?;
+        let mut record = Vec::with_capacity(RECORD_HEADER_SIZE + payload.len());
+        record.write_u8(LOG_RECORD_VERSION)?;
+        record.write_u64::<LittleEndian>(seq)?;
+        record.write_u32::<LittleEndian>(payload_len)?;
+        record.write_all(&Self::checksum(&payload))?;
+        let header_checksum = Self::checksum(&record);
+        record.write_all(&header_checksum)?;
+        record.write_all(&payload)?;
+
+        // Roll back a failed append, or the next one would follow a partial record and the log
+        // could not be loaded anymore.
+        let len = self.log.seek(SeekFrom::End(0))?;
+        if let Err(e) = self.write_record(&record) {
+            if self
+                .log
+                .set_len(len)
+                .and_then(|_| self.log.sync_data())
+                .is_err()
+            {
+                self.poisoned = true;
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn write_snapshot(&mut self, snapshot: &LedgerSnapshot) -> io::Result<()> {
+        // Write the new snapshot next to the old one and rename it, so that a crash leaves
+        // either the old or the new snapshot in place.
+        let tmp_path = self.dir.join(SNAPSHOT_TMP_FILE);
+        let mut tmp = File::create(&tmp_path)?;
+        tmp.write_all(&borsh::to_vec(snapshot)?)?;
+        tmp.sync_all()?;
+        fs::rename(&tmp_path, self.dir.join(SNAPSHOT_FILE))?;
+        self.sync_dir()?;
+
+        // The snapshot covers every logged result. If we crash before the log is truncated,
+        // the stale records are skipped on replay by their sequence numbers.
+        self.log.set_len(0)?;
+        self.log.sync_data()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{FileLedgerStorage, LedgerSnapshot, LedgerStorage, PersistentLedgerState};
+    use crate::commitment_tree::tests::random_commitment;
+    use crate::error::TransactionError;
+    use crate::ledger::LedgerState;
+    use crate::merkle_tree::Anchor;
+    use crate::nullifier::tests::random_nullifier;
+    use crate::transaction::TransactionResult;
+    use rand::rngs::OsRng;
+    use rand::RngCore;
+    use std::fs::OpenOptions;
+    use std::io::{self, Write};
+    use std::path::PathBuf;
+
+    // A storage whose snapshots fail until `fail_snapshots` is unset.
+    #[derive(Debug, Default)]
+    struct FlakySnapshotStorage {
+        results: Vec<(u64, TransactionResult)>,
+        snapshot: Option<LedgerSnapshot>,
+        fail_snapshots: bool,
+    }
+
+    impl LedgerStorage for FlakySnapshotStorage {
+        fn load(&mut self) -> io::Result<(Option<LedgerSnapshot>, Vec<(u64, TransactionResult)>)> {
+            Ok((self.snapshot.clone(), self.results.clone()))
+        }
+
+        fn append(&mut self, seq: u64, result: &TransactionResult) -> io::Result<()> {
+            self.results.push((seq, result.clone()));
+            Ok(())
+        }
+
+        fn write_snapshot(&mut self, snapshot: &LedgerSnapshot) -> io::Result<()> {
+            if self.fail_snapshots {
+                return Err(io::Error::other("disk full"));
+            }
+            self.snapshot = Some(snapshot.clone());
+            self.results.clear();
+            Ok(())
+        }
+    }
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("taiga_ledger_{}", OsRng.next_u64()))
+    }
+
+    fn random_result<R: RngCore>(rng: &mut R, anchor: Anchor) -> TransactionResult {
+        TransactionResult {
+            anchors: vec![anchor],
+            nullifiers: vec![random_nullifier(&mut *rng)],
+            output_cms: vec![random_commitment(&mut *rng), random_commitment(&mut *rng)],
+            public_delta: vec![],
+        }
+    }
+
+    #[test]
+    fn test_persistent_ledger_state_reopen() {
+        let mut rng = OsRng;
+        let dir = temp_dir();
+
+        let mut state =
+            PersistentLedgerState::open(FileLedgerStorage::open(&dir).unwrap()).unwrap();
+        state.set_snapshot_interval(2);
+        let mut results = vec![];
+        for _ in 0..3 {
+            let result = random_result(&mut rng, state.root());
+            state.validate(&result).unwrap();
+            state.apply(&result).unwrap();
+            results.push(result);
+        }
+        let root = state.root();
+        drop(state);
+
+        // One result is replayed on top of the snapshot.
+        let state = PersistentLedgerState::open(FileLedgerStorage::open(&dir).unwrap()).unwrap();
+        assert_eq!(state.applied(), 3);
+        assert_eq!(state.root(), root);
+        assert_eq!(state.get_state().get_tree().size(), 6);
+        for result in results.iter() {
+            assert!(state.is_nullifier_spent(&result.nullifiers[0]));
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_persistent_ledger_state_torn_log() {
+        let mut rng = OsRng;
+        let dir = temp_dir();
+
+        let mut state =
+            PersistentLedgerState::open(FileLedgerStorage::open(&dir).unwrap()).unwrap();
+        let result = random_result(&mut rng, state.root());
+        state.apply(&result).unwrap();
+        let root = state.root();
+        drop(state);
+
+        // Simulate a crash in the middle of appending a record.
+        let mut log = OpenOptions::new()
+            .append(true)
+            .open(dir.join(super::LOG_FILE))
+            .unwrap();
+        log.write_all(&[1, 0, 0, 0, 0, 0, 0, 0, 42]).unwrap();
+        drop(log);
+
+        let mut state =
+            PersistentLedgerState::open(FileLedgerStorage::open(&dir).unwrap()).unwrap();
+        assert_eq!(state.applied(), 1);
+        assert_eq!(state.root(), root);
+
+        // The log is usable after the torn record was discarded.
+        let result = random_result(&mut rng, state.root());
+        state.apply(&result).unwrap();
+        let root = state.root();
+        drop(state);
+        let state = PersistentLedgerState::open(FileLedgerStorage::open(&dir).unwrap()).unwrap();
+        assert_eq!(state.applied(), 2);
+        assert_eq!(state.root(), root);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_persistent_ledger_state_corrupted_log() {
+        let mut rng = OsRng;
+        let dir = temp_dir();
+
+        let mut state =
+            PersistentLedgerState::open(FileLedgerStorage::open(&dir).unwrap()).unwrap();
+        for _ in 0..2 {
+            let result = random_result(&mut rng, state.root());
+            state.apply(&result).unwrap();
+        }
+        drop(state);
+
+        // Corrupt the payload, then the payload length, of the first record, which is followed
+        // by a valid record.
+        let log_path = dir.join(super::LOG_FILE);
+        let bytes = std::fs::read(&log_path).unwrap();
+        for offset in [super::RECORD_HEADER_SIZE, 1 + 8] {
+            let mut corrupted_bytes = bytes.clone();
+            corrupted_bytes[offset] ^= 1;
+            std::fs::write(&log_path, &corrupted_bytes).unwrap();
+
+            assert!(matches!(
+                PersistentLedgerState::open(FileLedgerStorage::open(&dir).unwrap()),
+                Err(TransactionError::IoError(e)) if e.kind() == io::ErrorKind::InvalidData
+            ));
+            // The valid records after the corrupted one are kept.
+            assert_eq!(std::fs::read(&log_path).unwrap(), corrupted_bytes);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_persistent_ledger_state_failed_append() {
+        let mut rng = OsRng;
+        let dir = temp_dir();
+        let log_path = dir.join(super::LOG_FILE);
+
+        let mut state =
+            PersistentLedgerState::open(FileLedgerStorage::open(&dir).unwrap()).unwrap();
+        let result = random_result(&mut rng, state.root());
+        state.apply(&result).unwrap();
+        let log_len = std::fs::metadata(&log_path).unwrap().len();
+
+        // The partial record of a failed append is rolled back and the result is not applied.
+        state.storage.fail_append_after = Some(super::RECORD_HEADER_SIZE + 1);
+        let failed_result = random_result(&mut rng, state.root());
+        assert!(state.apply(&failed_result).is_err());
+        assert_eq!(state.applied(), 1);
+        assert!(!state.is_nullifier_spent(&failed_result.nullifiers[0]));
+        assert_eq!(std::fs::metadata(&log_path).unwrap().len(), log_len);
+
+        // The next append follows the last complete record, so the log can be reopened.
+        let result = random_result(&mut rng, state.root());
+        state.apply(&result).unwrap();
+        let root = state.root();
+        drop(state);
+        let state = PersistentLedgerState::open(FileLedgerStorage::open(&dir).unwrap()).unwrap();
+        assert_eq!(state.applied(), 2);
+        assert_eq!(state.root(), root);
+        assert!(!state.is_nullifier_spent(&failed_result.nullifiers[0]));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_persistent_ledger_state_failed_snapshot() {
+        let mut rng = OsRng;
+        let storage = FlakySnapshotStorage {
+            fail_snapshots: true,
+            ..Default::default()
+        };
+        let mut state = PersistentLedgerState::open(storage).unwrap();
+        state.set_snapshot_interval(1);
+
+        // The result is applied even though the snapshot fails.
+        let result = random_result(&mut rng, state.root());
+        state.apply(&result).unwrap();
+        assert_eq!(state.applied(), 1);
+        assert!(state.is_nullifier_spent(&result.nullifiers[0]));
+        assert!(state.snapshot_error().is_some());
+        assert!(state.get_storage().snapshot.is_none());
+
+        // The snapshot is retried on the next apply.
+        state.storage.fail_snapshots = false;
+        let result = random_result(&mut rng, state.root());
+        state.apply(&result).unwrap();
+        assert!(state.snapshot_error().is_none());
+        assert_eq!(state.get_storage().snapshot.as_ref().unwrap().applied, 2);
+        assert!(state.get_storage().results.is_empty());
+    }
+}
@@ -3,16 +3,24 @@
 
 pub mod binding_signature;
 pub mod circuit;
+pub mod commitment_tree;
 pub mod compliance;
 pub mod constant;
 pub mod delta_commitment;
 pub mod error;
 mod executable;
+#[cfg(feature = "serde")]
+pub mod json;
+pub mod ledger;
+#[cfg(feature = "borsh")]
+pub mod ledger_storage;
 pub mod merkle_tree;
 pub mod nullifier;
 pub mod proof;
+pub mod ptx_builder;
 pub mod resource;
 pub mod resource_encryption;
+pub mod setup_params;
 pub mod shielded_ptx;
 pub mod taiga_api;
 pub mod transaction;
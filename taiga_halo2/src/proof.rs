@@ -1,10 +1,11 @@
 use halo2_proofs::{
-    plonk::{self, Circuit, ProvingKey, SingleVerifier, VerifyingKey},
-    poly::commitment::Params,
-    transcript::{Blake2bRead, Blake2bWrite},
+    arithmetic::Field,
+    plonk::{self, Circuit, ProvingKey, SingleVerifier, VerificationStrategy, VerifyingKey},
+    poly::commitment::{Guard, Params, MSM},
+    transcript::{Blake2bRead, Blake2bWrite, EncodedChallenge},
 };
 use pasta_curves::{pallas, vesta};
-use rand::RngCore;
+use rand::{rngs::OsRng, RngCore};
 #[cfg(feature = "nif")]
 use rustler::NifTuple;
 
@@ -53,6 +54,26 @@ impl Proof {
         plonk::verify_proof(params, vk, strategy, &[instance], &mut transcript)
     }
 
+    /// Checks this proof with the given instances and defers its final multi-scalar
+    /// multiplication to `batch`.
+    pub fn add_to_batch(
+        &self,
+        batch: &mut BatchProofVerifier,
+        vk: &VerifyingKey<vesta::Affine>,
+        instance: &[&[pallas::Base]],
+    ) -> Result<(), plonk::Error> {
+        let strategy = DeferredVerifier {
+            msm: batch.params.empty_msm(),
+        };
+        let mut transcript = Blake2bRead::init(&self.0[..]);
+        let mut msm =
+            plonk::verify_proof(batch.params, vk, strategy, &[instance], &mut transcript)?;
+        // Randomize the msm so that invalid proofs can't cancel each other out.
+        msm.scale(pallas::Base::random(OsRng));
+        batch.msm.add_msm(&msm);
+        Ok(())
+    }
+
     /// Constructs a new Proof value.
     pub fn new(bytes: Vec<u8>) -> Self {
         Proof(bytes)
@@ -62,3 +83,58 @@ impl Proof {
         self.0.clone()
     }
 }
+
+/// Verifies proofs together: the final multi-scalar multiplications of all the proofs are
+/// checked at once in `finalize`. The proofs may have different verifying keys, but they must
+/// share the params.
+///
+/// halo2's `BatchVerifier` only batches proofs of a single verifying key, while a transaction
+/// mixes the compliance proofs with the proofs of many VP circuits. So each proof is checked up
+/// to its final msm with a `DeferredVerifier`, and the msms, each scaled by a random factor, are
+/// accumulated into one.
+#[derive(Debug)]
+pub struct BatchProofVerifier<'params> {
+    params: &'params Params<vesta::Affine>,
+    msm: MSM<'params, vesta::Affine>,
+}
+
+impl<'params> BatchProofVerifier<'params> {
+    pub fn new(params: &'params Params<vesta::Affine>) -> Self {
+        Self {
+            params,
+            msm: params.empty_msm(),
+        }
+    }
+
+    pub fn params(&self) -> &'params Params<vesta::Affine> {
+        self.params
+    }
+
+    /// Checks the accumulated multi-scalar multiplication.
+    pub fn finalize(self) -> Result<(), plonk::Error> {
+        if self.msm.eval() {
+            Ok(())
+        } else {
+            Err(plonk::Error::ConstraintSystemFailure)
+        }
+    }
+}
+
+// A verification strategy returning the final msm of a proof instead of evaluating it.
+struct DeferredVerifier<'params> {
+    msm: MSM<'params, vesta::Affine>,
+}
+
+impl<'params> VerificationStrategy<'params, vesta::Affine> for DeferredVerifier<'params> {
+    type Output = MSM<'params, vesta::Affine>;
+
+    fn process<E: EncodedChallenge<vesta::Affine>>(
+        self,
+        f: impl FnOnce(
+            MSM<'params, vesta::Affine>,
+        ) -> Result<Guard<'params, vesta::Affine, E>, plonk::Error>,
+    ) -> Result<Self::Output, plonk::Error> {
+        let guard = f(self.msm)?;
+        Ok(guard.use_challenges())
+    }
+}
@@ -0,0 +1,434 @@
+//! A builder of shielded partial transactions.
+//!
+//! The builder pairs the input and output resources into compliances, fills the unused resource
+//! slots of the ptx with padding resources and their trivial VPs, and derives the output resource
+//! nonces from the nullifiers of the input resources they are paired with.
+
+use crate::{
+    compliance::ComplianceInfo,
+    constant::NUM_RESOURCE,
+    error::TransactionError,
+    merkle_tree::{Anchor, MerklePath},
+    resource::{Resource, ResourceValidityPredicates},
+    shielded_ptx::ShieldedPartialTransaction,
+};
+use rand::RngCore;
+use std::fmt;
+
+/// Creates the VPs of a resource from the resource itself and all the resources of the ptx.
+/// Output resources are passed with their final nonce.
+pub type ResourceVPsFn<'a> = Box<
+    dyn FnOnce(
+            &Resource,
+            [Resource; NUM_RESOURCE],
+            [Resource; NUM_RESOURCE],
+        ) -> ResourceValidityPredicates
+        + 'a,
+>;
+
+#[derive(Debug)]
+pub enum PartialTransactionBuilderError {
+    /// The ptx has neither input nor output resources.
+    Empty,
+    /// More than NUM_RESOURCE input resources were added.
+    TooManyInputs(usize),
+    /// More than NUM_RESOURCE output resources were added.
+    TooManyOutputs(usize),
+    /// The input resource at this index has no nullifier key.
+    MissingNullifierKey(usize),
+    /// The input resource at this index was added as ephemeral but isn't.
+    NonEphemeralInput(usize),
+    /// Ephemeral or padding input resources need an anchor, but no anchor was set and no input
+    /// resource has a merkle path.
+    MissingAnchor,
+    /// An error occurred when creating the proofs.
+    Proof(TransactionError),
+}
+
+impl fmt::Display for PartialTransactionBuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use PartialTransactionBuilderError::*;
+        match self {
+            Empty => f.write_str("The partial transaction has no resources"),
+            TooManyInputs(n) => f.write_str(&format!(
+                "{n} input resources are more than the {NUM_RESOURCE} a partial transaction holds"
+            )),
+            TooManyOutputs(n) => f.write_str(&format!(
+                "{n} output resources are more than the {NUM_RESOURCE} a partial transaction holds"
+            )),
+            MissingNullifierKey(i) => {
+                f.write_str(&format!("Input resource {i} has no nullifier key"))
+            }
+            NonEphemeralInput(i) => f.write_str(&format!("Input resource {i} is not ephemeral")),
+            MissingAnchor => f.write_str("The anchor of the ephemeral input resources is missing"),
+            Proof(e) => f.write_str(&format!("Proof error: {e}")),
+        }
+    }
+}
+
+struct InputSpec<'a> {
+    resource: Resource,
+    // None for ephemeral input resources
+    merkle_path: Option<MerklePath>,
+    vps: ResourceVPsFn<'a>,
+}
+
+struct OutputSpec<'a> {
+    resource: Resource,
+    vps: ResourceVPsFn<'a>,
+}
+
+/// Builds a `ShieldedPartialTransaction` from up to NUM_RESOURCE input and output resources.
+/// The i-th input resource is paired with the i-th output resource in a compliance.
+#[derive(Default)]
+pub struct PartialTransactionBuilder<'a> {
+    inputs: Vec<InputSpec<'a>>,
+    outputs: Vec<OutputSpec<'a>>,
+    anchor: Option<Anchor>,
+    hints: Vec<u8>,
+}
+
+impl<'a> PartialTransactionBuilder<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an input resource with its merkle path in the commitment tree.
+    pub fn add_input(
+        &mut self,
+        resource: Resource,
+        merkle_path: MerklePath,
+        vps: impl FnOnce(
+                &Resource,
+                [Resource; NUM_RESOURCE],
+                [Resource; NUM_RESOURCE],
+            ) -> ResourceValidityPredicates
+            + 'a,
+    ) -> &mut Self {
+        self.inputs.push(InputSpec {
+            resource,
+            merkle_path: Some(merkle_path),
+            vps: Box::new(vps),
+        });
+        self
+    }
+
+    /// Adds an ephemeral input resource, which is not in the commitment tree.
+    pub fn add_ephemeral_input(
+        &mut self,
+        resource: Resource,
+        vps: impl FnOnce(
+                &Resource,
+                [Resource; NUM_RESOURCE],
+                [Resource; NUM_RESOURCE],
+            ) -> ResourceValidityPredicates
+            + 'a,
+    ) -> &mut Self {
+        self.inputs.push(InputSpec {
+            resource,
+            merkle_path: None,
+            vps: Box::new(vps),
+        });
+        self
+    }
+
+    /// Adds an output resource. Its nonce is derived when the ptx is built.
+    pub fn add_output(
+        &mut self,
+        resource: Resource,
+        vps: impl FnOnce(
+                &Resource,
+                [Resource; NUM_RESOURCE],
+                [Resource; NUM_RESOURCE],
+            ) -> ResourceValidityPredicates
+            + 'a,
+    ) -> &mut Self {
+        self.outputs.push(OutputSpec {
+            resource,
+            vps: Box::new(vps),
+        });
+        self
+    }
+
+    /// Sets the anchor of the ephemeral and padding input resources. It defaults to the root
+    /// computed from the first input resource with a merkle path.
+    pub fn set_anchor(&mut self, anchor: Anchor) -> &mut Self {
+        self.anchor = Some(anchor);
+        self
+    }
+
+    pub fn set_hints(&mut self, hints: Vec<u8>) -> &mut Self {
+        self.hints = hints;
+        self
+    }
+
+    pub fn build<R: RngCore>(
+        self,
+        mut rng: R,
+    ) -> Result<ShieldedPartialTransaction, PartialTransactionBuilderError> {
+        if self.inputs.is_empty() && self.outputs.is_empty() {
+            return Err(PartialTransactionBuilderError::Empty);
+        }
+        if self.inputs.len() > NUM_RESOURCE {
+            return Err(PartialTransactionBuilderError::TooManyInputs(
+                self.inputs.len(),
+            ));
+        }
+        if self.outputs.len() > NUM_RESOURCE {
+            return Err(PartialTransactionBuilderError::TooManyOutputs(
+                self.outputs.len(),
+            ));
+        }
+        for (i, input) in self.inputs.iter().enumerate() {
+            if input.resource.get_nk().is_none() {
+                return Err(PartialTransactionBuilderError::MissingNullifierKey(i));
+            }
+            if input.merkle_path.is_none() && !input.resource.is_ephemeral {
+                return Err(PartialTransactionBuilderError::NonEphemeralInput(i));
+            }
+        }
+
+        let anchor = self.anchor.or_else(|| {
+            self.inputs.iter().find_map(|input| {
+                input
+                    .merkle_path
+                    .as_ref()
+                    .map(|merkle_path| input.resource.calculate_root(merkle_path))
+            })
+        });
+
+        // Pair the resources into compliances, filling the unused slots with padding resources.
+        let mut input_vps_fns = vec![];
+        let mut output_vps_fns = vec![];
+        let mut input_resources = vec![];
+        let mut output_resources = vec![];
+        let mut inputs = self.inputs.into_iter();
+        let mut outputs = self.outputs.into_iter();
+        let mut compliances = Vec::with_capacity(NUM_RESOURCE);
+        for i in 0..NUM_RESOURCE {
+            let (mut output_resource, output_vps) = match outputs.next() {
+                Some(output) => (output.resource, Some(output.vps)),
+                None => (Resource::random_padding_resource(&mut rng), None),
+            };
+            let (input_resource, merkle_path, input_vps) = match inputs.next() {
+                Some(input) => (input.resource, input.merkle_path, Some(input.vps)),
+                None => (Resource::random_padding_resource(&mut rng), None, None),
+            };
+            let compliance = match merkle_path {
+                Some(merkle_path) => ComplianceInfo::new(
+                    input_resource,
+                    merkle_path,
+                    None,
+                    &mut output_resource,
+                    &mut rng,
+                ),
+                None => ComplianceInfo::new_ephemeral_input(
+                    input_resource,
+                    anchor.ok_or(PartialTransactionBuilderError::MissingAnchor)?,
+                    &mut output_resource,
+                    &mut rng,
+                )
+                .map_err(|_| PartialTransactionBuilderError::NonEphemeralInput(i))?,
+            };
+            compliances.push(compliance);
+            input_resources.push(input_resource);
+            output_resources.push(output_resource);
+            input_vps_fns.push(input_vps);
+            output_vps_fns.push(output_vps);
+        }
+
+        // Create the VPs once all the resources are final.
+        let input_resources: [Resource; NUM_RESOURCE] = input_resources.try_into().unwrap();
+        let output_resources: [Resource; NUM_RESOURCE] = output_resources.try_into().unwrap();
+        let input_vps = input_vps_fns
+            .into_iter()
+            .zip(input_resources.iter())
+            .map(|(vps, resource)| match vps {
+                Some(vps) => vps(resource, input_resources, output_resources),
+                None => ResourceValidityPredicates::create_input_padding_resource_vps(
+                    resource,
+                    input_resources,
+                    output_resources,
+                ),
+            })
+            .collect();
+        let output_vps = output_vps_fns
+            .into_iter()
+            .zip(output_resources.iter())
+            .map(|(vps, resource)| match vps {
+                Some(vps) => vps(resource, input_resources, output_resources),
+                None => ResourceValidityPredicates::create_output_padding_resource_vps(
+                    resource,
+                    input_resources,
+                    output_resources,
+                ),
+            })
+            .collect();
+
+        ShieldedPartialTransaction::build(compliances, input_vps, output_vps, self.hints, &mut rng)
+            .map_err(PartialTransactionBuilderError::Proof)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{PartialTransactionBuilder, PartialTransactionBuilderError};
+    use crate::{
+        circuit::vp_examples::COMPRESSED_TRIVIAL_VP_VK,
+        constant::NUM_RESOURCE,
+        executable::Executable,
+        ledger::{tests::commit_resources, InMemoryLedgerState, LedgerState},
+        resource::{tests::random_resource, ResourceValidityPredicates},
+    };
+    use rand::{
+        rngs::{OsRng, StdRng},
+        SeedableRng,
+    };
+
+    #[test]
+    fn test_ptx_builder() {
+        let mut rng = OsRng;
+        let mut input_resource = random_resource(&mut rng);
+        input_resource.kind.logic = *COMPRESSED_TRIVIAL_VP_VK;
+        let mut output_resource = random_resource(&mut rng);
+        output_resource.kind.logic = *COMPRESSED_TRIVIAL_VP_VK;
+        let mut state = InMemoryLedgerState::new();
+        let merkle_path = commit_resources(&mut state, &[input_resource])
+            .pop()
+            .unwrap();
+
+        // One input and one output, the other slots are padded.
+        let mut builder = PartialTransactionBuilder::new();
+        builder
+            .add_input(
+                input_resource,
+                merkle_path.clone(),
+                ResourceValidityPredicates::create_input_padding_resource_vps,
+            )
+            .add_output(
+                output_resource,
+                ResourceValidityPredicates::create_output_padding_resource_vps,
+            );
+        let ptx = builder.build(&mut rng).unwrap();
+        ptx.verify_proof().unwrap();
+        // The padding input resources use the root of the input resource as their anchor.
+        assert!(ptx
+            .get_anchors()
+            .iter()
+            .all(|anchor| state.is_known_anchor(anchor)));
+
+        // Too many inputs
+        let mut builder = PartialTransactionBuilder::new();
+        for _ in 0..=NUM_RESOURCE {
+            builder.add_input(
+                input_resource,
+                merkle_path.clone(),
+                ResourceValidityPredicates::create_input_padding_resource_vps,
+            );
+        }
+        assert!(matches!(
+            builder.build(&mut rng),
+            Err(PartialTransactionBuilderError::TooManyInputs(n)) if n == NUM_RESOURCE + 1
+        ));
+
+        // Padding input resources need an anchor.
+        let mut builder = PartialTransactionBuilder::new();
+        builder.add_output(
+            output_resource,
+            ResourceValidityPredicates::create_output_padding_resource_vps,
+        );
+        assert!(matches!(
+            builder.build(&mut rng),
+            Err(PartialTransactionBuilderError::MissingAnchor)
+        ));
+    }
+
+    #[test]
+    fn test_ptx_builder_asymmetric() {
+        let mut rng = OsRng;
+        let mut state = InMemoryLedgerState::new();
+        // Fewer inputs than outputs and the reverse, padded up to NUM_RESOURCE
+        for (input_num, output_num) in [(1, NUM_RESOURCE.min(3)), (NUM_RESOURCE.min(4), 1)] {
+            let input_resources: Vec<_> = (0..input_num)
+                .map(|_| {
+                    let mut resource = random_resource(&mut rng);
+                    resource.kind.logic = *COMPRESSED_TRIVIAL_VP_VK;
+                    resource
+                })
+                .collect();
+            let merkle_paths = commit_resources(&mut state, &input_resources);
+            let mut builder = PartialTransactionBuilder::new();
+            for (input_resource, merkle_path) in input_resources.into_iter().zip(merkle_paths) {
+                builder.add_input(
+                    input_resource,
+                    merkle_path,
+                    ResourceValidityPredicates::create_input_padding_resource_vps,
+                );
+            }
+            for _ in 0..output_num {
+                let mut output_resource = random_resource(&mut rng);
+                output_resource.kind.logic = *COMPRESSED_TRIVIAL_VP_VK;
+                builder.add_output(
+                    output_resource,
+                    ResourceValidityPredicates::create_output_padding_resource_vps,
+                );
+            }
+            let ptx = builder.build(&mut rng).unwrap();
+            ptx.verify_proof().unwrap();
+            assert!(ptx
+                .get_anchors()
+                .iter()
+                .all(|anchor| state.is_known_anchor(anchor)));
+            // The unused slots are filled with padding resources.
+            assert_eq!(ptx.get_nullifiers().unwrap().len(), NUM_RESOURCE);
+            assert_eq!(ptx.get_output_cms().len(), NUM_RESOURCE);
+        }
+
+        // Too many outputs
+        let mut builder = PartialTransactionBuilder::new();
+        for _ in 0..=NUM_RESOURCE {
+            builder.add_output(
+                random_resource(&mut rng),
+                ResourceValidityPredicates::create_output_padding_resource_vps,
+            );
+        }
+        assert!(matches!(
+            builder.build(&mut rng),
+            Err(PartialTransactionBuilderError::TooManyOutputs(n)) if n == NUM_RESOURCE + 1
+        ));
+    }
+
+    #[test]
+    fn test_ptx_builder_deterministic() {
+        let mut rng = OsRng;
+        let mut input_resource = random_resource(&mut rng);
+        input_resource.kind.logic = *COMPRESSED_TRIVIAL_VP_VK;
+        let mut output_resource = random_resource(&mut rng);
+        output_resource.kind.logic = *COMPRESSED_TRIVIAL_VP_VK;
+        let mut state = InMemoryLedgerState::new();
+        let merkle_path = commit_resources(&mut state, &[input_resource])
+            .pop()
+            .unwrap();
+
+        // The proofs only take their randomness from the rng of the builder, whether they are
+        // created serially or in parallel.
+        let build = |seed: u64| {
+            let mut builder = PartialTransactionBuilder::new();
+            builder
+                .add_input(
+                    input_resource,
+                    merkle_path.clone(),
+                    ResourceValidityPredicates::create_input_padding_resource_vps,
+                )
+                .add_output(
+                    output_resource,
+                    ResourceValidityPredicates::create_output_padding_resource_vps,
+                );
+            builder.build(StdRng::seed_from_u64(seed)).unwrap()
+        };
+        let ptx = build(1);
+        ptx.verify_proof().unwrap();
+        assert_eq!(ptx.proofs_digest(), build(1).proofs_digest());
+        assert_ne!(ptx.proofs_digest(), build(2).proofs_digest());
+    }
+}
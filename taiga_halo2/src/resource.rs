@@ -8,6 +8,7 @@ use crate::{
         PRF_EXPAND_PERSONALIZATION_TO_FIELD, PRF_EXPAND_PSI, PRF_EXPAND_PUBLIC_INPUT_PADDING,
         PRF_EXPAND_RCM, PRF_EXPAND_VCM_R,
     },
+    error::TransactionError,
     merkle_tree::{Anchor, MerklePath, Node},
     nullifier::{Nullifier, NullifierKeyContainer},
     shielded_ptx::ResourceVPVerifyingInfoSet,
@@ -17,7 +18,7 @@ use blake2b_simd::Params as Blake2bParams;
 use ff::{FromUniformBytes, PrimeField};
 use halo2_proofs::arithmetic::Field;
 use pasta_curves::pallas;
-use rand::RngCore;
+use rand::{rngs::OsRng, RngCore};
 use std::hash::{Hash, Hasher};
 use subtle::CtOption;
 
@@ -290,39 +291,35 @@ impl Resource {
     pub fn set_nonce(&mut self, input_resource: &Resource) {
         self.nonce = input_resource.get_nf().unwrap();
     }
-}
 
-#[cfg(feature = "borsh")]
-impl BorshSerialize for Resource {
-    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
-        use byteorder::{LittleEndian, WriteBytesExt};
-        // Write logic
-        writer.write_all(&self.kind.logic.to_repr())?;
-        // Write label
-        writer.write_all(&self.kind.label.to_repr())?;
-        // Write value
-        writer.write_all(&self.value.to_repr())?;
-        // Write resource quantity
-        writer.write_u64::<LittleEndian>(self.quantity)?;
-        // Write nk_container
+    /// The fixed-length encoding of the resource, also used by its borsh serialization.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&self.kind.logic.to_repr());
+        bytes.extend_from_slice(&self.kind.label.to_repr());
+        bytes.extend_from_slice(&self.value.to_repr());
+        bytes.extend_from_slice(&self.quantity.to_le_bytes());
         match self.nk_container {
             NullifierKeyContainer::PublicKey(nk) => {
-                writer.write_u8(1)?;
-                writer.write_all(&nk.to_repr())
+                bytes.push(1);
+                bytes.extend_from_slice(&nk.to_repr());
             }
             NullifierKeyContainer::Key(nk) => {
-                writer.write_u8(2)?;
-                writer.write_all(&nk.to_repr())
+                bytes.push(2);
+                bytes.extend_from_slice(&nk.to_repr());
             }
-        }?;
-        // Write nonce
-        writer.write_all(&self.nonce.to_bytes())?;
-        // Write is_ephemeral
-        writer.write_u8(if self.is_ephemeral { 1 } else { 0 })?;
-        // Write rseed
-        writer.write_all(&self.rseed.to_repr())?;
+        }
+        bytes.extend_from_slice(&self.nonce.to_bytes());
+        bytes.push(self.is_ephemeral as u8);
+        bytes.extend_from_slice(&self.rseed.to_repr());
+        bytes
+    }
+}
 
-        Ok(())
+#[cfg(feature = "borsh")]
+impl BorshSerialize for Resource {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(&self.to_bytes())
     }
 }
 
@@ -409,6 +406,10 @@ impl RandomSeed {
         Self(rseed)
     }
 
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0
+    }
+
     pub fn get_random_padding(&self, padding_len: usize) -> Vec<pallas::Base> {
         (0..padding_len)
             .map(|i| {
@@ -458,17 +459,35 @@ impl ResourceValidityPredicates {
         }
     }
 
+    // The application vp followed by the dynamic vps
+    pub fn get_vps(&self) -> Vec<&ValidityPredicate> {
+        std::iter::once(self.application_vp.as_ref())
+            .chain(self.dynamic_vps.iter().map(|vp| vp.as_ref()))
+            .collect()
+    }
+
     // Generate vp proofs
-    pub fn build(&self) -> ResourceVPVerifyingInfoSet {
-        let app_vp_verifying_info = self.application_vp.get_verifying_info();
+    pub fn build(&self) -> Result<ResourceVPVerifyingInfoSet, TransactionError> {
+        self.build_with_rng(&mut OsRng)
+    }
+
+    // Generate vp proofs, taking all the randomness from `rng`
+    pub fn build_with_rng(
+        &self,
+        rng: &mut dyn RngCore,
+    ) -> Result<ResourceVPVerifyingInfoSet, TransactionError> {
+        let app_vp_verifying_info = self.application_vp.get_verifying_info_with_rng(rng)?;
 
         let app_dynamic_vp_verifying_info = self
             .dynamic_vps
             .iter()
-            .map(|verifying_info| verifying_info.get_verifying_info())
-            .collect();
+            .map(|verifying_info| verifying_info.get_verifying_info_with_rng(rng))
+            .collect::<Result<_, _>>()?;
 
-        ResourceVPVerifyingInfoSet::new(app_vp_verifying_info, app_dynamic_vp_verifying_info)
+        Ok(ResourceVPVerifyingInfoSet::new(
+            app_vp_verifying_info,
+            app_dynamic_vp_verifying_info,
+        ))
     }
 
     // Create an input padding resource vps
@@ -0,0 +1,219 @@
+//! Loading and saving of the setup params.
+//!
+//! Params files start with a header holding a magic, the size `k` and a BLAKE2b checksum of
+//! the serialized params, so that truncated or tampered files are rejected on load.
+//!
+//! The params used by `SETUP_PARAMS_MAP`, the compliance keys and the vamp-ir circuits come
+//! from the process-wide `ParamsSource`. It defaults to the `TAIGA_PARAMS_DIR` directory if the
+//! variable is set, and to the params embedded in the binary otherwise.
+
+use crate::constant::{PARAMS_CHECKSUM_PERSONALIZATION, PARAMS_SIZE};
+use blake2b_simd::Params as Blake2bParams;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use halo2_proofs::poly::commitment::Params;
+use lazy_static::lazy_static;
+use pasta_curves::vesta;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+
+const PARAMS_FILE_MAGIC: &[u8; 8] = b"TaigaPrm";
+const PARAMS_CHECKSUM_SIZE: usize = 32;
+pub const PARAMS_DIR_ENV: &str = "TAIGA_PARAMS_DIR";
+
+lazy_static! {
+    static ref PARAMS_SOURCE: RwLock<ParamsSource> = RwLock::new(ParamsSource::from_env());
+    static ref PARAMS_CACHE: Mutex<HashMap<u32, Params<vesta::Affine>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Where the setup params are taken from.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ParamsSource {
+    /// The params embedded in the binary. Other sizes are generated.
+    #[default]
+    Embedded,
+    /// The `params_<k>` files in a directory. Missing files are generated and saved there.
+    Dir(PathBuf),
+}
+
+impl ParamsSource {
+    pub fn from_env() -> Self {
+        match std::env::var_os(PARAMS_DIR_ENV) {
+            Some(dir) => ParamsSource::Dir(dir.into()),
+            None => ParamsSource::Embedded,
+        }
+    }
+}
+
+/// Sets the process-wide params source. It must be called before the params are first used,
+/// e.g. through `SETUP_PARAMS_MAP`, since loaded params are not reloaded.
+pub fn set_params_source(source: ParamsSource) {
+    *PARAMS_SOURCE.write().unwrap() = source;
+}
+
+pub fn get_params_source() -> ParamsSource {
+    PARAMS_SOURCE.read().unwrap().clone()
+}
+
+/// Returns the params of size `k` from the configured source.
+pub fn load_setup_params(k: u32) -> io::Result<Params<vesta::Affine>> {
+    if let Some(params) = PARAMS_CACHE.lock().unwrap().get(&k) {
+        return Ok(params.clone());
+    }
+
+    let params = match get_params_source() {
+        ParamsSource::Embedded => {
+            if k == PARAMS_SIZE {
+                let bytes = include_bytes!("../params/params_15");
+                Params::<vesta::Affine>::read(&mut &bytes[..])?
+            } else {
+                Params::new(k)
+            }
+        }
+        ParamsSource::Dir(dir) => {
+            let path = params_file_path(&dir, k);
+            if path.exists() {
+                let (file_k, params) = read_sized_params(&mut BufReader::new(File::open(&path)?))?;
+                if file_k != k {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{} holds params of size {file_k}", path.display()),
+                    ));
+                }
+                params
+            } else {
+                let params = Params::new(k);
+                save_params_to_file(&params, &path)?;
+                params
+            }
+        }
+    };
+
+    PARAMS_CACHE.lock().unwrap().insert(k, params.clone());
+    Ok(params)
+}
+
+pub fn params_file_path(dir: impl AsRef<Path>, k: u32) -> PathBuf {
+    dir.as_ref().join(format!("params_{k}"))
+}
+
+/// Writes the params with a header holding the size and the checksum of the params.
+pub fn write_params<W: Write>(params: &Params<vesta::Affine>, writer: &mut W) -> io::Result<()> {
+    let mut bytes = vec![];
+    params.write(&mut bytes)?;
+    // The serialized params start with k.
+    let k = (&bytes[..]).read_u32::<LittleEndian>()?;
+    writer.write_all(PARAMS_FILE_MAGIC)?;
+    writer.write_u32::<LittleEndian>(k)?;
+    writer.write_all(&params_checksum(&bytes))?;
+    writer.write_all(&bytes)
+}
+
+/// Reads params written by `write_params`, checking the header and the checksum.
+pub fn read_params<R: Read>(reader: &mut R) -> io::Result<Params<vesta::Affine>> {
+    read_sized_params(reader).map(|(_, params)| params)
+}
+
+// Reads params written by `write_params` and returns them with their size.
+fn read_sized_params<R: Read>(reader: &mut R) -> io::Result<(u32, Params<vesta::Affine>)> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != PARAMS_FILE_MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a params file",
+        ));
+    }
+    let k = reader.read_u32::<LittleEndian>()?;
+    let mut checksum = [0u8; PARAMS_CHECKSUM_SIZE];
+    reader.read_exact(&mut checksum)?;
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+    if checksum != params_checksum(&bytes) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "params checksum mismatch",
+        ));
+    }
+
+    if (&bytes[..]).read_u32::<LittleEndian>()? != k {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "params size mismatch",
+        ));
+    }
+    let params = Params::<vesta::Affine>::read(&mut bytes.as_slice())?;
+    Ok((k, params))
+}
+
+/// Saves the params to `path`. The file is written next to it first and then renamed, so a
+/// crash never leaves a partial params file behind.
+pub fn save_params_to_file(
+    params: &Params<vesta::Affine>,
+    path: impl AsRef<Path>,
+) -> io::Result<()> {
+    let path = path.as_ref();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    let mut writer = BufWriter::new(File::create(&tmp_path)?);
+    write_params(params, &mut writer)?;
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+    fs::rename(tmp_path, path)
+}
+
+pub fn load_params_from_file(path: impl AsRef<Path>) -> io::Result<Params<vesta::Affine>> {
+    read_params(&mut BufReader::new(File::open(path)?))
+}
+
+fn params_checksum(bytes: &[u8]) -> [u8; PARAMS_CHECKSUM_SIZE] {
+    let mut h = Blake2bParams::new()
+        .hash_length(PARAMS_CHECKSUM_SIZE)
+        .personal(PARAMS_CHECKSUM_PERSONALIZATION)
+        .to_state();
+    h.update(bytes);
+    h.finalize().as_bytes().try_into().unwrap()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{load_params_from_file, read_params, save_params_to_file, write_params};
+    use halo2_proofs::poly::commitment::Params;
+    use pasta_curves::vesta;
+    use rand::{rngs::OsRng, RngCore};
+
+    #[test]
+    fn test_params_serialization() {
+        let params: Params<vesta::Affine> = Params::new(4);
+        let mut bytes = vec![];
+        write_params(&params, &mut bytes).unwrap();
+        let mut expected = vec![];
+        params.write(&mut expected).unwrap();
+        let to_bytes = |params: Params<vesta::Affine>| {
+            let mut bytes = vec![];
+            params.write(&mut bytes).unwrap();
+            bytes
+        };
+        assert_eq!(
+            to_bytes(read_params(&mut bytes.as_slice()).unwrap()),
+            expected
+        );
+
+        // Corrupted params are rejected.
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert!(read_params(&mut bytes.as_slice()).is_err());
+        // Truncated params are rejected.
+        assert!(read_params(&mut &bytes[..last]).is_err());
+
+        let path = std::env::temp_dir().join(format!("taiga_params_{}", OsRng.next_u64()));
+        save_params_to_file(&params, &path).unwrap();
+        assert_eq!(to_bytes(load_params_from_file(&path).unwrap()), expected);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
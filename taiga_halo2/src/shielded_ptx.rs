@@ -1,19 +1,25 @@
 use crate::circuit::vp_circuit::{VPVerifyingInfo, ValidityPredicate};
 use crate::compliance::{ComplianceInfo, CompliancePublicInputs};
 use crate::constant::{
-    COMPLIANCE_CIRCUIT_PARAMS_SIZE, COMPLIANCE_PROVING_KEY, COMPLIANCE_VERIFYING_KEY,
-    MAX_DYNAMIC_VP_NUM, NUM_RESOURCE, SETUP_PARAMS_MAP,
+    COMPLIANCE_CIRCUIT_PARAMS_SIZE, COMPLIANCE_PROVING_KEY, COMPLIANCE_VERIFYING_KEY, NUM_RESOURCE,
+    SETUP_PARAMS_MAP, SHIELDED_PTX_ID_PERSONALIZATION, SHIELDED_PTX_PROOFS_DIGEST_PERSONALIZATION,
 };
 use crate::delta_commitment::DeltaCommitment;
 use crate::error::TransactionError;
 use crate::executable::Executable;
 use crate::merkle_tree::Anchor;
 use crate::nullifier::Nullifier;
-use crate::proof::Proof;
+use crate::proof::{BatchProofVerifier, Proof};
 use crate::resource::{ResourceCommitment, ResourceValidityPredicates};
-use halo2_proofs::plonk::Error;
-use pasta_curves::pallas;
-use rand::RngCore;
+use crate::utils::{check_ptx_size, is_permutation};
+#[cfg(feature = "borsh")]
+use crate::vp_vk::VPVerifyingKeyRegistry;
+use crate::vp_vk::ValidityPredicateVerifyingKey;
+use blake2b_simd::Params as Blake2bParams;
+use ff::PrimeField;
+use halo2_proofs::plonk::{Error, VerifyingKey};
+use pasta_curves::{pallas, vesta};
+use rand::{rngs::StdRng, RngCore, SeedableRng};
 
 #[cfg(feature = "nif")]
 use rustler::{Decoder, Encoder, Env, NifResult, NifStruct, Term};
@@ -21,20 +27,18 @@ use rustler::{Decoder, Encoder, Env, NifResult, NifStruct, Term};
 #[cfg(feature = "serde")]
 use serde;
 
-use crate::circuit::vp_bytecode::ApplicationByteCode;
+use crate::circuit::vp_bytecode::{ApplicationByteCode, ValidityPredicateByteCode};
 #[cfg(feature = "borsh")]
 use borsh::{BorshDeserialize, BorshSerialize};
-#[cfg(feature = "borsh")]
-use ff::PrimeField;
 
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ShieldedPartialTransaction {
-    compliances: [ComplianceVerifyingInfo; NUM_RESOURCE],
-    inputs: [ResourceVPVerifyingInfoSet; NUM_RESOURCE],
-    outputs: [ResourceVPVerifyingInfoSet; NUM_RESOURCE],
-    binding_sig_r: Option<pallas::Scalar>,
-    hints: Vec<u8>,
+    pub(crate) compliances: [ComplianceVerifyingInfo; NUM_RESOURCE],
+    pub(crate) inputs: [ResourceVPVerifyingInfoSet; NUM_RESOURCE],
+    pub(crate) outputs: [ResourceVPVerifyingInfoSet; NUM_RESOURCE],
+    pub(crate) binding_sig_r: Option<pallas::Scalar>,
+    pub(crate) hints: Vec<u8>,
 }
 
 #[derive(Debug, Clone)]
@@ -43,8 +47,8 @@ pub struct ShieldedPartialTransaction {
 #[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ComplianceVerifyingInfo {
-    compliance_proof: Proof,
-    compliance_instance: CompliancePublicInputs,
+    pub(crate) compliance_proof: Proof,
+    pub(crate) compliance_instance: CompliancePublicInputs,
 }
 
 #[derive(Debug, Clone)]
@@ -53,8 +57,8 @@ pub struct ComplianceVerifyingInfo {
 #[cfg_attr(feature = "nif", derive(NifStruct))]
 #[cfg_attr(feature = "nif", module = "Taiga.Resource.VerifyingInfo")]
 pub struct ResourceVPVerifyingInfoSet {
-    app_vp_verifying_info: VPVerifyingInfo,
-    app_dynamic_vp_verifying_info: Vec<VPVerifyingInfo>,
+    pub(crate) app_vp_verifying_info: VPVerifyingInfo,
+    pub(crate) app_dynamic_vp_verifying_info: Vec<VPVerifyingInfo>,
     // TODO: add verifier proof and according public inputs.
     // When the verifier proof is added, we may need to reconsider the structure of `VPVerifyingInfo`
 }
@@ -77,29 +81,49 @@ impl ShieldedPartialTransaction {
         input_resource_app: Vec<ApplicationByteCode>,
         output_resource_app: Vec<ApplicationByteCode>,
         hints: Vec<u8>,
-        mut rng: R,
+        rng: R,
     ) -> Result<Self, TransactionError> {
-        let inputs: Result<Vec<_>, _> = input_resource_app
+        check_ptx_size(
+            compliances.len(),
+            input_resource_app.len(),
+            output_resource_app.len(),
+        )?;
+        let rcv_sum = get_rcv_sum(&compliances);
+        let input_vp_bytecodes: Vec<_> = input_resource_app
             .into_iter()
-            .map(|bytecode| bytecode.generate_proofs())
+            .map(ApplicationByteCode::into_vp_bytecodes)
             .collect();
-        let outputs: Result<Vec<_>, _> = output_resource_app
+        let output_vp_bytecodes: Vec<_> = output_resource_app
             .into_iter()
-            .map(|bytecode| bytecode.generate_proofs())
+            .map(ApplicationByteCode::into_vp_bytecodes)
             .collect();
-        let mut rcv_sum = pallas::Scalar::zero();
-        let compliances: Vec<ComplianceVerifyingInfo> = compliances
+        let input_vp_nums: Vec<_> = input_vp_bytecodes.iter().map(Vec::len).collect();
+        let output_vp_nums: Vec<_> = output_vp_bytecodes.iter().map(Vec::len).collect();
+
+        let tasks = compliances
             .iter()
-            .map(|compliance_info| {
-                rcv_sum += compliance_info.get_rcv();
-                ComplianceVerifyingInfo::create(compliance_info, &mut rng).unwrap()
-            })
+            .map(ProvingTask::Compliance)
+            .chain(
+                input_vp_bytecodes
+                    .into_iter()
+                    .chain(output_vp_bytecodes)
+                    .flatten()
+                    .map(ProvingTask::ByteCode),
+            )
             .collect();
+        let mut proofs = create_proofs(tasks, rng)?.into_iter();
 
+        let compliances: Vec<_> = proofs
+            .by_ref()
+            .take(compliances.len())
+            .map(ProvingOutput::into_compliance)
+            .collect();
+        let inputs = collect_vp_proofs(&mut proofs, &input_vp_nums);
+        let outputs = collect_vp_proofs(&mut proofs, &output_vp_nums);
         Ok(Self {
             compliances: compliances.try_into().unwrap(),
-            inputs: inputs?.try_into().unwrap(),
-            outputs: outputs?.try_into().unwrap(),
+            inputs: inputs.try_into().unwrap(),
+            outputs: outputs.try_into().unwrap(),
             binding_sig_r: Some(rcv_sum),
             hints,
         })
@@ -110,30 +134,45 @@ impl ShieldedPartialTransaction {
         input_resource_vps: Vec<ResourceValidityPredicates>,
         output_resource_vps: Vec<ResourceValidityPredicates>,
         hints: Vec<u8>,
-        mut rng: R,
-    ) -> Result<Self, Error> {
-        // Generate compliance proofs
-        let mut rcv_sum = pallas::Scalar::zero();
-        let compliances: Vec<ComplianceVerifyingInfo> = compliance_pairs
+        rng: R,
+    ) -> Result<Self, TransactionError> {
+        check_ptx_size(
+            compliance_pairs.len(),
+            input_resource_vps.len(),
+            output_resource_vps.len(),
+        )?;
+        let rcv_sum = get_rcv_sum(&compliance_pairs);
+        let input_vps: Vec<_> = input_resource_vps
             .iter()
-            .map(|compliance_info| {
-                rcv_sum += compliance_info.get_rcv();
-                ComplianceVerifyingInfo::create(compliance_info, &mut rng).unwrap()
-            })
+            .map(ResourceValidityPredicates::get_vps)
             .collect();
-
-        // Generate input vp proofs
-        let inputs: Vec<ResourceVPVerifyingInfoSet> = input_resource_vps
+        let output_vps: Vec<_> = output_resource_vps
             .iter()
-            .map(|input_resource_vp| input_resource_vp.build())
+            .map(ResourceValidityPredicates::get_vps)
             .collect();
+        let input_vp_nums: Vec<_> = input_vps.iter().map(Vec::len).collect();
+        let output_vp_nums: Vec<_> = output_vps.iter().map(Vec::len).collect();
 
-        // Generate output vp proofs
-        let outputs: Vec<ResourceVPVerifyingInfoSet> = output_resource_vps
+        let tasks = compliance_pairs
             .iter()
-            .map(|output_resource_vp| output_resource_vp.build())
+            .map(ProvingTask::Compliance)
+            .chain(
+                input_vps
+                    .into_iter()
+                    .chain(output_vps)
+                    .flatten()
+                    .map(ProvingTask::Vp),
+            )
             .collect();
+        let mut proofs = create_proofs(tasks, rng)?.into_iter();
 
+        let compliances: Vec<_> = proofs
+            .by_ref()
+            .take(compliance_pairs.len())
+            .map(ProvingOutput::into_compliance)
+            .collect();
+        let inputs = collect_vp_proofs(&mut proofs, &input_vp_nums);
+        let outputs = collect_vp_proofs(&mut proofs, &output_vp_nums);
         Ok(Self {
             compliances: compliances.try_into().unwrap(),
             inputs: inputs.try_into().unwrap(),
@@ -159,19 +198,48 @@ impl ShieldedPartialTransaction {
             verifying_info.verify()?;
         }
 
+        self.check_dynamic_vps()
+    }
+
+    /// Adds all the compliance and vp proofs to `batch` instead of verifying them one by one.
+    pub fn add_proofs_to_batch(
+        &self,
+        batch: &mut BatchProofVerifier,
+    ) -> Result<(), TransactionError> {
+        for verifying_info in self.compliances.iter() {
+            verifying_info.add_to_batch(batch)?;
+        }
+        for verifying_info in self.inputs.iter().chain(self.outputs.iter()) {
+            verifying_info.add_to_batch(batch)?;
+        }
+
+        self.check_dynamic_vps()
+    }
+
+    // check the dynamic vps of every resource are the ones its app vp commits to
+    fn check_dynamic_vps(&self) -> Result<(), TransactionError> {
+        for vp_info in self.inputs.iter().chain(self.outputs.iter()) {
+            vp_info.check_dynamic_vps()?;
+        }
+        Ok(())
+    }
+
+    /// Like `execute`, but the proofs are added to `batch` and only verified when it is finalized.
+    pub fn execute_batched(&self, batch: &mut BatchProofVerifier) -> Result<(), TransactionError> {
+        self.add_proofs_to_batch(batch)?;
+        self.check_nullifiers()?;
+        self.check_resource_commitments()?;
         Ok(())
     }
 
     // check the nullifiers are from compliance proofs
     fn check_nullifiers(&self) -> Result<(), TransactionError> {
-        assert_eq!(NUM_RESOURCE, 2);
-        let compliance_nfs = self.get_nullifiers();
+        let compliance_nfs = self.get_nullifiers()?;
+        let compliance_nf_values: Vec<_> = compliance_nfs.iter().map(|nf| nf.inner()).collect();
         for vp_info in self.inputs.iter().chain(self.outputs.iter()) {
             for nfs in vp_info.get_nullifiers().iter() {
                 // Check the vp actually uses the input resources from compliance circuits.
-                if !((compliance_nfs[0].inner() == nfs[0] && compliance_nfs[1].inner() == nfs[1])
-                    || (compliance_nfs[0].inner() == nfs[1] && compliance_nfs[1].inner() == nfs[0]))
-                {
+                if !is_permutation(&compliance_nf_values, nfs) {
                     return Err(TransactionError::InconsistentNullifier);
                 }
             }
@@ -196,14 +264,11 @@ impl ShieldedPartialTransaction {
 
     // check the output cms are from compliance proofs
     fn check_resource_commitments(&self) -> Result<(), TransactionError> {
-        assert_eq!(NUM_RESOURCE, 2);
         let compliance_cms = self.get_output_cms();
         for vp_info in self.inputs.iter().chain(self.outputs.iter()) {
             for cms in vp_info.get_resource_commitments().iter() {
                 // Check the vp actually uses the output resources from compliance circuits.
-                if !((compliance_cms[0] == cms[0] && compliance_cms[1] == cms[1])
-                    || (compliance_cms[0] == cms[1] && compliance_cms[1] == cms[0]))
-                {
+                if !is_permutation(&compliance_cms, cms) {
                     return Err(TransactionError::InconsistentOutputResourceCommitment);
                 }
             }
@@ -249,6 +314,67 @@ impl ShieldedPartialTransaction {
         self.binding_sig_r = None;
         self.hints = vec![];
     }
+
+    /// The id of the ptx, a hash of its public content: the public inputs of the compliance
+    /// proofs, and the vks and public inputs of the vp proofs. The proofs are committed to
+    /// separately by `proofs_digest`. The hints and binding_sig_r are not part of the id, since
+    /// they are removed when the ptx is put in a transaction.
+    pub fn id(&self) -> [u8; 32] {
+        let mut h = Blake2bParams::new()
+            .hash_length(32)
+            .personal(SHIELDED_PTX_ID_PERSONALIZATION)
+            .to_state();
+        for compliance in self.compliances.iter() {
+            for x in compliance.compliance_instance.to_instance() {
+                h.update(&x.to_repr());
+            }
+        }
+        for vp_info_set in self.inputs.iter().chain(self.outputs.iter()) {
+            // The number of dynamic vps varies per resource.
+            h.update(&(vp_info_set.app_dynamic_vp_verifying_info.len() as u32).to_le_bytes());
+            for vp_info in vp_info_set.iter() {
+                let vk = ValidityPredicateVerifyingKey::from_vk(vp_info.vk.clone());
+                h.update(&vk.get_compressed().to_repr());
+                for x in vp_info.public_inputs.inner() {
+                    h.update(&x.to_repr());
+                }
+            }
+        }
+        h.finalize().as_bytes().try_into().unwrap()
+    }
+
+    /// The vks of all the vp proofs of the ptx.
+    pub fn get_vp_vks(&self) -> Vec<VerifyingKey<vesta::Affine>> {
+        self.inputs
+            .iter()
+            .chain(self.outputs.iter())
+            .flat_map(|vp_info_set| vp_info_set.iter().map(|vp_info| vp_info.vk.clone()))
+            .collect()
+    }
+
+    /// A hash of all the proofs of the ptx, in the order they are verified.
+    pub fn proofs_digest(&self) -> [u8; 32] {
+        let mut h = Blake2bParams::new()
+            .hash_length(32)
+            .personal(SHIELDED_PTX_PROOFS_DIGEST_PERSONALIZATION)
+            .to_state();
+        let proofs = self
+            .compliances
+            .iter()
+            .map(|compliance| &compliance.compliance_proof)
+            .chain(
+                self.inputs
+                    .iter()
+                    .chain(self.outputs.iter())
+                    .flat_map(|vp_info_set| vp_info_set.iter().map(|vp_info| &vp_info.proof)),
+            );
+        for proof in proofs {
+            let bytes = proof.inner();
+            h.update(&(bytes.len() as u64).to_le_bytes());
+            h.update(&bytes);
+        }
+        h.finalize().as_bytes().try_into().unwrap()
+    }
 }
 
 impl ShieldedPartialTransactionProxy {
@@ -274,11 +400,12 @@ impl Executable for ShieldedPartialTransaction {
         Ok(())
     }
 
-    fn get_nullifiers(&self) -> Vec<Nullifier> {
-        self.compliances
+    fn get_nullifiers(&self) -> Result<Vec<Nullifier>, TransactionError> {
+        Ok(self
+            .compliances
             .iter()
             .map(|compliance| compliance.compliance_instance.nf)
-            .collect()
+            .collect())
     }
 
     fn get_output_cms(&self) -> Vec<ResourceCommitment> {
@@ -368,6 +495,65 @@ impl BorshDeserialize for ShieldedPartialTransaction {
     }
 }
 
+#[cfg(feature = "borsh")]
+impl ShieldedPartialTransaction {
+    /// Writes the ptx like borsh does, but the vp vks are replaced by their compressed vks.
+    pub fn serialize_compact<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        use byteorder::WriteBytesExt;
+        for compliance in self.compliances.iter() {
+            compliance.serialize(writer)?;
+        }
+
+        for vp_info_set in self.inputs.iter().chain(self.outputs.iter()) {
+            vp_info_set.serialize_compact(writer)?;
+        }
+
+        match self.binding_sig_r {
+            None => {
+                writer.write_u8(0)?;
+            }
+            Some(r) => {
+                writer.write_u8(1)?;
+                writer.write_all(&r.to_repr())?;
+            }
+        };
+
+        self.hints.serialize(writer)?;
+
+        Ok(())
+    }
+
+    /// Reads a ptx written by `serialize_compact`, resolving the vp vks in `registry`.
+    pub fn deserialize_compact<R: std::io::Read>(
+        reader: &mut R,
+        registry: &VPVerifyingKeyRegistry,
+    ) -> Result<Self, TransactionError> {
+        use byteorder::ReadBytesExt;
+        let compliances: Vec<_> = (0..NUM_RESOURCE)
+            .map(|_| ComplianceVerifyingInfo::deserialize_reader(reader))
+            .collect::<Result<_, _>>()?;
+        let inputs: Vec<_> = (0..NUM_RESOURCE)
+            .map(|_| ResourceVPVerifyingInfoSet::deserialize_compact(reader, registry))
+            .collect::<Result<_, _>>()?;
+        let outputs: Vec<_> = (0..NUM_RESOURCE)
+            .map(|_| ResourceVPVerifyingInfoSet::deserialize_compact(reader, registry))
+            .collect::<Result<_, _>>()?;
+        let binding_sig_r = if reader.read_u8()? == 0 {
+            None
+        } else {
+            Some(crate::utils::read_scalar_field(reader)?)
+        };
+        let hints = Vec::<u8>::deserialize_reader(reader)?;
+        Ok(ShieldedPartialTransaction {
+            compliances: compliances.try_into().unwrap(),
+            inputs: inputs.try_into().unwrap(),
+            outputs: outputs.try_into().unwrap(),
+            binding_sig_r,
+            hints,
+        })
+    }
+}
+
 #[cfg(feature = "nif")]
 impl Encoder for ShieldedPartialTransaction {
     fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
@@ -384,9 +570,140 @@ impl<'a> Decoder<'a> for ShieldedPartialTransaction {
     }
 }
 
+fn get_rcv_sum(compliances: &[ComplianceInfo]) -> pallas::Scalar {
+    compliances
+        .iter()
+        .fold(pallas::Scalar::zero(), |acc, compliance_info| {
+            acc + compliance_info.get_rcv()
+        })
+}
+
+// A proof of a ptx.
+enum ProvingTask<'a> {
+    Compliance(&'a ComplianceInfo),
+    Vp(&'a ValidityPredicate),
+    ByteCode(ValidityPredicateByteCode),
+}
+
+enum ProvingOutput {
+    Compliance(ComplianceVerifyingInfo),
+    Vp(VPVerifyingInfo),
+}
+
+impl ProvingTask<'_> {
+    fn run(self, mut rng: StdRng) -> Result<ProvingOutput, TransactionError> {
+        match self {
+            ProvingTask::Compliance(compliance_info) => Ok(ProvingOutput::Compliance(
+                ComplianceVerifyingInfo::create(compliance_info, rng)?,
+            )),
+            ProvingTask::Vp(vp) => vp
+                .get_verifying_info_with_rng(&mut rng)
+                .map(ProvingOutput::Vp),
+            ProvingTask::ByteCode(bytecode) => bytecode
+                .generate_proof_with_rng(&mut rng)
+                .map(ProvingOutput::Vp),
+        }
+    }
+}
+
+impl ProvingOutput {
+    fn into_compliance(self) -> ComplianceVerifyingInfo {
+        match self {
+            ProvingOutput::Compliance(verifying_info) => verifying_info,
+            ProvingOutput::Vp(_) => unreachable!("the compliance proofs are created first"),
+        }
+    }
+
+    fn into_vp(self) -> VPVerifyingInfo {
+        match self {
+            ProvingOutput::Vp(verifying_info) => verifying_info,
+            ProvingOutput::Compliance(_) => unreachable!("the vp proofs are created last"),
+        }
+    }
+}
+
+// Creates the proofs in the order of the tasks. Every task takes its randomness from its own rng,
+// seeded from the caller's rng in the order of the tasks. So the serial and the parallel builds
+// create the same ptx from the same rng.
+fn create_proofs<R: RngCore>(
+    tasks: Vec<ProvingTask<'_>>,
+    mut rng: R,
+) -> Result<Vec<ProvingOutput>, TransactionError> {
+    let rngs: Vec<_> = (0..tasks.len())
+        .map(|_| {
+            let mut seed = <StdRng as SeedableRng>::Seed::default();
+            rng.fill_bytes(&mut seed);
+            StdRng::from_seed(seed)
+        })
+        .collect();
+
+    #[cfg(not(feature = "parallel"))]
+    let proofs = tasks
+        .into_iter()
+        .zip(rngs)
+        .map(|(task, rng)| task.run(rng))
+        .collect();
+
+    // The tasks are shared by at most `available_parallelism` threads, every thread takes the next
+    // task until there are none left.
+    #[cfg(feature = "parallel")]
+    let proofs = {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        };
+        let thread_num = std::thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(tasks.len());
+        let tasks: Vec<_> = tasks
+            .into_iter()
+            .zip(rngs)
+            .map(|task| Mutex::new(Some(task)))
+            .collect();
+        let proofs: Vec<_> = tasks.iter().map(|_| Mutex::new(None)).collect();
+        let next_task = AtomicUsize::new(0);
+        std::thread::scope(|s| {
+            for _ in 0..thread_num {
+                s.spawn(|| loop {
+                    let i = next_task.fetch_add(1, Ordering::Relaxed);
+                    let Some(task) = tasks.get(i) else {
+                        break;
+                    };
+                    let (task, rng) = task.lock().unwrap().take().unwrap();
+                    *proofs[i].lock().unwrap() = Some(task.run(rng));
+                });
+            }
+        });
+        proofs
+            .into_iter()
+            .map(|proof| proof.into_inner().unwrap().unwrap())
+            .collect()
+    };
+
+    proofs
+}
+
+// Groups the vp proofs by resource, `vp_nums` being the number of vps of every resource.
+fn collect_vp_proofs(
+    proofs: &mut impl Iterator<Item = ProvingOutput>,
+    vp_nums: &[usize],
+) -> Vec<ResourceVPVerifyingInfoSet> {
+    vp_nums
+        .iter()
+        .map(|vp_num| {
+            let mut vp_proofs = proofs.by_ref().take(*vp_num).map(ProvingOutput::into_vp);
+            let app_vp_verifying_info = vp_proofs.next().unwrap();
+            ResourceVPVerifyingInfoSet::new(app_vp_verifying_info, vp_proofs.collect())
+        })
+        .collect()
+}
+
 impl ComplianceVerifyingInfo {
-    pub fn create<R: RngCore>(compliance_info: &ComplianceInfo, mut rng: R) -> Result<Self, Error> {
-        let (compliance_instance, circuit) = compliance_info.build();
+    pub fn create<R: RngCore>(
+        compliance_info: &ComplianceInfo,
+        mut rng: R,
+    ) -> Result<Self, TransactionError> {
+        let (compliance_instance, circuit) = compliance_info.build()?;
         let params = SETUP_PARAMS_MAP
             .get(&COMPLIANCE_CIRCUIT_PARAMS_SIZE)
             .unwrap();
@@ -413,15 +730,22 @@ impl ComplianceVerifyingInfo {
             &[&self.compliance_instance.to_instance()],
         )
     }
+
+    pub fn add_to_batch(&self, batch: &mut BatchProofVerifier) -> Result<(), Error> {
+        self.compliance_proof.add_to_batch(
+            batch,
+            &COMPLIANCE_VERIFYING_KEY,
+            &[&self.compliance_instance.to_instance()],
+        )
+    }
 }
 
 impl ResourceVPVerifyingInfoSet {
+    /// The dynamic vps are in the order of the dynamic vp list the app vp commits to.
     pub fn new(
         app_vp_verifying_info: VPVerifyingInfo,
         app_dynamic_vp_verifying_info: Vec<VPVerifyingInfo>,
     ) -> Self {
-        assert!(app_dynamic_vp_verifying_info.len() <= MAX_DYNAMIC_VP_NUM);
-
         Self {
             app_vp_verifying_info,
             app_dynamic_vp_verifying_info,
@@ -432,20 +756,18 @@ impl ResourceVPVerifyingInfoSet {
     pub fn build(
         application_vp: Box<ValidityPredicate>,
         dynamic_vps: Vec<Box<ValidityPredicate>>,
-    ) -> Self {
-        assert!(dynamic_vps.len() <= MAX_DYNAMIC_VP_NUM);
-
-        let app_vp_verifying_info = application_vp.get_verifying_info();
+    ) -> Result<Self, TransactionError> {
+        let app_vp_verifying_info = application_vp.get_verifying_info()?;
 
         let app_dynamic_vp_verifying_info = dynamic_vps
             .into_iter()
             .map(|verifying_info| verifying_info.get_verifying_info())
-            .collect();
+            .collect::<Result<_, _>>()?;
 
-        Self {
+        Ok(Self {
             app_vp_verifying_info,
             app_dynamic_vp_verifying_info,
-        }
+        })
     }
 
     pub fn verify(&self) -> Result<(), Error> {
@@ -462,6 +784,61 @@ impl ResourceVPVerifyingInfoSet {
         Ok(())
     }
 
+    pub fn add_to_batch(&self, batch: &mut BatchProofVerifier) -> Result<(), Error> {
+        self.app_vp_verifying_info.add_to_batch(batch)?;
+        for verify_info in self.app_dynamic_vp_verifying_info.iter() {
+            verify_info.add_to_batch(batch)?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "borsh")]
+    fn serialize_compact<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.app_vp_verifying_info.serialize_compact(writer)?;
+        (self.app_dynamic_vp_verifying_info.len() as u32).serialize(writer)?;
+        for vp_info in self.app_dynamic_vp_verifying_info.iter() {
+            vp_info.serialize_compact(writer)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "borsh")]
+    fn deserialize_compact<R: std::io::Read>(
+        reader: &mut R,
+        registry: &VPVerifyingKeyRegistry,
+    ) -> Result<Self, TransactionError> {
+        let app_vp_verifying_info = VPVerifyingInfo::deserialize_compact(reader, registry)?;
+        let dynamic_vp_num = u32::deserialize_reader(reader)?;
+        let app_dynamic_vp_verifying_info = (0..dynamic_vp_num)
+            .map(|_| VPVerifyingInfo::deserialize_compact(reader, registry))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            app_vp_verifying_info,
+            app_dynamic_vp_verifying_info,
+        })
+    }
+
+    // The app vp verifying info followed by the dynamic vp ones.
+    fn iter(&self) -> impl Iterator<Item = &VPVerifyingInfo> {
+        std::iter::once(&self.app_vp_verifying_info)
+            .chain(self.app_dynamic_vp_verifying_info.iter())
+    }
+
+    /// Checks the dynamic vp commitment of the app vp opens to the vks of the dynamic vps.
+    pub fn check_dynamic_vps(&self) -> Result<(), TransactionError> {
+        let vps: Vec<_> = self
+            .app_dynamic_vp_verifying_info
+            .iter()
+            .map(|vp_info| {
+                ValidityPredicateVerifyingKey::from_vk(vp_info.vk.clone()).get_compressed()
+            })
+            .collect();
+        self.app_vp_verifying_info
+            .public_inputs
+            .check_dynamic_vps(&vps)
+    }
+
     pub fn get_nullifiers(&self) -> Vec<[pallas::Base; NUM_RESOURCE]> {
         let mut nfs = vec![self.app_vp_verifying_info.get_nullifiers()];
         self.app_dynamic_vp_verifying_info
@@ -482,37 +859,37 @@ impl ResourceVPVerifyingInfoSet {
 #[cfg(test)]
 pub mod testing {
     use crate::{
-        circuit::vp_circuit::{ValidityPredicate, ValidityPredicateVerifyingInfo},
+        circuit::vp_circuit::ValidityPredicateVerifyingInfo,
         circuit::vp_examples::TrivialValidityPredicateCircuit,
         compliance::ComplianceInfo,
-        constant::TAIGA_COMMITMENT_TREE_DEPTH,
-        merkle_tree::MerklePath,
+        ledger::{tests::commit_resources, InMemoryLedgerState},
         nullifier::Nullifier,
         resource::{Resource, ResourceValidityPredicates},
         shielded_ptx::ShieldedPartialTransaction,
-        utils::poseidon_hash,
     };
     use halo2_proofs::arithmetic::Field;
     use pasta_curves::pallas;
     use rand::rngs::OsRng;
 
     pub fn create_shielded_ptx() -> ShieldedPartialTransaction {
+        create_shielded_ptx_in(&mut InMemoryLedgerState::new())
+    }
+
+    /// Creates a ptx spending resources committed to `state`.
+    pub fn create_shielded_ptx_in(state: &mut InMemoryLedgerState) -> ShieldedPartialTransaction {
         let mut rng = OsRng;
 
         // Create empty VP circuit without resource info
         let trivial_vp_circuit = TrivialValidityPredicateCircuit::default();
-        let trivial_vp_vk = trivial_vp_circuit.get_vp_vk();
+        let trivial_vp_vk = trivial_vp_circuit.get_vp_vk().unwrap();
         let compressed_trivial_vp_vk = trivial_vp_vk.get_compressed();
 
         // Generate resources
         let input_resource_1 = {
             let label = pallas::Base::zero();
-            // TODO: add real application dynamic VPs and encode them to value later.
-            let app_dynamic_vp_vk = [compressed_trivial_vp_vk, compressed_trivial_vp_vk];
-            // Encode the app_dynamic_vp_vk into value
-            // The encoding method is flexible and defined in the application vp.
-            // Use poseidon hash to encode the two dynamic VPs here
-            let value = poseidon_hash(app_dynamic_vp_vk[0], app_dynamic_vp_vk[1]);
+            // The trivial vp doesn't commit to any dynamic VPs, see the token vp for an
+            // application vp encoding its dynamic VPs into value.
+            let value = pallas::Base::zero();
             let nonce = Nullifier::from(pallas::Base::random(&mut rng));
             let quantity = 5000u64;
             let nk = pallas::Base::random(&mut rng);
@@ -549,16 +926,6 @@ pub mod testing {
             )
         };
 
-        // Construct compliance pair
-        let merkle_path_1 = MerklePath::random(&mut rng, TAIGA_COMMITMENT_TREE_DEPTH);
-        let compliance_1 = ComplianceInfo::new(
-            input_resource_1,
-            merkle_path_1,
-            None,
-            &mut output_resource_1,
-            &mut rng,
-        );
-
         // Generate resources
         let input_resource_2 = {
             let label = pallas::Base::one();
@@ -597,11 +964,18 @@ pub mod testing {
             )
         };
 
-        // Construct compliance pair
-        let merkle_path_2 = MerklePath::random(&mut rng, TAIGA_COMMITMENT_TREE_DEPTH);
+        // Commit the input resources to the ledger state and construct compliance pairs
+        let merkle_paths = commit_resources(state, &[input_resource_1, input_resource_2]);
+        let compliance_1 = ComplianceInfo::new(
+            input_resource_1,
+            merkle_paths[0].clone(),
+            None,
+            &mut output_resource_1,
+            &mut rng,
+        );
         let compliance_2 = ComplianceInfo::new(
             input_resource_2,
-            merkle_path_2,
+            merkle_paths[1].clone(),
             None,
             &mut output_resource_2,
             &mut rng,
@@ -614,11 +988,7 @@ pub mod testing {
             output_resources: [output_resource_1, output_resource_2],
         };
         let input_application_vp_1 = Box::new(trivial_vp_circuit.clone());
-        let trivial_app_logic_1: Box<ValidityPredicate> = Box::new(trivial_vp_circuit.clone());
-        let trivial_app_logic_2 = Box::new(trivial_vp_circuit.clone());
-        let trivial_dynamic_vps = vec![trivial_app_logic_1, trivial_app_logic_2];
-        let input_resource_1_vps =
-            ResourceValidityPredicates::new(input_application_vp_1, trivial_dynamic_vps);
+        let input_resource_1_vps = ResourceValidityPredicates::new(input_application_vp_1, vec![]);
 
         // The following resources use empty logic vps and use value with pallas::Base::zero() by default.
         trivial_vp_circuit.owned_resource_id = input_resource_2.get_nf().unwrap().inner();
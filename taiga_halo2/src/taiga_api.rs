@@ -1,7 +1,10 @@
 #[cfg(feature = "borsh")]
 use crate::{
-    circuit::vp_bytecode::ApplicationByteCode, compliance::ComplianceInfo,
-    transaction::TransactionResult,
+    circuit::vp_bytecode::ApplicationByteCode,
+    compliance::ComplianceInfo,
+    constant::NUM_RESOURCE,
+    transaction::{ExecutionContext, TransactionResult},
+    vp_vk::VPVerifyingKeyRegistry,
 };
 use crate::{
     error::TransactionError,
@@ -16,8 +19,40 @@ use rand::rngs::OsRng;
 
 pub const RESOURCE_SIZE: usize = 202;
 
+/// The serialized resources, ptxs and transactions are wrapped in an envelope:
+///
+/// Envelope layout:
+/// | Parameters            | type          |size(bytes)|
+/// |   -                   |   -           |   -       |
+/// |   magic               | [u8; 4]       |   4       |
+/// |   format version      | u16           |   2       |
+/// |   type tag            | u8            |   1       |
+/// |   NUM_RESOURCE        | u8            |   1       |
+/// |   payload             | -             |   -       |
+///
+/// Decoders accept the format versions from `WIRE_FORMAT_MIN_VERSION` to `WIRE_FORMAT_VERSION`.
+/// The raw borsh blobs written before the envelope existed are not supported: they have no header
+/// to tell the object type and NUM_RESOURCE apart, and their layouts changed since.
+pub const WIRE_MAGIC: &[u8; 4] = b"TAIG";
+pub const WIRE_FORMAT_VERSION: u16 = 1;
+pub const WIRE_FORMAT_MIN_VERSION: u16 = 1;
+pub const WIRE_HEADER_SIZE: usize = 8;
+
+/// The type tag of a serialized object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum WireType {
+    Resource = 1,
+    ShieldedPartialTransaction = 2,
+    Transaction = 3,
+    CompactShieldedPartialTransaction = 4,
+    CompactTransaction = 5,
+}
+
 #[cfg(feature = "borsh")]
 use borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "borsh")]
+use std::io;
 
 /// Create a resource
 /// logic is a hash of a predicate associated with the resource
@@ -69,7 +104,7 @@ pub fn create_output_resource(
 
 /// Resource borsh serialization
 ///
-/// Resource size: 202 bytes
+/// Resource size: 202 bytes, after the 8 bytes envelope header
 ///
 /// Resource layout:
 /// |   Parameters          | type          |size(bytes)|
@@ -85,7 +120,8 @@ pub fn create_output_resource(
 /// |   rseed               | pallas::Base  |   32      |
 #[cfg(feature = "borsh")]
 pub fn resource_serialize(resource: &Resource) -> std::io::Result<Vec<u8>> {
-    let mut result = Vec::with_capacity(RESOURCE_SIZE);
+    let mut result = Vec::with_capacity(WIRE_HEADER_SIZE + RESOURCE_SIZE);
+    write_header(WireType::Resource, &mut result);
     resource.serialize(&mut result)?;
     Ok(result)
 }
@@ -93,13 +129,14 @@ pub fn resource_serialize(resource: &Resource) -> std::io::Result<Vec<u8>> {
 /// Resource borsh deserialization
 #[cfg(feature = "borsh")]
 pub fn resource_deserialize(bytes: Vec<u8>) -> std::io::Result<Resource> {
-    if bytes.len() != RESOURCE_SIZE {
+    let (version, payload) = read_header(&bytes, WireType::Resource)?;
+    if payload.len() != RESOURCE_SIZE {
         return Err(std::io::Error::new(
             std::io::ErrorKind::InvalidData,
             "incorrect resource size",
         ));
     }
-    BorshDeserialize::deserialize(&mut bytes.as_ref())
+    decode_payload(version, payload, BorshDeserialize::deserialize)
 }
 
 /// Shielded Partial Transaction borsh serialization
@@ -128,7 +165,10 @@ pub fn resource_deserialize(bytes: Vec<u8>) -> std::io::Result<Resource> {
 /// is a constant and can be cached.
 #[cfg(feature = "borsh")]
 pub fn partial_transaction_serialize(ptx: &ShieldedPartialTransaction) -> std::io::Result<Vec<u8>> {
-    borsh::to_vec(&ptx)
+    let mut result = vec![];
+    write_header(WireType::ShieldedPartialTransaction, &mut result);
+    ptx.serialize(&mut result)?;
+    Ok(result)
 }
 
 /// Shielded Partial Transaction borsh deserialization
@@ -136,7 +176,8 @@ pub fn partial_transaction_serialize(ptx: &ShieldedPartialTransaction) -> std::i
 pub fn partial_transaction_deserialize(
     bytes: Vec<u8>,
 ) -> std::io::Result<ShieldedPartialTransaction> {
-    BorshDeserialize::deserialize(&mut bytes.as_ref())
+    let (version, payload) = read_header(&bytes, WireType::ShieldedPartialTransaction)?;
+    decode_payload(version, payload, BorshDeserialize::deserialize)
 }
 
 /// Transaction borsh serialization
@@ -145,19 +186,158 @@ pub fn partial_transaction_deserialize(
 /// | Parameters                                                | type                          | size(bytes)|
 /// |                   -                                       |       -                       |   -   |
 /// | shielded_ptx_bundle(a list of shielded ptx)               | ShieldedPartialTxBundle       | -     |
-/// | TODO: transparent_ptx_bundle(a list of transparent ptx)   | TransparentPartialTxBundle    | -     |
+/// | transparent_ptx_bundle(a list of transparent ptx)         | TransparentPartialTxBundle    | -     |
+/// | public_delta(a list of public quantities, e.g. fees)      | Vec<PublicDelta>              | 4 + 72 * num |
+/// | chain_id                                                  | u64                           | 8     |
+/// | expiry_height                                             | Option<u64>                   | 1 or 9 |
 /// | signature                                                 | BindingSignature              | 32    |
 ///
 #[cfg(feature = "borsh")]
 pub fn transaction_serialize(tx: &Transaction) -> std::io::Result<Vec<u8>> {
-    borsh::to_vec(&tx)
+    let mut result = vec![];
+    write_header(WireType::Transaction, &mut result);
+    tx.serialize(&mut result)?;
+    Ok(result)
 }
 
 /// Transaction borsh deserialization
 ///
 #[cfg(feature = "borsh")]
 pub fn transaction_deserialize(bytes: Vec<u8>) -> std::io::Result<Transaction> {
-    BorshDeserialize::deserialize(&mut bytes.as_ref())
+    let (version, payload) = read_header(&bytes, WireType::Transaction)?;
+    decode_payload(version, payload, BorshDeserialize::deserialize)
+}
+
+/// Compact shielded partial transaction serialization
+///
+/// The layout is the one of `partial_transaction_serialize`, except that every VPVerifyingInfo
+/// has the compressed vk instead of the vk:
+/// | Parameters                        | type                  | size(bytes)   |
+/// |       -                           |       -               |   -           |
+/// | compressed vk                     | pallas::Base          | 32            |
+/// | proof                             | Proof                 | -             |
+/// | public inputs                     | pallas::Base          | 32 * num      |
+///
+/// The verifier resolves the compressed vks through its `VPVerifyingKeyRegistry`.
+#[cfg(feature = "borsh")]
+pub fn partial_transaction_serialize_compact(
+    ptx: &ShieldedPartialTransaction,
+) -> std::io::Result<Vec<u8>> {
+    let mut result = vec![];
+    write_header(WireType::CompactShieldedPartialTransaction, &mut result);
+    ptx.serialize_compact(&mut result)?;
+    Ok(result)
+}
+
+/// Compact shielded partial transaction deserialization. It fails with
+/// `TransactionError::UnknownVerifyingKey` if a vk is not in the registry.
+#[cfg(feature = "borsh")]
+pub fn partial_transaction_deserialize_compact(
+    bytes: Vec<u8>,
+    registry: &VPVerifyingKeyRegistry,
+) -> Result<ShieldedPartialTransaction, TransactionError> {
+    let (version, payload) = read_header(&bytes, WireType::CompactShieldedPartialTransaction)?;
+    decode_payload(version, payload, |reader| {
+        ShieldedPartialTransaction::deserialize_compact(reader, registry)
+    })
+}
+
+/// Compact transaction serialization: the layout of `transaction_serialize` with the shielded
+/// ptxs written compactly.
+#[cfg(feature = "borsh")]
+pub fn transaction_serialize_compact(tx: &Transaction) -> std::io::Result<Vec<u8>> {
+    let mut result = vec![];
+    write_header(WireType::CompactTransaction, &mut result);
+    tx.serialize_compact(&mut result)?;
+    Ok(result)
+}
+
+/// Compact transaction deserialization. It fails with `TransactionError::UnknownVerifyingKey`
+/// if a vk is not in the registry.
+#[cfg(feature = "borsh")]
+pub fn transaction_deserialize_compact(
+    bytes: Vec<u8>,
+    registry: &VPVerifyingKeyRegistry,
+) -> Result<Transaction, TransactionError> {
+    let (version, payload) = read_header(&bytes, WireType::CompactTransaction)?;
+    decode_payload(version, payload, |reader| {
+        Transaction::deserialize_compact(reader, registry)
+    })
+}
+
+#[cfg(feature = "borsh")]
+fn check_no_trailing_bytes(bytes: &[u8]) -> io::Result<()> {
+    if bytes.is_empty() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} unexpected bytes after the object", bytes.len()),
+        ))
+    }
+}
+
+#[cfg(feature = "borsh")]
+fn write_header(wire_type: WireType, bytes: &mut Vec<u8>) {
+    bytes.extend_from_slice(WIRE_MAGIC);
+    bytes.extend_from_slice(&WIRE_FORMAT_VERSION.to_le_bytes());
+    bytes.push(wire_type as u8);
+    bytes.push(NUM_RESOURCE as u8);
+}
+
+// Checks the envelope of an object of type `wire_type` and returns its format version and payload.
+#[cfg(feature = "borsh")]
+fn read_header(bytes: &[u8], wire_type: WireType) -> io::Result<(u16, &[u8])> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+    if bytes.len() < WIRE_HEADER_SIZE || &bytes[..4] != WIRE_MAGIC {
+        return Err(invalid(
+            "not a taiga object, the magic bytes are missing (raw borsh from before the \
+             versioned envelope is not supported)"
+                .into(),
+        ));
+    }
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if !(WIRE_FORMAT_MIN_VERSION..=WIRE_FORMAT_VERSION).contains(&version) {
+        return Err(invalid(format!(
+            "unsupported format version {version}, the supported versions are \
+             {WIRE_FORMAT_MIN_VERSION} to {WIRE_FORMAT_VERSION}"
+        )));
+    }
+    if bytes[6] != wire_type as u8 {
+        return Err(invalid(format!(
+            "expected a {wire_type:?} (type tag {}), found type tag {}",
+            wire_type as u8, bytes[6]
+        )));
+    }
+    if bytes[7] as usize != NUM_RESOURCE {
+        return Err(invalid(format!(
+            "the object has {} resources per ptx, expected {NUM_RESOURCE}",
+            bytes[7]
+        )));
+    }
+    Ok((version, &bytes[WIRE_HEADER_SIZE..]))
+}
+
+// Decodes the payload of the given format version, `decode` reads the current layout. When a
+// layout changes, the decoding of the older versions is dispatched here.
+#[cfg(feature = "borsh")]
+fn decode_payload<T, E: From<io::Error>>(
+    version: u16,
+    mut payload: &[u8],
+    decode: impl FnOnce(&mut &[u8]) -> Result<T, E>,
+) -> Result<T, E> {
+    let object = match version {
+        1 => decode(&mut payload)?,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("no decoder for format version {version}"),
+            )
+            .into())
+        }
+    };
+    check_no_trailing_bytes(payload)?;
+    Ok(object)
 }
 
 /// Create a shielded partial transaction from vp bytecode
@@ -179,20 +359,30 @@ pub fn create_shielded_partial_transaction(
 }
 
 /// Create a transaction from partial transactions
+/// The transaction can only be executed on `chain_id`, and not after `expiry_height` if set.
 ///
 pub fn create_transaction(
     shielded_ptxs: Vec<ShieldedPartialTransaction>,
     // TODO: add transparent_ptxs
     // transparent_ptxs: Vec<TransparentPartialTransaction>,
+    chain_id: u64,
+    expiry_height: Option<u64>,
 ) -> Result<Transaction, TransactionError> {
     let rng = OsRng;
     let shielded_ptx_bundle = ShieldedPartialTxBundle::new(shielded_ptxs);
     // empty transparent_ptx_bundle
     let transparent_ptx_bundle = TransparentPartialTxBundle::default();
-    Transaction::build(rng, shielded_ptx_bundle, transparent_ptx_bundle)
+    Transaction::build(
+        rng,
+        shielded_ptx_bundle,
+        transparent_ptx_bundle,
+        vec![],
+        chain_id,
+        expiry_height,
+    )
 }
 
-/// Verify a transaction and return the results
+/// Verify a transaction against the current chain id and height and return the results
 ///
 /// TransactionResult layout:
 /// | Parameters     | type         | size(bytes)|
@@ -203,14 +393,44 @@ pub fn create_transaction(
 /// | nullifiers     | pallas::Base | 32 * num   |
 /// | output cm num  | u32          | 4          |
 /// | output cms     | pallas::Base | 32 * num   |
+/// | public delta num | u32        | 4          |
+/// | public deltas  | PublicDelta  | 72 * num   |
 ///
 #[cfg(feature = "borsh")]
-pub fn verify_transaction(tx_bytes: Vec<u8>) -> Result<TransactionResult, TransactionError> {
+pub fn verify_transaction(
+    tx_bytes: Vec<u8>,
+    chain_id: u64,
+    height: u64,
+) -> Result<TransactionResult, TransactionError> {
     // Decode the tx
     let tx = transaction_deserialize(tx_bytes)?;
 
     // Verify the tx
-    tx.execute()
+    tx.execute(&ExecutionContext { chain_id, height })
+}
+
+/// Verify a compactly encoded transaction, resolving the vp vks in `registry`
+///
+#[cfg(feature = "borsh")]
+pub fn verify_compact_transaction(
+    tx_bytes: Vec<u8>,
+    registry: &VPVerifyingKeyRegistry,
+    chain_id: u64,
+    height: u64,
+) -> Result<TransactionResult, TransactionError> {
+    let tx = transaction_deserialize_compact(tx_bytes, registry)?;
+    tx.execute(&ExecutionContext { chain_id, height })
+}
+
+/// Verify a compactly encoded shielded partial transaction, resolving the vp vks in `registry`
+///
+#[cfg(feature = "borsh")]
+pub fn verify_compact_shielded_partial_transaction(
+    ptx_bytes: Vec<u8>,
+    registry: &VPVerifyingKeyRegistry,
+) -> Result<(), TransactionError> {
+    let ptx = partial_transaction_deserialize_compact(ptx_bytes, registry)?;
+    ptx.verify_proof()
 }
 
 /// Verify a shielded transaction
@@ -252,13 +472,39 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn wire_envelope_test() {
+        let mut rng = OsRng;
+        let bytes = resource_serialize(&random_resource(&mut rng)).unwrap();
+        assert_eq!(&bytes[..4], WIRE_MAGIC);
+        assert!(resource_deserialize(bytes.clone()).is_ok());
+
+        // Raw borsh without the envelope
+        let err = resource_deserialize(bytes[WIRE_HEADER_SIZE..].to_vec()).unwrap_err();
+        assert!(err.to_string().contains("raw borsh"));
+
+        // Unknown version
+        let mut unknown_version = bytes.clone();
+        unknown_version[4..6].copy_from_slice(&(WIRE_FORMAT_VERSION + 1).to_le_bytes());
+        let err = resource_deserialize(unknown_version).unwrap_err();
+        assert!(err.to_string().contains("unsupported format version"));
+
+        // Wrong type
+        let err = transaction_deserialize(bytes.clone()).unwrap_err();
+        assert!(err.to_string().contains("expected a Transaction"));
+
+        // Different NUM_RESOURCE
+        let mut other_num_resource = bytes;
+        other_num_resource[7] += 1;
+        assert!(resource_deserialize(other_num_resource).is_err());
+    }
+
     // #[ignore]
     #[test]
     fn ptx_example_test() {
         use crate::circuit::vp_examples::TrivialValidityPredicateCircuit;
         use crate::compliance::ComplianceInfo;
-        use crate::constant::TAIGA_COMMITMENT_TREE_DEPTH;
-        use crate::merkle_tree::MerklePath;
+        use crate::ledger::{tests::commit_resources, InMemoryLedgerState};
         use crate::resource::tests::random_resource;
 
         let mut rng = OsRng;
@@ -267,22 +513,25 @@ pub mod tests {
         let input_resource_1 = random_resource(&mut rng);
         let input_resource_1_nf = input_resource_1.get_nf().unwrap();
         let mut output_resource_1 = random_resource(&mut rng);
-        let merkle_path_1 = MerklePath::random(&mut rng, TAIGA_COMMITMENT_TREE_DEPTH);
+        let input_resource_2 = random_resource(&mut rng);
+        let input_resource_2_nf = input_resource_2.get_nf().unwrap();
+        let mut output_resource_2 = random_resource(&mut rng);
+
+        // commit the input resources to the ledger state
+        let merkle_paths = commit_resources(
+            &mut InMemoryLedgerState::new(),
+            &[input_resource_1, input_resource_2],
+        );
         let compliance_1 = ComplianceInfo::new(
             input_resource_1,
-            merkle_path_1,
+            merkle_paths[0].clone(),
             None,
             &mut output_resource_1,
             &mut rng,
         );
-
-        let input_resource_2 = random_resource(&mut rng);
-        let input_resource_2_nf = input_resource_2.get_nf().unwrap();
-        let mut output_resource_2 = random_resource(&mut rng);
-        let merkle_path_2 = MerklePath::random(&mut rng, TAIGA_COMMITMENT_TREE_DEPTH);
         let compliance_2 = ComplianceInfo::new(
             input_resource_2,
-            merkle_path_2,
+            merkle_paths[1].clone(),
             None,
             &mut output_resource_2,
             &mut rng,
@@ -339,6 +588,19 @@ pub mod tests {
         .unwrap();
 
         let ptx_bytes = partial_transaction_serialize(&ptx).unwrap();
-        verify_shielded_partial_transaction(ptx_bytes).unwrap();
+        verify_shielded_partial_transaction(ptx_bytes.clone()).unwrap();
+
+        // The compact encoding only works with the vks in the registry.
+        let compact_ptx_bytes = partial_transaction_serialize_compact(&ptx).unwrap();
+        assert!(compact_ptx_bytes.len() < ptx_bytes.len());
+        let mut registry = VPVerifyingKeyRegistry::new();
+        assert!(matches!(
+            verify_compact_shielded_partial_transaction(compact_ptx_bytes.clone(), &registry),
+            Err(TransactionError::UnknownVerifyingKey(_))
+        ));
+        for vk in ptx.get_vp_vks() {
+            registry.register(vk);
+        }
+        verify_compact_shielded_partial_transaction(compact_ptx_bytes, &registry).unwrap();
     }
 }
@@ -1,16 +1,22 @@
 use crate::binding_signature::{BindingSignature, BindingSigningKey, BindingVerificationKey};
-use crate::constant::TRANSACTION_BINDING_HASH_PERSONALIZATION;
-use crate::delta_commitment::DeltaCommitment;
+use crate::constant::{
+    PARAMS_SIZE, SETUP_PARAMS_MAP, TRANSACTION_AUTH_DIGEST_PERSONALIZATION,
+    TRANSACTION_BINDING_HASH_PERSONALIZATION, TRANSACTION_ID_PERSONALIZATION,
+};
+use crate::delta_commitment::{DeltaCommitment, PublicDelta};
 use crate::error::TransactionError;
 use crate::executable::Executable;
+use crate::ledger::LedgerState;
 use crate::merkle_tree::Anchor;
 use crate::nullifier::Nullifier;
+use crate::proof::BatchProofVerifier;
 use crate::resource::ResourceCommitment;
 use crate::shielded_ptx::ShieldedPartialTransaction;
 use crate::transparent_ptx::TransparentPartialTransaction;
 use blake2b_simd::Params as Blake2bParams;
 use pasta_curves::{group::Group, pallas};
 use rand::{CryptoRng, RngCore};
+use std::collections::HashMap;
 
 #[cfg(feature = "nif")]
 use rustler::{atoms, types::atom, Decoder, Env, NifRecord, NifResult, NifStruct, Term};
@@ -18,6 +24,8 @@ use rustler::{atoms, types::atom, Decoder, Env, NifRecord, NifResult, NifStruct,
 #[cfg(feature = "serde")]
 use serde;
 
+#[cfg(feature = "borsh")]
+use crate::vp_vk::VPVerifyingKeyRegistry;
 #[cfg(feature = "borsh")]
 use borsh::{BorshDeserialize, BorshSerialize};
 
@@ -26,10 +34,16 @@ use borsh::{BorshDeserialize, BorshSerialize};
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Transaction {
     // TODO: Other parameters to be added.
-    shielded_ptx_bundle: ShieldedPartialTxBundle,
-    transparent_ptx_bundle: TransparentPartialTxBundle,
+    pub(crate) shielded_ptx_bundle: ShieldedPartialTxBundle,
+    pub(crate) transparent_ptx_bundle: TransparentPartialTxBundle,
+    // quantities balanced in the clear, e.g. fees
+    pub(crate) public_delta: Vec<PublicDelta>,
+    // the chain the transaction can be executed on
+    pub(crate) chain_id: u64,
+    // the last height the transaction can be executed at, if any
+    pub(crate) expiry_height: Option<u64>,
     // binding signature to check balance
-    signature: BindingSignature,
+    pub(crate) signature: BindingSignature,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -41,6 +55,21 @@ pub struct TransactionResult {
     pub anchors: Vec<Anchor>,
     pub nullifiers: Vec<Nullifier>,
     pub output_cms: Vec<ResourceCommitment>,
+    pub public_delta: Vec<PublicDelta>,
+}
+
+/// The state of the chain a transaction is executed in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExecutionContext {
+    pub chain_id: u64,
+    pub height: u64,
+}
+
+/// The position of a partial transaction in a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartialTxIndex {
+    Shielded(usize),
+    Transparent(usize),
 }
 
 #[derive(Debug, Clone, Default)]
@@ -48,39 +77,157 @@ pub struct TransactionResult {
 #[cfg_attr(feature = "nif", tag = "bundle")]
 #[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-pub struct ShieldedPartialTxBundle(Vec<ShieldedPartialTransaction>);
+pub struct ShieldedPartialTxBundle(pub(crate) Vec<ShieldedPartialTransaction>);
 
 #[derive(Debug, Clone, Default)]
 #[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-pub struct TransparentPartialTxBundle(Vec<TransparentPartialTransaction>);
+pub struct TransparentPartialTxBundle(pub(crate) Vec<TransparentPartialTransaction>);
 
 impl Transaction {
     // Generate the transaction
+    // The ptxs must balance up to `public_delta`: a positive quantity of a kind is consumed by
+    // the ptxs without being created, e.g. a fee, and a negative one is created without being
+    // consumed.
+    // The transaction can only be executed on `chain_id`, and not after `expiry_height` if set.
     pub fn build<R: RngCore + CryptoRng>(
         rng: R,
         mut shielded_ptx_bundle: ShieldedPartialTxBundle,
         transparent_ptx_bundle: TransparentPartialTxBundle,
+        public_delta: Vec<PublicDelta>,
+        chain_id: u64,
+        expiry_height: Option<u64>,
     ) -> Result<Self, TransactionError> {
         assert!(!(shielded_ptx_bundle.is_empty() && transparent_ptx_bundle.is_empty()));
         let shielded_sk = shielded_ptx_bundle.get_binding_sig_r()?;
         let binding_sk = BindingSigningKey::from(shielded_sk);
-        let sig_hash = Self::digest(&shielded_ptx_bundle, &transparent_ptx_bundle);
+        let sig_hash = Self::digest(
+            &shielded_ptx_bundle,
+            &transparent_ptx_bundle,
+            &public_delta,
+            chain_id,
+            expiry_height,
+        )?;
         let signature = binding_sk.sign(rng, &sig_hash);
         shielded_ptx_bundle.clean_private_info();
 
         Ok(Self {
             shielded_ptx_bundle,
             transparent_ptx_bundle,
+            public_delta,
+            chain_id,
+            expiry_height,
             signature,
         })
     }
 
+    pub fn get_public_delta(&self) -> &[PublicDelta] {
+        &self.public_delta
+    }
+
+    pub fn get_chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    pub fn get_expiry_height(&self) -> Option<u64> {
+        self.expiry_height
+    }
+
+    /// The id of the transaction, a hash of its public content: the binding signature digest,
+    /// which covers the public delta, the chain id and the expiry height, and the ids of the
+    /// shielded and transparent ptxs. The proofs and the binding signature are committed to by
+    /// `auth_digest`.
+    pub fn id(&self) -> Result<[u8; 32], TransactionError> {
+        let mut h = Blake2bParams::new()
+            .hash_length(32)
+            .personal(TRANSACTION_ID_PERSONALIZATION)
+            .to_state();
+        h.update(&Self::digest(
+            &self.shielded_ptx_bundle,
+            &self.transparent_ptx_bundle,
+            &self.public_delta,
+            self.chain_id,
+            self.expiry_height,
+        )?);
+        h.update(&(self.shielded_ptx_bundle.0.len() as u32).to_le_bytes());
+        self.shielded_ptx_bundle.0.iter().for_each(|ptx| {
+            h.update(&ptx.id());
+        });
+        h.update(&(self.transparent_ptx_bundle.0.len() as u32).to_le_bytes());
+        self.transparent_ptx_bundle.0.iter().for_each(|ptx| {
+            h.update(&ptx.id());
+        });
+        Ok(h.finalize().as_bytes().try_into().unwrap())
+    }
+
+    /// A hash of the proofs of the shielded ptxs and of the binding signature.
+    pub fn auth_digest(&self) -> [u8; 32] {
+        let mut h = Blake2bParams::new()
+            .hash_length(32)
+            .personal(TRANSACTION_AUTH_DIGEST_PERSONALIZATION)
+            .to_state();
+        self.shielded_ptx_bundle.0.iter().for_each(|ptx| {
+            h.update(&ptx.proofs_digest());
+        });
+        h.update(&self.signature.to_bytes());
+        h.finalize().as_bytes().try_into().unwrap()
+    }
+
     #[allow(clippy::type_complexity)]
-    pub fn execute(&self) -> Result<TransactionResult, TransactionError> {
-        let mut result = self.shielded_ptx_bundle.execute()?;
+    pub fn execute(&self, ctx: &ExecutionContext) -> Result<TransactionResult, TransactionError> {
+        self.check_context(ctx)?;
+        let result = self.shielded_ptx_bundle.execute()?;
+        self.execute_with_shielded_result(result)
+    }
+
+    /// Like `execute`, but verifies all the proofs of the transaction together with one
+    /// multi-scalar multiplication check.
+    pub fn verify_batched(
+        &self,
+        ctx: &ExecutionContext,
+    ) -> Result<TransactionResult, TransactionError> {
+        self.check_context(ctx)?;
+        let params = SETUP_PARAMS_MAP.get(&PARAMS_SIZE).unwrap();
+        let mut batch = BatchProofVerifier::new(params);
+        let result = self.shielded_ptx_bundle.execute_batched(&mut batch)?;
+        batch.finalize()?;
+        self.execute_with_shielded_result(result)
+    }
+
+    // Rejects the transaction if it is for another chain or has expired. The chain id and the
+    // expiry height themselves are authenticated by the binding signature.
+    fn check_context(&self, ctx: &ExecutionContext) -> Result<(), TransactionError> {
+        if self.chain_id != ctx.chain_id {
+            return Err(TransactionError::ChainIdMismatch(
+                self.chain_id,
+                ctx.chain_id,
+            ));
+        }
+        match self.expiry_height {
+            Some(expiry_height) if ctx.height > expiry_height => Err(
+                TransactionError::TransactionExpired(expiry_height, ctx.height),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    // Executes the transparent bundle and the checks across bundles, once the shielded bundle
+    // has been executed.
+    fn execute_with_shielded_result(
+        &self,
+        mut result: TransactionResult,
+    ) -> Result<TransactionResult, TransactionError> {
         let mut transparent_result = self.transparent_ptx_bundle.execute()?;
         result.append(&mut transparent_result);
+        result.public_delta = self.public_delta.clone();
+
+        // check the nullifiers and output cms are unique across both bundles
+        check_duplicates(
+            self.shielded_ptx_bundle
+                .get_ptx_outputs()?
+                .into_iter()
+                .chain(self.transparent_ptx_bundle.get_ptx_outputs()?),
+        )?;
 
         // check balance
         self.verify_binding_sig()?;
@@ -88,9 +235,28 @@ impl Transaction {
         Ok(result)
     }
 
+    /// Executes the transaction against the ledger state: rejects spent nullifiers and unknown
+    /// anchors, then applies the result to the state.
+    pub fn execute_against(
+        &self,
+        state: &mut impl LedgerState,
+        ctx: &ExecutionContext,
+    ) -> Result<TransactionResult, TransactionError> {
+        let result = self.execute(ctx)?;
+        state.validate(&result)?;
+        state.apply(&result)?;
+        Ok(result)
+    }
+
     fn verify_binding_sig(&self) -> Result<(), TransactionError> {
         let binding_vk = self.get_binding_vk();
-        let sig_hash = Self::digest(&self.shielded_ptx_bundle, &self.transparent_ptx_bundle);
+        let sig_hash = Self::digest(
+            &self.shielded_ptx_bundle,
+            &self.transparent_ptx_bundle,
+            &self.public_delta,
+            self.chain_id,
+            self.expiry_height,
+        )?;
         binding_vk
             .verify(&sig_hash, &self.signature)
             .map_err(|_| TransactionError::InvalidBindingSignature)
@@ -110,51 +276,111 @@ impl Transaction {
             .iter()
             .fold(vk, |acc, cv| acc + cv.inner());
 
+        vk = self
+            .public_delta
+            .iter()
+            .fold(vk, |acc, delta| acc - delta.commit().inner());
+
         BindingVerificationKey::from(vk)
     }
 
     fn digest(
         shielded_bundle: &ShieldedPartialTxBundle,
         transparent_bundle: &TransparentPartialTxBundle,
-    ) -> [u8; 32] {
+        public_delta: &[PublicDelta],
+        chain_id: u64,
+        expiry_height: Option<u64>,
+    ) -> Result<[u8; 32], TransactionError> {
         let mut h = Blake2bParams::new()
             .hash_length(32)
             .personal(TRANSACTION_BINDING_HASH_PERSONALIZATION)
             .to_state();
-        shielded_bundle.get_nullifiers().iter().for_each(|nf| {
-            h.update(&nf.to_bytes());
-        });
-        shielded_bundle.get_output_cms().iter().for_each(|cm| {
-            h.update(&cm.to_bytes());
-        });
-        shielded_bundle
-            .get_delta_commitments()
-            .iter()
-            .for_each(|vc| {
-                h.update(&vc.to_bytes());
-            });
-        shielded_bundle.get_anchors().iter().for_each(|anchor| {
-            h.update(&anchor.to_bytes());
-        });
+        // Every list is prefixed with its length, so that adjacent lists can't trade elements.
+        update_with_len(
+            &mut h,
+            &shielded_bundle.get_nullifiers()?,
+            Nullifier::to_bytes,
+        );
+        update_with_len(
+            &mut h,
+            &shielded_bundle.get_output_cms(),
+            ResourceCommitment::to_bytes,
+        );
+        update_with_len(
+            &mut h,
+            &shielded_bundle.get_delta_commitments(),
+            DeltaCommitment::to_bytes,
+        );
+        update_with_len(&mut h, &shielded_bundle.get_anchors(), Anchor::to_bytes);
 
         // TODO: the transparent digest may be not reasonable, fix it once the transparent execution is nailed down.
-        transparent_bundle.get_nullifiers().iter().for_each(|nf| {
-            h.update(&nf.to_bytes());
-        });
-        transparent_bundle.get_output_cms().iter().for_each(|cm| {
-            h.update(&cm.to_bytes());
-        });
-        transparent_bundle
-            .get_delta_commitments()
-            .iter()
-            .for_each(|vc| {
-                h.update(&vc.to_bytes());
-            });
-        transparent_bundle.get_anchors().iter().for_each(|anchor| {
-            h.update(&anchor.to_bytes());
-        });
+        update_with_len(
+            &mut h,
+            &transparent_bundle.get_nullifiers()?,
+            Nullifier::to_bytes,
+        );
+        update_with_len(
+            &mut h,
+            &transparent_bundle.get_output_cms(),
+            ResourceCommitment::to_bytes,
+        );
+        update_with_len(
+            &mut h,
+            &transparent_bundle.get_delta_commitments(),
+            DeltaCommitment::to_bytes,
+        );
+        update_with_len(&mut h, &transparent_bundle.get_anchors(), Anchor::to_bytes);
 
-        h.finalize().as_bytes().try_into().unwrap()
+        update_with_len(&mut h, public_delta, PublicDelta::to_bytes);
+
+        h.update(&chain_id.to_le_bytes());
+        match expiry_height {
+            Some(height) => {
+                h.update(&[1]);
+                h.update(&height.to_le_bytes());
+            }
+            None => {
+                h.update(&[0]);
+            }
+        }
+
+        Ok(h.finalize().as_bytes().try_into().unwrap())
+    }
+}
+
+#[cfg(feature = "borsh")]
+impl Transaction {
+    /// Writes the transaction like borsh does, but the shielded ptxs are written compactly.
+    /// See `ShieldedPartialTransaction::serialize_compact`.
+    pub fn serialize_compact<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        (self.shielded_ptx_bundle.0.len() as u32).serialize(writer)?;
+        for ptx in self.shielded_ptx_bundle.0.iter() {
+            ptx.serialize_compact(writer)?;
+        }
+        self.transparent_ptx_bundle.serialize(writer)?;
+        self.public_delta.serialize(writer)?;
+        self.chain_id.serialize(writer)?;
+        self.expiry_height.serialize(writer)?;
+        self.signature.serialize(writer)
+    }
+
+    /// Reads a transaction written by `serialize_compact`, resolving the vp vks in `registry`.
+    pub fn deserialize_compact<R: std::io::Read>(
+        reader: &mut R,
+        registry: &VPVerifyingKeyRegistry,
+    ) -> Result<Self, TransactionError> {
+        let ptx_num = u32::deserialize_reader(reader)?;
+        let shielded_ptxs = (0..ptx_num)
+            .map(|_| ShieldedPartialTransaction::deserialize_compact(reader, registry))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            shielded_ptx_bundle: ShieldedPartialTxBundle::new(shielded_ptxs),
+            transparent_ptx_bundle: BorshDeserialize::deserialize_reader(reader)?,
+            public_delta: BorshDeserialize::deserialize_reader(reader)?,
+            chain_id: BorshDeserialize::deserialize_reader(reader)?,
+            expiry_height: BorshDeserialize::deserialize_reader(reader)?,
+            signature: BorshDeserialize::deserialize_reader(reader)?,
+        })
     }
 }
 
@@ -170,6 +396,9 @@ impl rustler::Encoder for Transaction {
             borsh::to_vec(&self.transparent_ptx_bundle)
                 .unwrap_or_default()
                 .encode(env),
+            self.public_delta.encode(env),
+            self.chain_id.encode(env),
+            self.expiry_height.encode(env),
             borsh::to_vec(&self.signature)
                 .unwrap_or_default()
                 .encode(env),
@@ -181,10 +410,22 @@ impl rustler::Encoder for Transaction {
 #[cfg(feature = "nif")]
 impl<'a> Decoder<'a> for Transaction {
     fn decode(term: Term<'a>) -> NifResult<Self> {
-        let (term, shielded_ptx_bundle, transparent_bytes, sig_bytes): (
+        #[allow(clippy::type_complexity)]
+        let (
+            term,
+            shielded_ptx_bundle,
+            transparent_bytes,
+            public_delta,
+            chain_id,
+            expiry_height,
+            sig_bytes,
+        ): (
             atom::Atom,
             ShieldedPartialTxBundle,
             Vec<u8>,
+            Vec<PublicDelta>,
+            u64,
+            Option<u64>,
             Vec<u8>,
         ) = term.decode()?;
         if term == transaction() {
@@ -197,6 +438,9 @@ impl<'a> Decoder<'a> for Transaction {
                 shielded_ptx_bundle,
                 signature,
                 transparent_ptx_bundle,
+                public_delta,
+                chain_id,
+                expiry_height,
             })
         } else {
             Err(rustler::Error::BadArg)
@@ -209,6 +453,7 @@ impl TransactionResult {
         self.anchors.append(&mut result.anchors);
         self.nullifiers.append(&mut result.nullifiers);
         self.output_cms.append(&mut result.output_cms);
+        self.public_delta.append(&mut result.public_delta);
     }
 }
 
@@ -247,12 +492,32 @@ impl ShieldedPartialTxBundle {
         for partial_tx in self.0.iter() {
             partial_tx.execute()?;
         }
+        check_duplicates(self.get_ptx_outputs()?)?;
 
         // Return Nullifiers to check double-spent, ResourceCommitments to store, anchors to check the root-existence
         Ok(TransactionResult {
-            nullifiers: self.get_nullifiers(),
+            nullifiers: self.get_nullifiers()?,
+            output_cms: self.get_output_cms(),
+            anchors: self.get_anchors(),
+            public_delta: vec![],
+        })
+    }
+
+    /// Like `execute`, but the proofs are added to `batch` and only verified when it is finalized.
+    pub fn execute_batched(
+        &self,
+        batch: &mut BatchProofVerifier,
+    ) -> Result<TransactionResult, TransactionError> {
+        for partial_tx in self.0.iter() {
+            partial_tx.execute_batched(batch)?;
+        }
+        check_duplicates(self.get_ptx_outputs()?)?;
+
+        Ok(TransactionResult {
+            nullifiers: self.get_nullifiers()?,
             output_cms: self.get_output_cms(),
             anchors: self.get_anchors(),
+            public_delta: vec![],
         })
     }
 
@@ -263,8 +528,12 @@ impl ShieldedPartialTxBundle {
             .collect()
     }
 
-    pub fn get_nullifiers(&self) -> Vec<Nullifier> {
-        self.0.iter().flat_map(|ptx| ptx.get_nullifiers()).collect()
+    pub fn get_nullifiers(&self) -> Result<Vec<Nullifier>, TransactionError> {
+        let mut nfs = vec![];
+        for ptx in self.0.iter() {
+            nfs.extend(ptx.get_nullifiers()?);
+        }
+        Ok(nfs)
     }
 
     pub fn get_output_cms(&self) -> Vec<ResourceCommitment> {
@@ -274,6 +543,25 @@ impl ShieldedPartialTxBundle {
     pub fn get_anchors(&self) -> Vec<Anchor> {
         self.0.iter().flat_map(|ptx| ptx.get_anchors()).collect()
     }
+
+    // Nullifiers and output cms of every ptx, tagged with the ptx index
+    #[allow(clippy::type_complexity)]
+    fn get_ptx_outputs(
+        &self,
+    ) -> Result<Vec<(PartialTxIndex, Vec<Nullifier>, Vec<ResourceCommitment>)>, TransactionError>
+    {
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(i, ptx)| {
+                Ok((
+                    PartialTxIndex::Shielded(i),
+                    ptx.get_nullifiers()?,
+                    ptx.get_output_cms(),
+                ))
+            })
+            .collect()
+    }
 }
 
 impl TransparentPartialTxBundle {
@@ -293,11 +581,13 @@ impl TransparentPartialTxBundle {
         for partial_tx in self.0.iter() {
             partial_tx.execute()?;
         }
+        check_duplicates(self.get_ptx_outputs()?)?;
 
         Ok(TransactionResult {
-            nullifiers: self.get_nullifiers(),
+            nullifiers: self.get_nullifiers()?,
             output_cms: self.get_output_cms(),
             anchors: self.get_anchors(),
+            public_delta: vec![],
         })
     }
 
@@ -308,8 +598,12 @@ impl TransparentPartialTxBundle {
             .collect()
     }
 
-    pub fn get_nullifiers(&self) -> Vec<Nullifier> {
-        self.0.iter().flat_map(|ptx| ptx.get_nullifiers()).collect()
+    pub fn get_nullifiers(&self) -> Result<Vec<Nullifier>, TransactionError> {
+        let mut nfs = vec![];
+        for ptx in self.0.iter() {
+            nfs.extend(ptx.get_nullifiers()?);
+        }
+        Ok(nfs)
     }
 
     pub fn get_output_cms(&self) -> Vec<ResourceCommitment> {
@@ -319,6 +613,60 @@ impl TransparentPartialTxBundle {
     pub fn get_anchors(&self) -> Vec<Anchor> {
         self.0.iter().flat_map(|ptx| ptx.get_anchors()).collect()
     }
+
+    // Nullifiers and output cms of every ptx, tagged with the ptx index
+    #[allow(clippy::type_complexity)]
+    fn get_ptx_outputs(
+        &self,
+    ) -> Result<Vec<(PartialTxIndex, Vec<Nullifier>, Vec<ResourceCommitment>)>, TransactionError>
+    {
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(i, ptx)| {
+                Ok((
+                    PartialTxIndex::Transparent(i),
+                    ptx.get_nullifiers()?,
+                    ptx.get_output_cms(),
+                ))
+            })
+            .collect()
+    }
+}
+
+// Hashes the number of `items`, then the items.
+fn update_with_len<T, B: AsRef<[u8]>>(
+    h: &mut blake2b_simd::State,
+    items: &[T],
+    to_bytes: impl Fn(&T) -> B,
+) {
+    h.update(&(items.len() as u32).to_le_bytes());
+    items.iter().for_each(|item| {
+        h.update(to_bytes(item).as_ref());
+    });
+}
+
+// Check that no nullifier is revealed twice and no output cm is created twice.
+fn check_duplicates(
+    ptx_outputs: impl IntoIterator<Item = (PartialTxIndex, Vec<Nullifier>, Vec<ResourceCommitment>)>,
+) -> Result<(), TransactionError> {
+    let mut nfs = HashMap::new();
+    let mut cms = HashMap::new();
+    for (index, ptx_nfs, ptx_cms) in ptx_outputs {
+        for nf in ptx_nfs {
+            if let Some(first) = nfs.insert(nf, index) {
+                return Err(TransactionError::DuplicateNullifier(nf, first, index));
+            }
+        }
+        for cm in ptx_cms {
+            if let Some(first) = cms.insert(cm, index) {
+                return Err(TransactionError::DuplicateOutputResourceCommitment(
+                    cm, first, index,
+                ));
+            }
+        }
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -355,21 +703,328 @@ pub mod testing {
         let rng = OsRng;
 
         let shielded_ptx_bundle = create_shielded_ptx_bundle(1);
+        let ptx_ids: Vec<_> = shielded_ptx_bundle.0.iter().map(|ptx| ptx.id()).collect();
 
         #[cfg(feature = "borsh")]
         let transparent_ptx_bundle = create_transparent_ptx_bundle(1);
         #[cfg(not(feature = "borsh"))]
         let transparent_ptx_bundle = TransparentPartialTxBundle::default();
 
-        let tx = Transaction::build(rng, shielded_ptx_bundle, transparent_ptx_bundle).unwrap();
-        let _ret = tx.execute().unwrap();
+        let ctx = ExecutionContext {
+            chain_id: 1,
+            height: 10,
+        };
+        let tx = Transaction::build(
+            rng,
+            shielded_ptx_bundle,
+            transparent_ptx_bundle,
+            vec![],
+            ctx.chain_id,
+            Some(ctx.height),
+        )
+        .unwrap();
+        let _ret = tx.execute(&ctx).unwrap();
+        assert_eq!(tx.verify_batched(&ctx).unwrap(), _ret);
+        assert_ne!(tx.id().unwrap(), tx.auth_digest());
+        // The ptx ids don't depend on the private info removed when building the tx.
+        let tx_ptx_ids: Vec<_> = tx
+            .shielded_ptx_bundle
+            .0
+            .iter()
+            .map(|ptx| ptx.id())
+            .collect();
+        assert_eq!(tx_ptx_ids, ptx_ids);
+
+        // Wrong chain
+        let other_chain = ExecutionContext { chain_id: 2, ..ctx };
+        assert!(matches!(
+            tx.execute(&other_chain),
+            Err(TransactionError::ChainIdMismatch(1, 2))
+        ));
+        // Expired
+        let later = ExecutionContext {
+            height: ctx.height + 1,
+            ..ctx
+        };
+        assert!(matches!(
+            tx.execute(&later),
+            Err(TransactionError::TransactionExpired(10, 11))
+        ));
 
         #[cfg(feature = "borsh")]
         {
             let borsh = borsh::to_vec(&tx).unwrap();
             let de_tx: Transaction = BorshDeserialize::deserialize(&mut borsh.as_ref()).unwrap();
-            let de_ret = de_tx.execute().unwrap();
+            let de_ret = de_tx.execute(&ctx).unwrap();
             assert_eq!(_ret, de_ret);
+            assert_eq!(de_tx.id().unwrap(), tx.id().unwrap());
+            assert_eq!(de_tx.auth_digest(), tx.auth_digest());
         }
     }
+
+    #[test]
+    fn test_verify_batched_rejects_invalid_proofs() {
+        use super::*;
+        use crate::constant::VP_CIRCUIT_CUSTOM_PUBLIC_INPUT_BEGIN_IDX;
+        use crate::proof::Proof;
+        use rand::rngs::OsRng;
+
+        let tx = Transaction::build(
+            OsRng,
+            create_shielded_ptx_bundle(1),
+            TransparentPartialTxBundle::default(),
+            vec![],
+            0,
+            None,
+        )
+        .unwrap();
+        let ctx = ExecutionContext::default();
+        assert!(tx.verify_batched(&ctx).is_ok());
+
+        // Flip the low bit of the last scalar of a compliance proof, so that the proof still
+        // decodes and only the final msm check fails.
+        let mut tampered_compliance_tx = tx.clone();
+        let compliance = &mut tampered_compliance_tx.shielded_ptx_bundle.0[0].compliances[0];
+        let mut proof = compliance.compliance_proof.inner();
+        let last_scalar = proof.len() - 32;
+        proof[last_scalar] ^= 1;
+        compliance.compliance_proof = Proof::new(proof);
+        assert!(tampered_compliance_tx.execute(&ctx).is_err());
+        assert!(matches!(
+            tampered_compliance_tx.verify_batched(&ctx),
+            Err(TransactionError::Proof(_))
+        ));
+
+        // A custom public input isn't checked against the other proofs, only by the vp proof.
+        let mut tampered_vp_tx = tx;
+        let vp_info = &mut tampered_vp_tx.shielded_ptx_bundle.0[0].inputs[0].app_vp_verifying_info;
+        let mut public_inputs = vp_info.public_inputs.to_vec();
+        public_inputs[VP_CIRCUIT_CUSTOM_PUBLIC_INPUT_BEGIN_IDX] += pallas::Base::one();
+        vp_info.public_inputs = public_inputs.into();
+        assert!(tampered_vp_tx.execute(&ctx).is_err());
+        assert!(matches!(
+            tampered_vp_tx.verify_batched(&ctx),
+            Err(TransactionError::Proof(_))
+        ));
+    }
+
+    #[cfg(feature = "borsh")]
+    #[test]
+    fn test_execute_against_ledger() {
+        use super::*;
+        use crate::ledger::{tests::commit_resources, InMemoryLedgerState};
+        use crate::resource::tests::random_resource;
+        use crate::shielded_ptx::testing::create_shielded_ptx_in;
+        use crate::transparent_ptx::testing::create_transparent_ptx_in;
+        use rand::rngs::OsRng;
+
+        // The ptxs spend resources committed to the ledger state, so their anchors are recent roots.
+        let mut state = InMemoryLedgerState::new();
+        let shielded_ptx_bundle =
+            ShieldedPartialTxBundle::new(vec![create_shielded_ptx_in(&mut state)]);
+        let transparent_ptx_bundle =
+            TransparentPartialTxBundle::new(vec![create_transparent_ptx_in(&mut state)]);
+        let tx = Transaction::build(
+            OsRng,
+            shielded_ptx_bundle,
+            transparent_ptx_bundle,
+            vec![],
+            0,
+            None,
+        )
+        .unwrap();
+        let ctx = ExecutionContext::default();
+        let result = tx.execute_against(&mut state, &ctx).unwrap();
+        assert!(result
+            .anchors
+            .iter()
+            .all(|anchor| state.is_known_anchor(anchor)));
+
+        // Replaying the tx spends the same nullifiers again.
+        assert!(matches!(
+            tx.execute_against(&mut state, &ctx),
+            Err(TransactionError::SpentNullifier(_))
+        ));
+
+        // The anchors fall out of a ledger state with a short root history.
+        let mut state = InMemoryLedgerState::with_root_history_size(1);
+        let ptx = create_transparent_ptx_in(&mut state);
+        commit_resources(&mut state, &[random_resource(OsRng)]);
+        let tx = Transaction::build(
+            OsRng,
+            ShieldedPartialTxBundle::default(),
+            TransparentPartialTxBundle::new(vec![ptx]),
+            vec![],
+            0,
+            None,
+        )
+        .unwrap();
+        assert!(matches!(
+            tx.execute_against(&mut state, &ctx),
+            Err(TransactionError::UnknownAnchor(_))
+        ));
+    }
+
+    #[test]
+    fn test_public_delta() {
+        use super::*;
+        use crate::resource::ResourceKind;
+        use halo2_proofs::arithmetic::Field;
+        use rand::rngs::OsRng;
+
+        let mut rng = OsRng;
+        let shielded_ptx_bundle = create_shielded_ptx_bundle(1);
+        let kind = ResourceKind::new(pallas::Base::random(&mut rng), pallas::Base::one());
+
+        // Public deltas that cancel out keep the tx balanced.
+        let public_delta = vec![PublicDelta::new(kind, 7), PublicDelta::new(kind, -7)];
+        let tx = Transaction::build(
+            &mut rng,
+            shielded_ptx_bundle.clone(),
+            TransparentPartialTxBundle::default(),
+            public_delta.clone(),
+            0,
+            None,
+        )
+        .unwrap();
+        let ctx = ExecutionContext::default();
+        assert_eq!(tx.execute(&ctx).unwrap().public_delta, public_delta);
+
+        // A public delta the ptxs don't pay for breaks the balance.
+        let tx = Transaction::build(
+            &mut rng,
+            shielded_ptx_bundle,
+            TransparentPartialTxBundle::default(),
+            vec![PublicDelta::new(kind, 1)],
+            0,
+            None,
+        )
+        .unwrap();
+        assert!(matches!(
+            tx.execute(&ctx),
+            Err(TransactionError::InvalidBindingSignature)
+        ));
+    }
+
+    #[test]
+    fn test_public_delta_fee() {
+        use super::*;
+        use crate::circuit::vp_examples::COMPRESSED_TRIVIAL_VP_VK;
+        use crate::ledger::{tests::commit_resources, InMemoryLedgerState};
+        use crate::ptx_builder::PartialTransactionBuilder;
+        use crate::resource::{tests::random_resource, ResourceValidityPredicates};
+        use rand::rngs::OsRng;
+
+        let mut rng = OsRng;
+        // The ptx consumes a resource without creating one of the same kind, the quantity is
+        // paid as a fee.
+        let fee = 5u64;
+        let mut input_resource = random_resource(&mut rng);
+        input_resource.kind.logic = *COMPRESSED_TRIVIAL_VP_VK;
+        input_resource.quantity = fee;
+        let mut state = InMemoryLedgerState::new();
+        let merkle_path = commit_resources(&mut state, &[input_resource])
+            .pop()
+            .unwrap();
+        let mut builder = PartialTransactionBuilder::new();
+        builder.add_input(
+            input_resource,
+            merkle_path,
+            ResourceValidityPredicates::create_input_padding_resource_vps,
+        );
+        let ptx = builder.build(&mut rng).unwrap();
+
+        let public_delta = vec![PublicDelta::new(input_resource.kind, fee as i64)];
+        let tx = Transaction::build(
+            &mut rng,
+            ShieldedPartialTxBundle::new(vec![ptx.clone()]),
+            TransparentPartialTxBundle::default(),
+            public_delta.clone(),
+            0,
+            None,
+        )
+        .unwrap();
+        let ctx = ExecutionContext::default();
+        assert_eq!(tx.execute(&ctx).unwrap().public_delta, public_delta);
+
+        // Without the fee the ptx doesn't balance.
+        let unpaid_tx = Transaction::build(
+            &mut rng,
+            ShieldedPartialTxBundle::new(vec![ptx]),
+            TransparentPartialTxBundle::default(),
+            vec![],
+            0,
+            None,
+        )
+        .unwrap();
+        assert!(matches!(
+            unpaid_tx.execute(&ctx),
+            Err(TransactionError::InvalidBindingSignature)
+        ));
+
+        // The binding signature covers the public delta.
+        let mut tampered_tx = tx;
+        tampered_tx.public_delta[0].quantity -= 1;
+        assert!(matches!(
+            tampered_tx.execute(&ctx),
+            Err(TransactionError::InvalidBindingSignature)
+        ));
+    }
+
+    #[test]
+    fn test_duplicate_nullifiers_and_output_cms() {
+        use super::{check_duplicates, PartialTxIndex};
+        use crate::commitment_tree::tests::random_commitment;
+        use crate::error::TransactionError;
+        use crate::nullifier::tests::random_nullifier;
+        use rand::rngs::OsRng;
+
+        let mut rng = OsRng;
+        let nf = random_nullifier(&mut rng);
+        let cm = random_commitment(&mut rng);
+
+        let unique = vec![
+            (PartialTxIndex::Shielded(0), vec![nf], vec![cm]),
+            (
+                PartialTxIndex::Transparent(0),
+                vec![random_nullifier(&mut rng)],
+                vec![random_commitment(&mut rng)],
+            ),
+        ];
+        assert!(check_duplicates(unique).is_ok());
+
+        let duplicate_nf = vec![
+            (PartialTxIndex::Shielded(0), vec![nf], vec![cm]),
+            (
+                PartialTxIndex::Transparent(1),
+                vec![nf],
+                vec![random_commitment(&mut rng)],
+            ),
+        ];
+        assert!(matches!(
+            check_duplicates(duplicate_nf),
+            Err(TransactionError::DuplicateNullifier(
+                dup,
+                PartialTxIndex::Shielded(0),
+                PartialTxIndex::Transparent(1)
+            )) if dup == nf
+        ));
+
+        let duplicate_cm = vec![
+            (PartialTxIndex::Shielded(0), vec![nf], vec![cm]),
+            (
+                PartialTxIndex::Shielded(1),
+                vec![random_nullifier(&mut rng)],
+                vec![cm],
+            ),
+        ];
+        assert!(matches!(
+            check_duplicates(duplicate_cm),
+            Err(TransactionError::DuplicateOutputResourceCommitment(
+                dup,
+                PartialTxIndex::Shielded(0),
+                PartialTxIndex::Shielded(1)
+            )) if dup == cm
+        ));
+    }
 }
@@ -1,9 +1,17 @@
 use crate::{
-    circuit::vp_bytecode::ApplicationByteCode, compliance::ComplianceInfo, constant::NUM_RESOURCE,
-    delta_commitment::DeltaCommitment, error::TransactionError, executable::Executable,
-    merkle_tree::Anchor, nullifier::Nullifier, resource::ResourceCommitment,
+    circuit::{vp_bytecode::ApplicationByteCode, vp_diagnostics::ResourceSlot},
+    compliance::ComplianceInfo,
+    constant::TRANSPARENT_PTX_ID_PERSONALIZATION,
+    delta_commitment::DeltaCommitment,
+    error::TransactionError,
+    executable::Executable,
+    merkle_tree::Anchor,
+    nullifier::Nullifier,
+    resource::ResourceCommitment,
+    utils::check_ptx_size,
 };
 
+use blake2b_simd::Params as Blake2bParams;
 use pasta_curves::pallas;
 #[cfg(feature = "serde")]
 use serde;
@@ -15,10 +23,10 @@ use borsh::{BorshDeserialize, BorshSerialize};
 #[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TransparentPartialTransaction {
-    compliances: Vec<ComplianceInfo>,
-    input_resource_app: Vec<ApplicationByteCode>,
-    output_resource_app: Vec<ApplicationByteCode>,
-    hints: Vec<u8>,
+    pub(crate) compliances: Vec<ComplianceInfo>,
+    pub(crate) input_resource_app: Vec<ApplicationByteCode>,
+    pub(crate) output_resource_app: Vec<ApplicationByteCode>,
+    pub(crate) hints: Vec<u8>,
 }
 
 impl TransparentPartialTransaction {
@@ -27,35 +35,80 @@ impl TransparentPartialTransaction {
         input_resource_app: Vec<ApplicationByteCode>,
         output_resource_app: Vec<ApplicationByteCode>,
         hints: Vec<u8>,
-    ) -> Self {
-        assert_eq!(compliances.len(), NUM_RESOURCE);
-        assert_eq!(input_resource_app.len(), NUM_RESOURCE);
-        assert_eq!(output_resource_app.len(), NUM_RESOURCE);
+    ) -> Result<Self, TransactionError> {
+        check_ptx_size(
+            compliances.len(),
+            input_resource_app.len(),
+            output_resource_app.len(),
+        )?;
 
-        Self {
+        Ok(Self {
             compliances,
             input_resource_app,
             output_resource_app,
             hints,
+        })
+    }
+
+    /// A hash of the whole ptx. Everything in a transparent ptx is public and determines its
+    /// execution, so the id covers the resources and the VP bytecode too.
+    pub fn id(&self) -> [u8; 32] {
+        let mut h = Blake2bParams::new()
+            .hash_length(32)
+            .personal(TRANSPARENT_PTX_ID_PERSONALIZATION)
+            .to_state();
+        h.update(&(self.compliances.len() as u32).to_le_bytes());
+        for compliance in self.compliances.iter() {
+            h.update(&compliance.to_bytes());
+        }
+        for apps in [&self.input_resource_app, &self.output_resource_app] {
+            h.update(&(apps.len() as u32).to_le_bytes());
+            for app in apps.iter() {
+                h.update(&app.to_bytes());
+            }
         }
+        h.update(&(self.hints.len() as u32).to_le_bytes());
+        h.update(&self.hints);
+        h.finalize().as_bytes().try_into().unwrap()
     }
 }
 
 impl Executable for TransparentPartialTransaction {
     fn execute(&self) -> Result<(), TransactionError> {
+        // A deserialized ptx doesn't go through `new`.
+        check_ptx_size(
+            self.compliances.len(),
+            self.input_resource_app.len(),
+            self.output_resource_app.len(),
+        )?;
+
         // check VPs, nullifiers, and resource commitments
-        let compliance_nfs = self.get_nullifiers();
+        let compliance_nfs = self.get_nullifiers()?;
         let compliance_cms = self.get_output_cms();
-        for (vp, nf) in self.input_resource_app.iter().zip(compliance_nfs.iter()) {
-            let owned_resource_id = vp.verify_transparently(&compliance_nfs, &compliance_cms)?;
+        for (i, (vp, nf)) in self
+            .input_resource_app
+            .iter()
+            .zip(compliance_nfs.iter())
+            .enumerate()
+        {
+            let owned_resource_id = vp
+                .verify_transparently(&compliance_nfs, &compliance_cms)
+                .map_err(|e| e.with_vp_owner(ResourceSlot::Input(i)))?;
             // Check all resources are checked
             if owned_resource_id != nf.inner() {
                 return Err(TransactionError::InconsistentOwnedResourceID);
             }
         }
 
-        for (vp, cm) in self.output_resource_app.iter().zip(compliance_cms.iter()) {
-            let owned_resource_id = vp.verify_transparently(&compliance_nfs, &compliance_cms)?;
+        for (i, (vp, cm)) in self
+            .output_resource_app
+            .iter()
+            .zip(compliance_cms.iter())
+            .enumerate()
+        {
+            let owned_resource_id = vp
+                .verify_transparently(&compliance_nfs, &compliance_cms)
+                .map_err(|e| e.with_vp_owner(ResourceSlot::Output(i)))?;
             // Check all resources are checked
             if owned_resource_id != cm.inner() {
                 return Err(TransactionError::InconsistentOwnedResourceID);
@@ -66,7 +119,7 @@ impl Executable for TransparentPartialTransaction {
     }
 
     // get nullifiers from compliances
-    fn get_nullifiers(&self) -> Vec<Nullifier> {
+    fn get_nullifiers(&self) -> Result<Vec<Nullifier>, TransactionError> {
         self.compliances
             .iter()
             .map(|compliance| compliance.get_input_resource_nullifier())
@@ -89,11 +142,17 @@ impl Executable for TransparentPartialTransaction {
     }
 
     fn get_anchors(&self) -> Vec<Anchor> {
-        // TODO: We have easier way to check the anchor in transparent scenario, but keep consistent with shielded right now.
-        // TODO: we can skip the root if the is_ephemeral flag is true?
+        // As in the shielded ptx, the anchor of an ephemeral input resource is the one it was
+        // created with: it is not checked against the resource but must still be a known root.
         self.compliances
             .iter()
-            .map(|compliance| compliance.calculate_root())
+            .map(|compliance| {
+                if compliance.is_input_ephemeral() {
+                    compliance.input_anchor
+                } else {
+                    compliance.calculate_root()
+                }
+            })
             .collect()
     }
 }
@@ -103,12 +162,20 @@ impl Executable for TransparentPartialTransaction {
 pub mod testing {
     use crate::{
         circuit::vp_examples::TrivialValidityPredicateCircuit,
-        constant::TAIGA_COMMITMENT_TREE_DEPTH, merkle_tree::MerklePath,
-        resource::tests::random_resource, transparent_ptx::*,
+        ledger::{tests::commit_resources, InMemoryLedgerState},
+        resource::tests::random_resource,
+        transparent_ptx::*,
     };
     use rand::rngs::OsRng;
 
     pub fn create_transparent_ptx() -> TransparentPartialTransaction {
+        create_transparent_ptx_in(&mut InMemoryLedgerState::new())
+    }
+
+    /// Creates a ptx spending resources committed to `state`.
+    pub fn create_transparent_ptx_in(
+        state: &mut InMemoryLedgerState,
+    ) -> TransparentPartialTransaction {
         let mut rng = OsRng;
         // construct resources
         let input_resource_1 = random_resource(&mut rng);
@@ -118,15 +185,6 @@ pub mod testing {
             resource.quantity = input_resource_1.quantity;
             resource
         };
-        let merkle_path_1 = MerklePath::random(&mut rng, TAIGA_COMMITMENT_TREE_DEPTH);
-        let compliance_1 = ComplianceInfo::new(
-            input_resource_1,
-            merkle_path_1,
-            None,
-            &mut output_resource_1,
-            &mut rng,
-        );
-
         let input_resource_2 = random_resource(&mut rng);
         let mut output_resource_2 = {
             let mut resource = random_resource(&mut rng);
@@ -134,10 +192,19 @@ pub mod testing {
             resource.quantity = input_resource_2.quantity;
             resource
         };
-        let merkle_path_2 = MerklePath::random(&mut rng, TAIGA_COMMITMENT_TREE_DEPTH);
+
+        // commit the input resources to the ledger state
+        let merkle_paths = commit_resources(state, &[input_resource_1, input_resource_2]);
+        let compliance_1 = ComplianceInfo::new(
+            input_resource_1,
+            merkle_paths[0].clone(),
+            None,
+            &mut output_resource_1,
+            &mut rng,
+        );
         let compliance_2 = ComplianceInfo::new(
             input_resource_2,
-            merkle_path_2,
+            merkle_paths[1].clone(),
             None,
             &mut output_resource_2,
             &mut rng,
@@ -190,5 +257,141 @@ pub mod testing {
             vec![output_resource_1_app, output_resource_2_app],
             vec![],
         )
+        .unwrap()
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "borsh")]
+pub mod tests {
+    use super::{testing::create_transparent_ptx_in, TransparentPartialTransaction};
+    use crate::{
+        circuit::{vp_bytecode::ApplicationByteCode, vp_examples::TrivialValidityPredicateCircuit},
+        compliance::ComplianceInfo,
+        constant::NUM_RESOURCE,
+        error::TransactionError,
+        executable::Executable,
+        ledger::{InMemoryLedgerState, LedgerState},
+        merkle_tree::Anchor,
+        resource::tests::random_resource,
+        transaction::TransactionResult,
+    };
+    use ff::Field;
+    use pasta_curves::pallas;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn test_ephemeral_input_anchor() {
+        let mut rng = OsRng;
+        let mut state = InMemoryLedgerState::new();
+        let mut ptx = create_transparent_ptx_in(&mut state);
+        assert_eq!(ptx.get_anchors(), vec![state.root(), state.root()]);
+
+        // The anchor of an ephemeral input resource is the one it was created with.
+        let mut input_resource = random_resource(&mut rng);
+        input_resource.is_ephemeral = true;
+        let mut output_resource = random_resource(&mut rng);
+        ptx.compliances[1] = ComplianceInfo::new_ephemeral_input(
+            input_resource,
+            state.root(),
+            &mut output_resource,
+            &mut rng,
+        )
+        .unwrap();
+        assert_eq!(ptx.get_anchors(), vec![state.root(), state.root()]);
+
+        // It must still be a known root.
+        let unknown_anchor = Anchor::from(pallas::Base::random(&mut rng));
+        ptx.compliances[1] = ComplianceInfo::new_ephemeral_input(
+            input_resource,
+            unknown_anchor,
+            &mut output_resource,
+            &mut rng,
+        )
+        .unwrap();
+        let result = TransactionResult {
+            anchors: ptx.get_anchors(),
+            nullifiers: vec![],
+            output_cms: vec![],
+            public_delta: vec![],
+        };
+        assert!(matches!(
+            state.validate(&result),
+            Err(TransactionError::UnknownAnchor(anchor)) if anchor == unknown_anchor
+        ));
+    }
+
+    #[test]
+    fn test_ptx_size() {
+        let mut ptx = create_transparent_ptx_in(&mut InMemoryLedgerState::new());
+        assert!(matches!(
+            TransparentPartialTransaction::new(
+                ptx.compliances[1..].to_vec(),
+                ptx.input_resource_app.clone(),
+                ptx.output_resource_app.clone(),
+                vec![],
+            ),
+            Err(TransactionError::InvalidPartialTxSize(n, _, _)) if n == NUM_RESOURCE - 1
+        ));
+
+        // A deserialized ptx is checked when it's executed.
+        ptx.output_resource_app.pop();
+        assert!(matches!(
+            ptx.execute(),
+            Err(TransactionError::InvalidPartialTxSize(_, _, n)) if n == NUM_RESOURCE - 1
+        ));
+    }
+
+    #[test]
+    fn test_transparent_ptx_id() {
+        let ptx = create_transparent_ptx_in(&mut InMemoryLedgerState::new());
+        assert_eq!(ptx.id(), ptx.clone().id());
+
+        // The VP bytecode and the hints don't change the nullifiers, cms, deltas or anchors,
+        // but they change the id.
+        let mut other_ptx = ptx.clone();
+        other_ptx.input_resource_app[0]
+            .app_vp_bytecode
+            .inputs
+            .push(0);
+        assert_ne!(other_ptx.id(), ptx.id());
+
+        let mut other_ptx = ptx.clone();
+        other_ptx.hints.push(0);
+        assert_ne!(other_ptx.id(), ptx.id());
+    }
+
+    #[test]
+    fn test_missing_nullifier_key() {
+        let ptx = create_transparent_ptx_in(&mut InMemoryLedgerState::new());
+
+        // The nullifier of a compliance input resource can't be derived without its key.
+        let mut compliance_ptx = ptx.clone();
+        let input_resource = &mut compliance_ptx.compliances[0].input_resource;
+        input_resource.nk_container = input_resource.nk_container.to_commitment();
+        assert!(matches!(
+            compliance_ptx.execute(),
+            Err(TransactionError::MissingTransparentResourceNullifierKey)
+        ));
+
+        // Neither can the nullifiers in the public inputs of a vp.
+        let mut vp_ptx = ptx.clone();
+        let mut input_resources: [_; NUM_RESOURCE] =
+            std::array::from_fn(|i| ptx.compliances[i].input_resource);
+        let output_resources = std::array::from_fn(|i| ptx.compliances[i].output_resource);
+        input_resources[1].nk_container = input_resources[1].nk_container.to_commitment();
+        let app_vp = TrivialValidityPredicateCircuit::new(
+            ptx.compliances[0]
+                .get_input_resource_nullifier()
+                .unwrap()
+                .inner(),
+            input_resources,
+            output_resources,
+        );
+        vp_ptx.input_resource_app[0] = ApplicationByteCode::new(app_vp.to_bytecode(), vec![]);
+        assert!(matches!(
+            vp_ptx.execute(),
+            Err(TransactionError::MissingTransparentResourceNullifierKey)
+        ));
     }
 }
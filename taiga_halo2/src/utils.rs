@@ -1,4 +1,5 @@
-use crate::constant::{POSEIDON_TO_FIELD_U_0_POSTFIX, POSEIDON_TO_FIELD_U_1_POSTFIX};
+use crate::constant::{NUM_RESOURCE, POSEIDON_TO_FIELD_U_0_POSTFIX, POSEIDON_TO_FIELD_U_1_POSTFIX};
+use crate::error::TransactionError;
 use halo2_gadgets::poseidon::primitives as poseidon;
 use halo2_proofs::arithmetic::CurveAffine;
 use pasta_curves::{
@@ -117,3 +118,36 @@ pub fn read_point<R: std::io::Read>(reader: &mut R) -> std::io::Result<pallas::P
     Option::from(pallas::Point::from_bytes(&bytes))
         .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid point"))
 }
+
+/// Returns true if `b` is a reordering of `a`.
+pub fn is_permutation<T: PartialEq>(a: &[T], b: &[T]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut matched = vec![false; b.len()];
+    a.iter().all(
+        |x| match (0..b.len()).find(|&j| !matched[j] && b[j] == *x) {
+            Some(j) => {
+                matched[j] = true;
+                true
+            }
+            None => false,
+        },
+    )
+}
+
+/// Checks a ptx has NUM_RESOURCE compliances, input resource VPs and output resource VPs.
+pub(crate) fn check_ptx_size(
+    compliances: usize,
+    inputs: usize,
+    outputs: usize,
+) -> Result<(), TransactionError> {
+    if compliances != NUM_RESOURCE || inputs != NUM_RESOURCE || outputs != NUM_RESOURCE {
+        return Err(TransactionError::InvalidPartialTxSize(
+            compliances,
+            inputs,
+            outputs,
+        ));
+    }
+    Ok(())
+}
@@ -1,7 +1,9 @@
 use crate::constant::VP_COMMITMENT_PERSONALIZATION;
+use crate::utils::poseidon_hash;
 use blake2s_simd::Params;
 use byteorder::{ByteOrder, LittleEndian};
 use ff::PrimeField;
+use pasta_curves::pallas;
 #[cfg(feature = "nif")]
 use rustler::NifTuple;
 #[cfg(feature = "serde")]
@@ -24,6 +26,17 @@ impl ValidityPredicateCommitment {
         Self(hash.as_bytes().to_vec())
     }
 
+    /// Commits to a list of dynamic vps. An empty list is committed to with the default commitment.
+    /// The vks of the dynamic vps are public in a ptx, so the commitment takes no randomness: the
+    /// verifier recomputes it from the vks to check the dynamic vps are the ones the app vp requires.
+    pub fn commit_dynamic_vps(vps: &[pallas::Base]) -> Self {
+        if vps.is_empty() {
+            Self::default()
+        } else {
+            Self::commit(&dynamic_vps_digest(vps), &pallas::Base::zero())
+        }
+    }
+
     pub fn to_bytes(&self) -> [u8; 32] {
         self.0.clone().try_into().unwrap()
     }
@@ -51,3 +64,14 @@ impl Default for ValidityPredicateCommitment {
         ValidityPredicateCommitment([0u8; 32].to_vec())
     }
 }
+
+/// Returns the digest of a list of dynamic vps(their compressed vks):
+/// `digest([vp_1, ..., vp_n]) = Poseidon(vp_1, digest([vp_2, ..., vp_n]))` and `digest([]) = 0`.
+///
+/// A VP circuit can commit to any number of dynamic vps with a fixed circuit, e.g. it can constrain
+/// the first one and take the digest of the remaining ones as a witness.
+pub fn dynamic_vps_digest(vps: &[pallas::Base]) -> pallas::Base {
+    vps.iter().rev().fold(pallas::Base::zero(), |digest, vp| {
+        poseidon_hash(*vp, digest)
+    })
+}
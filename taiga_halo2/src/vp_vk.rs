@@ -4,6 +4,7 @@ use pasta_curves::{
     group::ff::{FromUniformBytes, PrimeField},
     pallas, vesta,
 };
+use std::collections::HashMap;
 use std::hash::Hash;
 
 #[derive(Debug, Clone)]
@@ -72,6 +73,42 @@ impl PartialEq for ValidityPredicateVerifyingKey {
 
 impl Eq for ValidityPredicateVerifyingKey {}
 
+/// The vks a verifier knows, indexed by their compressed vk. Compactly encoded ptxs only carry
+/// the compressed vks, which are resolved through the registry.
+#[derive(Debug, Clone, Default)]
+pub struct VPVerifyingKeyRegistry {
+    vks: HashMap<[u8; 32], VerifyingKey<vesta::Affine>>,
+}
+
+impl VPVerifyingKeyRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the vk and returns its compressed vk.
+    pub fn register(&mut self, vk: VerifyingKey<vesta::Affine>) -> pallas::Base {
+        let compressed = ValidityPredicateVerifyingKey::from_vk(vk.clone()).get_compressed();
+        self.vks.insert(compressed.to_repr(), vk);
+        compressed
+    }
+
+    pub fn get(&self, compressed: &pallas::Base) -> Option<&VerifyingKey<vesta::Affine>> {
+        self.vks.get(&compressed.to_repr())
+    }
+
+    pub fn contains(&self, compressed: &pallas::Base) -> bool {
+        self.vks.contains_key(&compressed.to_repr())
+    }
+
+    pub fn len(&self) -> usize {
+        self.vks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vks.is_empty()
+    }
+}
+
 #[test]
 fn test_vpd_hashing() {
     use crate::circuit::vp_examples::tests::random_trivial_vp_circuit;